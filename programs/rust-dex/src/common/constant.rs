@@ -4,3 +4,27 @@ pub const MAX_TOKEN_MINTS: usize = 32;
 pub const ORDER_HEAP_CAPACITY: usize = 16; // Capacity of the order heap
 
 pub const MAX_EVENTS: usize = 8;
+
+pub const MAX_PENDING_EVENTS: usize = 60; // Capacity of a user's EventList ring buffer, sized to fit `EventList::SPACE`
+
+pub const MAX_MARKET_EVENTS: usize = 64; // Capacity of a market's event log ring buffer
+
+pub const MAX_ROUTE_HOPS: usize = 3; // Max number of market orders chained by swap_route
+
+pub const BPS_DENOMINATOR: u64 = 10_000; // Basis points in 100%
+
+pub const MAX_FEE_BPS: u16 = 1_000; // Highest maker or taker fee a market may charge, 10%
+
+pub const MAX_REFERRAL_SHARE_BPS: u16 = 5_000; // Highest share of taker fees paid to referrers, 50%
+
+pub const MAX_PRICE_BAND_BPS: u16 = 5_000; // Widest price band a market may set, ±50% of its reference price
+
+pub const MAX_FEE_TIERS: usize = 8; // Entries in a quote mint's fee tier table
+
+pub const VOLUME_WINDOW_SECS: i64 = 30 * 24 * 60 * 60; // Length of one trailing volume window, 30 days
+
+pub const MAX_GOVERNANCE_SIGNERS: usize = 10; // Members of the governance multisig
+
+pub const MAX_PROPOSAL_ACCOUNTS: usize = 16; // Accounts of the instruction a proposal executes
+
+pub const MAX_PROPOSAL_DATA: usize = 256; // Bytes of instruction data a proposal executes
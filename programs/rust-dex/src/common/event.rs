@@ -1,158 +1,102 @@
 use anchor_lang::prelude::*;
 
-use crate::{common::{OrderRequest, OrderSide, OrderType}, OrderNode};
+use crate::common::{OrderSide, OrderType};
 
-#[event]
-pub struct AcceptedOrderEvent {
-    pub order_id: u64,
-    pub owner: Pubkey,
-    pub base_token: Pubkey,
-    pub quote_token: Pubkey,
-    pub price: f64,
-    pub amount: u64,
-    pub order_type: String, // "Limit" or "Market"
-    pub side: String, // "Buy" or "Sell"
-    pub timestamp: i64,
-}
+/// Layout version of the order lifecycle events, bumped whenever one changes.
+/// Orders never expire, they stay on the book until filled or cancelled, so
+/// the lifecycle has no expiry event.
+pub const ORDER_EVENT_VERSION: u8 = 1;
 
-impl AcceptedOrderEvent {
-    pub fn from_order_request(order: &OrderRequest) -> Self {
-        Self {
-            order_id: order.id,
-            owner: order.owner,
-            base_token: if order.order_side == OrderSide::Buy { order.buy_token } else { order.sell_token },
-            quote_token: if order.order_side == OrderSide::Buy { order.sell_token } else { order.buy_token },
-            price: order.sell_quantity as f64 / order.buy_quantity as f64, // Assuming price is calculated as sell/buy
-            amount: if order.order_side == OrderSide::Buy {
-                order.buy_quantity
-            } else {
-                order.sell_quantity
-            },
-            order_type: if order.order_type == OrderType::Limit { "Limit".to_string() } else { "Market".to_string() },
-            side: if order.order_side == OrderSide::Buy { "Buy".to_string() } else { "Sell".to_string() },
-            timestamp: Clock::get().unwrap().unix_timestamp,
-        }
-    }
-}
-
-#[event]
-pub struct CanceledOrderEvent {
+/// Fields shared by every order lifecycle event. `seq_num` increases by one
+/// with each lifecycle event across all markets, and `market` is the address of
+/// the market's `Market` account. Quantities and prices of the events are in
+/// the market's base/quote terms.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct OrderEventHeader {
+    pub version: u8,
+    pub seq_num: u64,
+    pub market: Pubkey,
     pub order_id: u64,
     pub owner: Pubkey,
-    pub buy_token: Pubkey,
-    pub sell_token: Pubkey,
-    pub sell_quantity: u64,
-    pub buy_quantity: u64,
-    pub order_type: String, // "Limit" or "Market"
+    pub side: OrderSide,
     pub timestamp: i64,
 }
 
-impl CanceledOrderEvent {
-    pub fn from_order_node(order: &OrderNode, order_type: OrderType) -> Self {
-        Self {
-            order_id: order.id,
-            owner: order.owner,
-            buy_token: order.buy_token,
-            sell_token: order.sell_token,
-            sell_quantity: order.sell_quantity,
-            buy_quantity: order.buy_quantity,
-            order_type: if order_type == OrderType::Limit { "Limit".to_string() } else { "Market".to_string() },
-            timestamp: Clock::get().unwrap().unix_timestamp,
-        }
-    }
-    
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, Clone, Copy, Eq, PartialEq)]
+pub enum OrderRole {
+    Maker,
+    Taker,
 }
 
-#[event]
-pub struct FilledOrderEvent {
-    pub order_id: u64,
-    pub owner: Pubkey,
-    pub buy_token: Pubkey,
-    pub sell_token: Pubkey,
-    pub sell_quantity: u64,
-    pub buy_quantity: u64,
-    pub order_type: String, // "Limit" or "Market"
-    pub timestamp: i64,
+/// Why the unmatched part of an order was handed back to its owner.
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, Clone, Copy, Eq, PartialEq)]
+pub enum RollbackReason {
+    NoMatch,
+    TooManyEvents,
+    OrderHeapFull,
+    PriceBandBreached,
 }
 
-impl FilledOrderEvent {
-    pub fn from_order_node(order: &OrderNode, order_type: OrderType) -> Self {
-        Self {
-            order_id: order.id,
-            owner: order.owner,
-            buy_token: order.buy_token,
-            sell_token: order.sell_token,
-            sell_quantity: order.sell_quantity,
-            buy_quantity: order.buy_quantity,
-            order_type: if order_type == OrderType::Limit { "Limit".to_string() } else { "Market".to_string() },
-            timestamp: Clock::get().unwrap().unix_timestamp,
-        }
-    }
+/// An order was accepted by the matching engine. `price` is 0 for market orders.
+#[event]
+pub struct OrderPlacedEvent {
+    pub header: OrderEventHeader,
+    pub order_type: OrderType,
+    pub price: f64,
+    pub base_quantity: u64,
+    pub quote_quantity: u64,
 }
 
+/// What is left of a limit order after matching was added to the book.
 #[event]
-pub struct PartiallyFilledOrderEvent {
-    pub order_id: u64,
-    pub owner: Pubkey,
-    pub buy_token: Pubkey,
-    pub sell_token: Pubkey,
-    pub sell_quantity: u64,
-    pub buy_quantity: u64,
-    pub order_type: String, // "Limit" or "Market"
-    pub timestamp: i64,
+pub struct OrderRestedEvent {
+    pub header: OrderEventHeader,
+    pub price: f64,
+    pub base_quantity: u64,
+    pub quote_quantity: u64,
 }
 
-impl PartiallyFilledOrderEvent {
-    pub fn from_order_node(
-        order_id: u64,
-        owner: Pubkey,
-        buy_token: Pubkey,
-        sell_token: Pubkey,
-        buy_quantity: u64,
-        sell_quantity: u64,
-        order_type: OrderType,
-    ) -> Self {
-        Self {
-            order_id,
-            owner,
-            buy_token,
-            sell_token,
-            buy_quantity,
-            sell_quantity,
-            order_type: if order_type == OrderType::Limit { "Limit".to_string() } else { "Market".to_string() },
-            timestamp: Clock::get().unwrap().unix_timestamp,
-        }
-    }
+/// One side of a fill. Every fill emits one event for the taker and one for
+/// the maker, each naming the other as counterparty. `fee` is what that side
+/// pays in `fee_mint`, the token it receives. `rebate` is what a maker in a
+/// rebate tier is paid back in the token it sells, 0 for takers.
+/// `referral_fee` is the part of a taker's fee paid to its referrer.
+#[event]
+pub struct OrderFilledEvent {
+    pub header: OrderEventHeader,
+    pub order_type: OrderType,
+    pub role: OrderRole,
+    pub counterparty: Pubkey,
+    pub counterparty_order_id: u64,
+    pub price: f64,
+    pub base_quantity: u64,
+    pub quote_quantity: u64,
+    pub remaining_base: u64,
+    pub remaining_quote: u64,
+    pub fee: u64,
+    pub fee_mint: Pubkey,
+    pub rebate: u64,
+    pub referral_fee: u64,
 }
 
+/// A resting order was removed by its owner, with what it still had open.
 #[event]
-pub struct NoMatchedOrderEvent {
-    pub order_id: u64,
-    pub owner: Pubkey,
-    pub buy_token: Pubkey,
-    pub sell_token: Pubkey,
-    pub sell_quantity: u64,
-    pub buy_quantity: u64,
-    pub order_type: String, // "Limit" or "Market"
-    pub timestamp: i64,
+pub struct OrderCancelledEvent {
+    pub header: OrderEventHeader,
+    pub base_quantity: u64,
+    pub quote_quantity: u64,
 }
 
-impl NoMatchedOrderEvent {
-    pub fn from_order_node(order: &OrderNode, order_type: OrderType) -> Self {
-        Self {
-            order_id: order.id,
-            owner: order.owner,
-            buy_token: order.buy_token,
-            sell_token: order.sell_token,
-            sell_quantity: order.sell_quantity,
-            buy_quantity: order.buy_quantity,
-            order_type: if order_type == OrderType::Limit { "Limit".to_string() } else { "Market".to_string() },
-            timestamp: Clock::get().unwrap().unix_timestamp,
-        }
-    }
+/// The part of an order that could neither be matched nor rested.
+#[event]
+pub struct OrderRolledBackEvent {
+    pub header: OrderEventHeader,
+    pub order_type: OrderType,
+    pub reason: RollbackReason,
+    pub base_quantity: u64,
+    pub quote_quantity: u64,
 }
 
-
 #[event]
 pub struct InternalErrorEvent {
     pub error_message: String,
@@ -166,4 +110,22 @@ impl InternalErrorEvent {
             timestamp: Clock::get().unwrap().unix_timestamp,
         }
     }
-}
\ No newline at end of file
+}
+
+#[event]
+pub struct VaultAuditedEvent {
+    pub mint: Pubkey,
+    pub vault_token_account: Pubkey,
+    pub total_balance: u64,
+    /// Tokens the vault token account holds beyond `total_balance`
+    pub surplus: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct FeesCollectedEvent {
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub destination: Pubkey,
+    pub timestamp: i64,
+}
@@ -1,4 +1,5 @@
-use anchor_lang::prelude::Pubkey;
+use anchor_lang::prelude::*;
+use crate::common::BPS_DENOMINATOR;
 
 
 #[macro_export]
@@ -9,20 +10,51 @@ macro_rules! market_seeds {
     };
 }
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+/// Encoded as a single byte in instruction data and events.
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, Clone, Copy, Eq, PartialEq)]
 #[repr(u8)]
 pub enum OrderType {
     Limit,
     Market
 }
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+/// Side of the base token the order takes: `Buy` pays in quote, `Sell` pays in base.
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, Clone, Copy, Eq, PartialEq)]
 #[repr(u8)]
 pub enum OrderSide {
     Buy,
     Sell
 }
 
+/// What a market accepts. `PostOnly` takes limit orders that rest without
+/// matching, `CancelOnly` lets orders leave and fills settle, `Halted` freezes
+/// the market's book and the settlement of its fills. `Delisted` works like
+/// `CancelOnly` for good, with keepers cancelling what is left on the book
+/// until the market can be closed.
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, Default, Clone, Copy, Eq, PartialEq)]
+#[repr(u8)]
+pub enum MarketStatus {
+    #[default]
+    Active,
+    CancelOnly,
+    PostOnly,
+    Halted,
+    Delisted,
+}
+
+impl MarketStatus {
+    /// Unknown bytes read as `Halted`, so a corrupt status never opens a market.
+    pub fn from_u8(status: u8) -> Self {
+        match status {
+            0 => MarketStatus::Active,
+            1 => MarketStatus::CancelOnly,
+            2 => MarketStatus::PostOnly,
+            4 => MarketStatus::Delisted,
+            _ => MarketStatus::Halted,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct OrderRequest {
     pub id: u64,
@@ -33,7 +65,7 @@ pub struct OrderRequest {
     pub owner: Pubkey,
     pub timestamp: i64,
     pub order_type: OrderType,
-    pub order_side: OrderSide
+    pub fee_discount: FeeDiscount,
 }
 
 impl OrderRequest {
@@ -46,7 +78,7 @@ impl OrderRequest {
         owner: Pubkey,
         timestamp: i64,
         order_type: OrderType,
-        order_side: OrderSide
+        fee_discount: FeeDiscount,
     ) -> Self {
         Self {
             id,
@@ -57,7 +89,192 @@ impl OrderRequest {
             owner,
             timestamp,
             order_type,
-            order_side
+            fee_discount,
+        }
+    }
+}
+/// Converts `amount` at `price` into the other token of the market. Returns
+/// `None` unless the price is a positive finite number and the result is a
+/// non-zero amount that fits in a `u64`.
+pub fn amount_at_price(amount: u64, price: f64) -> Option<u64> {
+    if !price.is_finite() || price <= 0.0 {
+        return None;
+    }
+    let value = amount as f64 * price;
+    if value < 1.0 || value >= u64::MAX as f64 {
+        return None;
+    }
+    Some(value as u64)
+}
+
+/// Maker and taker fee rates of a market, in basis points of what each side
+/// of a fill receives.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub struct FeeRates {
+    pub maker_fee_bps: u16,
+    pub taker_fee_bps: u16,
+}
+
+impl FeeRates {
+    pub fn maker_fee(&self, amount: u64) -> u64 {
+        fee_of(amount, self.maker_fee_bps)
+    }
+
+    pub fn taker_fee(&self, amount: u64) -> u64 {
+        fee_of(amount, self.taker_fee_bps)
+    }
+
+    /// Fees of a fill where the taker buys `buy_quantity` and the maker buys
+    /// `sell_quantity`, after each side's fee tier discount. A maker discount
+    /// above the maker fee turns into a rebate in the token the maker sold,
+    /// paid out of the taker fee and never more than it. The taker's referrer
+    /// gets `referral_share_bps` of what is left of the taker fee.
+    pub fn fill_fees(&self, buy_quantity: u64, sell_quantity: u64, taker_discount_bps: u16, maker_discount_bps: u16, referral_share_bps: u16) -> FillFees {
+        let taker_fee = fee_of(buy_quantity, self.taker_fee_bps.saturating_sub(taker_discount_bps));
+        let maker_fee = fee_of(sell_quantity, self.maker_fee_bps.saturating_sub(maker_discount_bps));
+        let rebate_bps = maker_discount_bps.saturating_sub(self.maker_fee_bps);
+        let maker_rebate = ((buy_quantity as u128 * rebate_bps as u128 / BPS_DENOMINATOR as u128) as u64).min(taker_fee);
+        let referral_fee = ((taker_fee - maker_rebate) as u128 * referral_share_bps as u128 / BPS_DENOMINATOR as u128) as u64;
+        FillFees { taker_fee, maker_fee, maker_rebate, referral_fee }
+    }
+
+    /// The smallest amount a taker has to buy to keep at least `net` once the
+    /// taker fee is taken out of it.
+    pub fn gross_up_taker(&self, net: u64) -> Option<u64> {
+        let kept = BPS_DENOMINATOR.checked_sub(self.taker_fee_bps as u64).filter(|kept| *kept > 0)?;
+        u64::try_from((net as u128 * BPS_DENOMINATOR as u128).div_ceil(kept as u128)).ok()
+    }
+}
+
+/// Basis points a user's fee tier takes off a market's base fee rates.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub struct FeeDiscount {
+    pub maker_bps: u16,
+    pub taker_bps: u16,
+}
+
+/// The prices a market lets fills happen at: within `band_bps` of
+/// `reference_price`, in quote per base. No band applies while `band_bps` is
+/// 0 or before the market has a reference price.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct PriceBand {
+    pub base_mint: Pubkey,
+    pub reference_price: f64,
+    pub band_bps: u16,
+}
+
+impl PriceBand {
+    pub fn contains(&self, price: f64) -> bool {
+        if self.band_bps == 0 || self.reference_price <= 0.0 {
+            return true;
+        }
+        let width = self.reference_price * self.band_bps as f64 / BPS_DENOMINATOR as f64;
+        (price - self.reference_price).abs() <= width
+    }
+
+    /// Whether an order buying `buy_token` at `price` lies outside the band on
+    /// the side that favours whoever trades against it: an ask below the band
+    /// or a bid above it.
+    pub fn favours_taker(&self, buy_token: &Pubkey, price: f64) -> bool {
+        if self.contains(price) {
+            return false;
+        }
+        // Asks buy quote
+        (price < self.reference_price) == (*buy_token != self.base_mint)
+    }
+
+    /// The price, in quote per base, of an order buying `buy_quantity` of
+    /// `buy_token` for `sell_quantity` of the other token.
+    pub fn price_of(&self, buy_token: &Pubkey, buy_quantity: u64, sell_quantity: u64) -> f64 {
+        if *buy_token == self.base_mint {
+            sell_quantity as f64 / buy_quantity as f64
+        } else {
+            buy_quantity as f64 / sell_quantity as f64
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub struct FillFees {
+    pub taker_fee: u64,
+    pub maker_fee: u64,
+    pub maker_rebate: u64,
+    pub referral_fee: u64,
+}
+
+/// `bps` of `amount`, rounded up so that splitting a fill never lowers its fee.
+fn fee_of(amount: u64, bps: u16) -> u64 {
+    (amount as u128 * bps as u128).div_ceil(BPS_DENOMINATOR as u128) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fees_round_up() {
+        let rates = FeeRates { maker_fee_bps: 10, taker_fee_bps: 25 };
+        assert_eq!(rates.maker_fee(10_000), 10);
+        assert_eq!(rates.maker_fee(1), 1);
+        assert_eq!(rates.taker_fee(400), 1);
+        assert_eq!(rates.taker_fee(0), 0);
+        assert_eq!(FeeRates::default().taker_fee(u64::MAX), 0);
+    }
+
+    #[test]
+    fn test_price_band_contains() {
+        let base_mint = Pubkey::new_unique();
+        let band = PriceBand { base_mint, reference_price: 100.0, band_bps: 1_000 };
+        assert!(band.contains(90.0) && band.contains(110.0));
+        assert!(!band.contains(89.9) && !band.contains(110.1));
+        assert!(PriceBand { band_bps: 0, ..band }.contains(1_000.0));
+        assert!(PriceBand { reference_price: 0.0, ..band }.contains(1_000.0));
+
+        // Buying 2 base for 210 quote, or selling them for it, is 105 quote per base
+        let quote_mint = Pubkey::new_unique();
+        assert_eq!(band.price_of(&base_mint, 2, 210), 105.0);
+        assert_eq!(band.price_of(&quote_mint, 210, 2), 105.0);
+    }
+
+    #[test]
+    fn test_price_band_favours_taker() {
+        let base_mint = Pubkey::new_unique();
+        let quote_mint = Pubkey::new_unique();
+        let band = PriceBand { base_mint, reference_price: 100.0, band_bps: 1_000 };
+        // Asks buy quote, bids buy base
+        assert!(band.favours_taker(&quote_mint, 80.0) && !band.favours_taker(&quote_mint, 120.0));
+        assert!(band.favours_taker(&base_mint, 120.0) && !band.favours_taker(&base_mint, 80.0));
+        assert!(!band.favours_taker(&quote_mint, 95.0) && !band.favours_taker(&base_mint, 105.0));
+    }
+
+    #[test]
+    fn test_gross_up_taker_keeps_net() {
+        let rates = FeeRates { maker_fee_bps: 0, taker_fee_bps: 30 };
+        for net in [1, 5, 997, 1_000, 123_456_789] {
+            let gross = rates.gross_up_taker(net).unwrap();
+            assert!(gross - rates.taker_fee(gross) >= net);
+            assert!(gross - 1 - rates.taker_fee(gross - 1) < net);
         }
+        assert_eq!(FeeRates::default().gross_up_taker(42), Some(42));
+    }
+
+    #[test]
+    fn test_fill_fees_apply_discounts_and_rebates() {
+        let rates = FeeRates { maker_fee_bps: 10, taker_fee_bps: 25 };
+        assert_eq!(rates.fill_fees(10_000, 20_000, 0, 0, 0), FillFees { taker_fee: 25, maker_fee: 20, maker_rebate: 0, referral_fee: 0 });
+        assert_eq!(rates.fill_fees(10_000, 20_000, 5, 10, 0), FillFees { taker_fee: 20, maker_fee: 0, maker_rebate: 0, referral_fee: 0 });
+        assert_eq!(rates.fill_fees(10_000, 20_000, 50, 12, 0), FillFees { taker_fee: 0, maker_fee: 0, maker_rebate: 0, referral_fee: 0 });
+        assert_eq!(rates.fill_fees(10_000, 20_000, 0, 12, 0), FillFees { taker_fee: 25, maker_fee: 0, maker_rebate: 2, referral_fee: 0 });
+        // A rebate is paid out of the taker fee of the same fill
+        assert_eq!(rates.fill_fees(10_000, 20_000, 20, 100, 0), FillFees { taker_fee: 5, maker_fee: 0, maker_rebate: 5, referral_fee: 0 });
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_referral_fee_shares_what_the_rebate_leaves() {
+        let rates = FeeRates { maker_fee_bps: 10, taker_fee_bps: 25 };
+        assert_eq!(rates.fill_fees(10_000, 20_000, 0, 0, 2_000).referral_fee, 5);
+        assert_eq!(rates.fill_fees(10_000, 20_000, 0, 15, 2_000).referral_fee, 4);
+        assert_eq!(rates.fill_fees(10_000, 20_000, 20, 100, 10_000).referral_fee, 0);
+        assert_eq!(rates.fill_fees(100, 100, 0, 0, 5_000).referral_fee, 0);
+    }
+}
@@ -0,0 +1,222 @@
+use anchor_lang::prelude::*;
+
+/// Every error the program returns. Codes are assigned in declaration order
+/// starting at 6000, so new variants are only ever appended to keep the codes
+/// clients decode stable.
+#[error_code]
+pub enum ErrorCode {
+    // Instruction arguments
+    /// No longer returned now that sides are typed, kept so later codes stay put
+    #[msg("The order side must be \"buy\" or \"sell\".")]
+    InvalidOrderSide,
+    #[msg("The price must be a positive finite number.")]
+    InvalidPrice,
+    #[msg("The amount must be greater than zero and fit the market at the given price.")]
+    InvalidAmount,
+    #[msg("max_events must be greater than zero.")]
+    InvalidMaxEvents,
+    #[msg("The token pair does not match the mints of the instruction.")]
+    InvalidTokenPair,
+    #[msg("A route needs between 2 and MAX_ROUTE_HOPS + 1 distinct tokens.")]
+    InvalidRoute,
+
+    // Balances
+    #[msg("The available balance is lower than the amount.")]
+    InsufficientBalance,
+    #[msg("The vault ledger holds less than the withdrawn amount.")]
+    InsufficientVaultBalance,
+    #[msg("A balance update would overflow or go negative.")]
+    BalanceOverflow,
+    #[msg("The vault token account holds less than the vault ledger accounts for.")]
+    VaultBalanceMismatch,
+
+    // Orders and matching
+    #[msg("The order was not found.")]
+    OrderNotFound,
+    #[msg("Only the owner of an order may cancel it.")]
+    NotOrderOwner,
+    #[msg("The order heap of the token pair is full.")]
+    OrderHeapFull,
+    #[msg("The order would produce more events than fit in one instruction.")]
+    TooManyEvents,
+    #[msg("The book has no order to match against.")]
+    NoMatch,
+    #[msg("Order quantities overflowed while matching.")]
+    ArithmeticOverflow,
+    #[msg("The order could not be fully filled by the book.")]
+    InsufficientLiquidity,
+    #[msg("The output is below min_amount_out.")]
+    SlippageExceeded,
+    #[msg("The swap input is above max_amount_in.")]
+    MaxAmountInExceeded,
+    #[msg("An order settled in place may not match against the taker's own orders.")]
+    SelfTrade,
+
+    // Events and settlement
+    #[msg("The event list has no room for the events of another order.")]
+    EventListFull,
+    #[msg("The event list has no pending events.")]
+    NoPendingEvents,
+    #[msg("The opposite user key need to be consistent to next event.")]
+    InconsistentUserKey,
+    #[msg("A counterparty token ledger required for settlement was not provided.")]
+    MissingCounterpartyLedger,
+    #[msg("Counterparty token ledgers must be passed as writable accounts.")]
+    CounterpartyLedgerNotWritable,
+    #[msg("Settlement would make a ledger balance negative or overflow.")]
+    SettlementOverflow,
+
+    // Token ledgers
+    #[msg("A token ledger does not belong to the user it settles for.")]
+    LedgerOwnerMismatch,
+    #[msg("A token ledger does not hold the mint it is used for.")]
+    LedgerMintMismatch,
+    #[msg("The income ledger is not in the token bought by the next event.")]
+    IncomeLedgerMintMismatch,
+    #[msg("The outcome ledger is not in the token sold by the next event.")]
+    OutcomeLedgerMintMismatch,
+    #[msg("The mint account does not match the mint of the ledger.")]
+    MintAccountMismatch,
+
+    // Account capacity
+    #[msg("The user has registered the maximum number of tokens.")]
+    MaxTokensReached,
+    #[msg("The user orderbook is full.")]
+    OrderbookCapacityReached,
+    MaxLeavesReached,
+    MaxMiddlesReached,
+    TooLongKey,
+    KeyNotFound,
+
+    // Crank
+    #[msg("The crank pool does not belong to the market of the event list.")]
+    InvalidCrankPool,
+
+    // Fees
+    #[msg("Only the DEX admin may do this.")]
+    Unauthorized,
+    #[msg("A fee rate is above MAX_FEE_BPS.")]
+    InvalidFeeRate,
+    #[msg("The fee ledger of a mint charged a fee was not passed as a writable account.")]
+    MissingFeeLedger,
+    #[msg("There are no accrued fees to collect.")]
+    NoFeesToCollect,
+    #[msg("Fee tiers must have strictly increasing volumes and discounts within the fee cap.")]
+    InvalidFeeTiers,
+    #[msg("A user cannot refer itself.")]
+    InvalidReferrer,
+    #[msg("The referral share exceeds the maximum allowed.")]
+    InvalidReferralShare,
+    #[msg("The referrer's token ledger of the fee mint is missing from the remaining accounts.")]
+    MissingReferrerLedger,
+
+    // Admin
+    #[msg("Only the proposed admin may accept the admin authority.")]
+    NotPendingAdmin,
+
+    // Governance
+    #[msg("Governance needs 1 to MAX_GOVERNANCE_SIGNERS distinct signers, a threshold they can reach and a non-negative timelock.")]
+    InvalidGovernanceConfig,
+    #[msg("Only a governance signer may do this.")]
+    NotGovernanceSigner,
+    #[msg("A proposal must call another instruction of this program within the account and data limits.")]
+    InvalidProposal,
+    #[msg("The proposal was made under a previous governance configuration.")]
+    StaleProposal,
+    #[msg("This signer already approved the proposal.")]
+    ProposalAlreadyApproved,
+    #[msg("The proposal does not have enough approvals yet.")]
+    ProposalNotApproved,
+    #[msg("The proposal's timelock has not elapsed yet.")]
+    TimelockNotElapsed,
+    #[msg("The proposal was already executed.")]
+    ProposalAlreadyExecuted,
+    #[msg("An account of the proposed instruction is missing from the remaining accounts.")]
+    MissingProposalAccount,
+
+    // Market status
+    #[msg("Trading is paused on every market.")]
+    TradingPaused,
+    #[msg("The market does not take this kind of order in its current status.")]
+    MarketNotAcceptingOrders,
+    #[msg("The market is post-only and the order would match resting orders.")]
+    PostOnlyWouldMatch,
+    #[msg("The market is halted.")]
+    MarketHalted,
+    #[msg("The market passed is not the event's market.")]
+    EventMarketMismatch,
+    #[msg("The market of an event is missing from the remaining accounts.")]
+    MissingMarketEvents,
+
+    // Price bands
+    #[msg("The order price is outside the market's price band.")]
+    PriceOutsideBand,
+    #[msg("Matching stopped at a resting order priced outside the market's price band.")]
+    PriceBandBreached,
+    #[msg("The market's circuit breaker tripped, trading resumes once its cooldown ends.")]
+    CircuitBreakerTripped,
+    #[msg("Invalid price band settings.")]
+    InvalidPriceBand,
+
+    // Delisting
+    #[msg("The market is delisted, its status can no longer change.")]
+    MarketDelisted,
+    #[msg("The market has not been delisted.")]
+    MarketNotDelisted,
+    #[msg("The market still has orders on its book.")]
+    MarketNotEmpty,
+    #[msg("Fills of the market are still waiting to be settled.")]
+    MarketHasPendingFills,
+
+    // Account closure
+    #[msg("The token ledger still holds a balance.")]
+    LedgerNotEmpty,
+    #[msg("Tokens of the user are locked in open orders or fills yet to settle.")]
+    TokensLocked,
+    #[msg("The user has events waiting to be consumed.")]
+    EventsPending,
+    #[msg("A token ledger of the user is missing from the remaining accounts.")]
+    MissingUserTokenLedger,
+
+    // Market accounts
+    #[msg("The base and quote mints passed are not the market's, in its base/quote order.")]
+    MarketMintsMismatch,
+
+    // Delisting crank
+    #[msg("The user orderbook of an order's owner is missing from the remaining accounts.")]
+    MissingUserOrderbook,
+
+    // Batch settlement
+    #[msg("The Market of an event is missing from the remaining accounts.")]
+    MissingMarket,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_codes_are_stable() {
+        assert_eq!(u32::from(ErrorCode::InvalidOrderSide), 6000);
+        assert_eq!(u32::from(ErrorCode::InsufficientBalance), 6006);
+        assert_eq!(u32::from(ErrorCode::OrderHeapFull), 6012);
+        assert_eq!(u32::from(ErrorCode::InvalidCrankPool), 6037);
+        assert_eq!(u32::from(ErrorCode::NoFeesToCollect), 6041);
+        assert_eq!(u32::from(ErrorCode::InvalidFeeTiers), 6042);
+        assert_eq!(u32::from(ErrorCode::MissingReferrerLedger), 6045);
+        assert_eq!(u32::from(ErrorCode::NotPendingAdmin), 6046);
+        assert_eq!(u32::from(ErrorCode::InvalidGovernanceConfig), 6047);
+        assert_eq!(u32::from(ErrorCode::MissingProposalAccount), 6055);
+        assert_eq!(u32::from(ErrorCode::TradingPaused), 6056);
+        assert_eq!(u32::from(ErrorCode::MissingMarketEvents), 6061);
+        assert_eq!(u32::from(ErrorCode::PriceOutsideBand), 6062);
+        assert_eq!(u32::from(ErrorCode::InvalidPriceBand), 6065);
+        assert_eq!(u32::from(ErrorCode::MarketDelisted), 6066);
+        assert_eq!(u32::from(ErrorCode::MarketHasPendingFills), 6069);
+        assert_eq!(u32::from(ErrorCode::LedgerNotEmpty), 6070);
+        assert_eq!(u32::from(ErrorCode::MissingUserTokenLedger), 6073);
+        assert_eq!(u32::from(ErrorCode::MarketMintsMismatch), 6074);
+        assert_eq!(u32::from(ErrorCode::MissingUserOrderbook), 6075);
+        assert_eq!(u32::from(ErrorCode::MissingMarket), 6076);
+    }
+}
@@ -0,0 +1,44 @@
+use anchor_lang::prelude::*;
+use crate::state::DexManager;
+use crate::state::DEX_MANAGER_SEED;
+use crate::error::ErrorCode;
+
+/// First step of handing the admin authority over: `new_admin` has to accept
+/// it with `accept_admin`. Proposing `Pubkey::default()` withdraws a proposal.
+pub fn propose_admin_impl(ctx: Context<ProposeAdmin>, new_admin: Pubkey) -> Result<()> {
+    msg!("Proposing {} as DEX admin", new_admin);
+    ctx.accounts.dex_manager.pending_admin = new_admin;
+    Ok(())
+}
+
+pub fn accept_admin_impl(ctx: Context<AcceptAdmin>) -> Result<()> {
+    let dex_manager = &mut ctx.accounts.dex_manager;
+    msg!("DEX admin changes from {} to {}", dex_manager.admin, dex_manager.pending_admin);
+    dex_manager.admin = dex_manager.pending_admin;
+    dex_manager.pending_admin = Pubkey::default();
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct ProposeAdmin<'info> {
+    #[account(
+        mut,
+        seeds = [DEX_MANAGER_SEED],
+        bump = dex_manager.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub dex_manager: Account<'info, DexManager>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptAdmin<'info> {
+    #[account(
+        mut,
+        seeds = [DEX_MANAGER_SEED],
+        bump = dex_manager.bump,
+        has_one = pending_admin @ ErrorCode::NotPendingAdmin
+    )]
+    pub dex_manager: Account<'info, DexManager>,
+    pub pending_admin: Signer<'info>,
+}
@@ -0,0 +1,42 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::TokenAccount;
+use crate::common::VaultAuditedEvent;
+use crate::state::VaultTokenLedgerAccount;
+use crate::state::VAULT_TOKEN_LEDGER_SEED;
+use crate::error::ErrorCode;
+
+/// Checks that the tokens held by the vault of `mint` cover what its ledger
+/// accounts for. Anyone may call it, a failing audit means the vault is short.
+/// Tokens sent straight to the vault token account are not an error, they are
+/// reported as the audit's surplus.
+pub fn audit_vault_impl(ctx: Context<AuditVault>, mint: Pubkey) -> Result<()> {
+    let ledger_balance = ctx.accounts.vault_token_ledger.total_balance;
+    let vault_balance = ctx.accounts.vault_token_account.amount;
+    if vault_balance < ledger_balance {
+        msg!("Vault of {} is short: ledger {}, token account {}", mint, ledger_balance, vault_balance);
+        return Err(ErrorCode::VaultBalanceMismatch.into());
+    }
+
+    let surplus = vault_balance - ledger_balance;
+    msg!("Vault of {} holds {}, {} above its ledger", mint, vault_balance, surplus);
+    emit!(VaultAuditedEvent {
+        mint,
+        vault_token_account: ctx.accounts.vault_token_account.key(),
+        total_balance: ledger_balance,
+        surplus,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+    Ok(())
+}
+
+#[derive(Accounts)]
+#[instruction(mint: Pubkey)]
+pub struct AuditVault<'info> {
+    #[account(
+        seeds = [VAULT_TOKEN_LEDGER_SEED, mint.as_ref()],
+        bump = vault_token_ledger.bump,
+        has_one = vault_token_account
+    )]
+    pub vault_token_ledger: Account<'info, VaultTokenLedgerAccount>,
+    pub vault_token_account: Account<'info, TokenAccount>,
+}
@@ -1,27 +1,43 @@
 use anchor_lang::prelude::*;
 
 use crate::{
-    common::{CanceledOrderEvent, OrderType}, 
-    IndividualTokenLedgerAccount, 
-    OrderHeap, TokenPairAccount, UserOrderbook, USER_ORDERBOOK_SEED
+    instructions::common::OrderEventEmitter,
+    DexManager, IndividualTokenLedgerAccount, Market, MarketEventLog,
+    OrderHeap, TokenPairAccount, UserOrderbook, DEX_MANAGER_SEED, INDIVIDUAL_TOKEN_LEDGER_SEED, USER_ORDERBOOK_SEED
 };
+use crate::error::ErrorCode;
+use crate::event_sink;
 
-
+/// Cancels the user's resting order whatever the market's status, even halted
+/// or delisted. `cancel_delisted_orders` cancels what owners leave on a
+/// delisted book.
 pub fn cancel_order_impl(ctx: Context<CancelOrder>, order_id: u64) -> Result<()> {
     let mut base_quote_queue = ctx.accounts.base_quote_queue.load_mut()?;
+    let order = base_quote_queue.order_heap.get_order_by_id(order_id).ok_or(ErrorCode::OrderNotFound)?;
+    require_keys_eq!(order.owner, ctx.accounts.user.key(), ErrorCode::NotOrderOwner);
+    require_keys_eq!(order.sell_token, ctx.accounts.user_sell_token_ledger.mint_account, ErrorCode::LedgerMintMismatch);
     let node = base_quote_queue.order_heap.remove_order(order_id)?;
     
     let user_orderbook = &mut ctx.accounts.user_order_book;
     user_orderbook.remove_order(order_id as u128)?;
-    emit!(CanceledOrderEvent::from_order_node(&node, OrderType::Limit));
+    let timestamp = Clock::get()?.unix_timestamp;
+    let mut market_events = ctx.accounts.market_events.load_mut()?;
+    market_events.push_out(node.owner, node.id, &node.buy_token, node.buy_quantity, node.sell_quantity, timestamp);
+    OrderEventEmitter::new(&mut ctx.accounts.dex_manager, event_sink!(ctx), ctx.accounts.market.key(), ctx.accounts.market.base_mint, timestamp)
+        .cancelled(&node)?;
 
     let user_token_outcome_ledger = &mut ctx.accounts.user_sell_token_ledger;
-    user_token_outcome_ledger.locked_balance -= node.sell_quantity;
-    user_token_outcome_ledger.available_balance += node.sell_quantity;
+    user_token_outcome_ledger.locked_balance = user_token_outcome_ledger.locked_balance
+        .checked_sub(node.sell_quantity)
+        .ok_or(ErrorCode::BalanceOverflow)?;
+    user_token_outcome_ledger.available_balance = user_token_outcome_ledger.available_balance
+        .checked_add(node.sell_quantity)
+        .ok_or(ErrorCode::BalanceOverflow)?;
 
     Ok(())
 }
 
+#[cfg_attr(feature = "event-cpi", event_cpi)]
 #[derive(Accounts)]
 pub struct CancelOrder<'info> {
     #[account(
@@ -34,8 +50,24 @@ pub struct CancelOrder<'info> {
     #[account(mut)]
     pub base_quote_queue: AccountLoader<'info, TokenPairAccount>,
 
+    #[account(constraint = market.is_market(&base_quote_queue.load()?.buy_token, &base_quote_queue.load()?.sell_token))]
+    pub market: Box<Account<'info, Market>>,
+
+    /// Receives an out event for the cancelled order
+    #[account(
+        mut,
+        constraint = market_events.load()?.is_market(&base_quote_queue.load()?.buy_token, &base_quote_queue.load()?.sell_token)
+    )]
+    pub market_events: AccountLoader<'info, MarketEventLog>,
+
+    #[account(mut, seeds = [DEX_MANAGER_SEED], bump = dex_manager.bump)]
+    pub dex_manager: Account<'info, DexManager>,
     
-    #[account(mut)]
+    #[account(
+        mut,
+        seeds = [INDIVIDUAL_TOKEN_LEDGER_SEED, user_sell_token_ledger.mint_account.as_ref(), user.key().as_ref()],
+        bump = user_sell_token_ledger.bump,
+    )]
     pub user_sell_token_ledger: Box<Account<'info, IndividualTokenLedgerAccount>>,
     
     #[account(mut)]
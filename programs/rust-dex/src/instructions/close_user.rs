@@ -0,0 +1,131 @@
+use anchor_lang::prelude::*;
+use crate::state::{EventList, IndividualLedgerAccount, IndividualTokenLedgerAccount, UserOrderbook};
+use crate::state::ORDER_EVENTS_SEED;
+use crate::state::INDIVIDUAL_LEDGER_SEED;
+use crate::state::INDIVIDUAL_TOKEN_LEDGER_SEED;
+use crate::state::USER_ORDERBOOK_SEED;
+use crate::error::ErrorCode;
+
+/// Closes the user's token ledger of `mint`, refunding its rent. The ledger
+/// must be empty and the user's event list drained. The user's other token
+/// ledgers go through `remaining_accounts` and must lock nothing: a locked
+/// balance means an open order or a fill yet to settle, and settling it may
+/// need the user's ledger of any mint.
+pub fn close_user_token_ledger_impl<'info>(ctx: Context<'_, '_, 'info, 'info, CloseUserTokenLedger<'info>>, mint: Pubkey) -> Result<()> {
+    let user = ctx.accounts.user.key();
+    msg!("Closing token ledger of {} for {}", mint, user);
+    if !ctx.accounts.order_events.is_empty() {
+        return Err(ErrorCode::EventsPending.into());
+    }
+    check_empty(&ctx.accounts.user_token_ledger)?;
+    let others = user_token_ledgers(ctx.remaining_accounts, &ctx.accounts.individual_ledger, &user, Some(&mint))?;
+    if others.iter().any(|ledger| ledger.locked_balance > 0) {
+        return Err(ErrorCode::TokensLocked.into());
+    }
+    ctx.accounts.individual_ledger.remove_token(&mint);
+    Ok(())
+}
+
+/// Closes every account `register_user` and `register_user_token_ledger`
+/// created for the user, refunding their rent. Every token ledger of the user
+/// goes through `remaining_accounts`, writable, and must be empty, and the
+/// event list must be drained. Empty ledgers leave no open order behind, the
+/// ids the `UserOrderbook` may still hold of orders filled as a maker are stale.
+pub fn deregister_user_impl<'info>(ctx: Context<'_, '_, 'info, 'info, DeregisterUser<'info>>) -> Result<()> {
+    let user = ctx.accounts.user.key();
+    msg!("Deregistering user {}", user);
+    if !ctx.accounts.order_events.is_empty() {
+        return Err(ErrorCode::EventsPending.into());
+    }
+    let ledgers = user_token_ledgers(ctx.remaining_accounts, &ctx.accounts.individual_ledger, &user, None)?;
+    for ledger in ledgers {
+        check_empty(&ledger)?;
+        ledger.close(ctx.accounts.user.to_account_info())?;
+    }
+    Ok(())
+}
+
+fn check_empty(ledger: &IndividualTokenLedgerAccount) -> Result<()> {
+    if ledger.available_balance > 0 || ledger.locked_balance > 0 {
+        return Err(ErrorCode::LedgerNotEmpty.into());
+    }
+    Ok(())
+}
+
+/// The user's token ledger of every mint it registered but `skip`, looked up in `accounts`.
+fn user_token_ledgers<'info>(
+    accounts: &'info [AccountInfo<'info>],
+    individual_ledger: &IndividualLedgerAccount,
+    user: &Pubkey,
+    skip: Option<&Pubkey>,
+) -> Result<Vec<Account<'info, IndividualTokenLedgerAccount>>> {
+    let mut passed = accounts
+        .iter()
+        .map(Account::<IndividualTokenLedgerAccount>::try_from)
+        .collect::<Result<Vec<_>>>()?;
+    let mut ledgers = Vec::with_capacity(individual_ledger.registered_tokens().len());
+    for mint in individual_ledger.registered_tokens().iter().filter(|mint| Some(*mint) != skip) {
+        let index = passed
+            .iter()
+            .position(|ledger| ledger.owner == *user && ledger.mint_account == *mint)
+            .ok_or(ErrorCode::MissingUserTokenLedger)?;
+        ledgers.push(passed.swap_remove(index));
+    }
+    Ok(ledgers)
+}
+
+#[derive(Accounts)]
+#[instruction(mint: Pubkey)]
+pub struct CloseUserTokenLedger<'info> {
+    #[account(
+        mut,
+        seeds = [INDIVIDUAL_TOKEN_LEDGER_SEED, mint.as_ref(), user.key().as_ref()],
+        bump = user_token_ledger.bump,
+        close = user
+    )]
+    pub user_token_ledger: Account<'info, IndividualTokenLedgerAccount>,
+    #[account(
+        mut,
+        seeds = [INDIVIDUAL_LEDGER_SEED, user.key().as_ref()],
+        bump = individual_ledger.bump,
+    )]
+    pub individual_ledger: Box<Account<'info, IndividualLedgerAccount>>,
+    #[account(
+        seeds = [ORDER_EVENTS_SEED, user.key().as_ref()],
+        bump = order_events.bump,
+        has_one = user
+    )]
+    pub order_events: Box<Account<'info, EventList>>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct DeregisterUser<'info> {
+    #[account(
+        mut,
+        seeds = [INDIVIDUAL_LEDGER_SEED, user.key().as_ref()],
+        bump = individual_ledger.bump,
+        close = user
+    )]
+    pub individual_ledger: Box<Account<'info, IndividualLedgerAccount>>,
+    #[account(
+        mut,
+        seeds = [USER_ORDERBOOK_SEED, user.key().as_ref()],
+        bump = user_order_book.bump,
+        close = user
+    )]
+    pub user_order_book: Box<Account<'info, UserOrderbook>>,
+    #[account(
+        mut,
+        seeds = [ORDER_EVENTS_SEED, user.key().as_ref()],
+        bump = order_events.bump,
+        has_one = user,
+        close = user
+    )]
+    pub order_events: Box<Account<'info, EventList>>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+}
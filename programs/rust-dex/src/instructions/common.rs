@@ -1,7 +1,13 @@
-use crate::state::OrderHeapImpl;
+use crate::common::{
+    FeeDiscount, FeeRates, MarketStatus, OrderCancelledEvent, OrderEventHeader, OrderFilledEvent, OrderPlacedEvent, OrderRequest, OrderRestedEvent,
+    OrderRole, OrderRolledBackEvent, OrderSide, OrderType, RollbackReason, ORDER_EVENT_VERSION,
+};
+use crate::state::{price_of, DexManager, FeeTierTable, IndividualTokenLedgerAccount, OrderHeapImpl, OrderNode};
 use crate::state::EventList;
-use crate::matching_engine::OrderSuccess;
+use crate::state::{Market, MarketEventLog};
+use crate::matching_engine::{OrderFailure, OrderProcessResult, OrderSuccess};
 use crate::OrderHeap;
+use crate::error::ErrorCode;
 use anchor_lang::prelude::*;
 
 pub fn token_pair_queue_logging(buy_queue: &OrderHeapImpl, sell_queue: & OrderHeapImpl) {
@@ -18,7 +24,11 @@ pub fn token_pair_queue_logging(buy_queue: &OrderHeapImpl, sell_queue: & OrderHe
     }
 }
 
-pub fn convert_to_event_list(event_list: &mut EventList, result: Vec<std::result::Result<OrderSuccess, crate::matching_engine::OrderFailure>>) {
+/// Queues `result` in the taker's `event_list`. Returns the number of fills
+/// queued, which the market counts until they are consumed. Fails with
+/// `EventListFull` rather than drop an event the list has no room for.
+pub fn convert_to_event_list(event_list: &mut EventList, order_id: u64, token_buy: Pubkey, token_sell: Pubkey, result: Vec<std::result::Result<OrderSuccess, crate::matching_engine::OrderFailure>>) -> Result<u64> {
+    let mut fills = 0;
     for res in result {
         match res {
             Ok(success) => {
@@ -32,11 +42,15 @@ pub fn convert_to_event_list(event_list: &mut EventList, result: Vec<std::result
                         sell_quantity,
                         buy_quantity,
                         filled,
-                        oppo_filled, 
+                        oppo_filled,
+                        taker_fee,
+                        maker_fee,
+                        maker_rebate,
+                        referral_fee,
+                        ..
                     } => {
-                        if let Err(e) = event_list.add_event(oppo_user, buy_quantity, sell_quantity, 0, oppo_order_id, oppo_filled as u8, filled as u8) {
-                            msg!("Add Event Failed: {:?}", e);
-                        }
+                        event_list.add_event(order_id, token_buy, token_sell, oppo_user, buy_quantity, sell_quantity, 0, oppo_order_id, oppo_filled as u8, filled as u8, taker_fee, maker_fee, maker_rebate, referral_fee)?;
+                        fills += 1;
                     },
                 }
             },
@@ -50,14 +64,333 @@ pub fn convert_to_event_list(event_list: &mut EventList, result: Vec<std::result
                     }
                     | crate::matching_engine::OrderFailure::OrderHeapFull { 
                         who, _order_id: _, _order_type: _, sell_quantity, buy_quantity 
+                    }
+                    | crate::matching_engine::OrderFailure::PriceBandBreached { 
+                        who, _order_id: _, _order_type: _, sell_quantity, buy_quantity 
                     } => {
-                        if let Err(e) = event_list.add_event(who, buy_quantity, sell_quantity, 1, 0, 0 , 0) {
-                            msg!("Add Event Failed: {:?}", e);
-                        }
+                        event_list.add_event(order_id, token_buy, token_sell, who, buy_quantity, sell_quantity, 1, 0, 0 , 0, 0, 0, 0, 0)?;
                     },
-                    _ => msg!("Order Failure: {:?}", failure),
+                    _ => msg!("Order Failure {}: {:?}", u32::from(failure.error_code()), failure),
                 }
             }
         }
     }
-}
\ No newline at end of file
+    Ok(fills)
+}
+
+/// Sets the fees of every fill in `result` from the market's rates, less the
+/// taker's `taker_discount_bps` and the discount each maker order rests with.
+/// `referral_share_bps` is 0 unless the taker has a referrer.
+pub fn charge_fees(result: &mut OrderProcessResult, rates: FeeRates, taker_discount_bps: u16, referral_share_bps: u16) {
+    for res in result.iter_mut() {
+        if let Ok(OrderSuccess::Filled { sell_quantity, buy_quantity, taker_fee, maker_fee, maker_rebate, referral_fee, maker_discount_bps, .. }) = res {
+            let fees = rates.fill_fees(*buy_quantity, *sell_quantity, taker_discount_bps, *maker_discount_bps, referral_share_bps);
+            *taker_fee = fees.taker_fee;
+            *maker_fee = fees.maker_fee;
+            *maker_rebate = fees.maker_rebate;
+            *referral_fee = fees.referral_fee;
+        }
+    }
+}
+
+/// Share of a user's taker fees paid to its referrer, 0 for users without one.
+pub fn referral_share(dex_manager: &DexManager, ledger: &IndividualTokenLedgerAccount) -> u16 {
+    if ledger.referrer == Pubkey::default() {
+        0
+    } else {
+        dex_manager.referral_share_bps
+    }
+}
+
+/// Fee tier discount of the user owning `quote_ledger`, picked by its trailing
+/// volume. Markets whose quote mint has no fee tier table charge the base rates.
+pub fn tier_discount(fee_tiers: &AccountInfo, quote_ledger: &IndividualTokenLedgerAccount, now: i64) -> Result<FeeDiscount> {
+    if fee_tiers.owner != &crate::ID || fee_tiers.data_is_empty() {
+        return Ok(FeeDiscount::default());
+    }
+    let table = FeeTierTable::try_deserialize(&mut &fee_tiers.try_borrow_data()?[..])?;
+    Ok(table.discount_for(quote_ledger.trailing_volume(now)))
+}
+
+/// Rejects a new order of `order_type` while trading is paused, the market's
+/// status does not take it or its circuit breaker cools down. Post-only
+/// markets take limit orders, which must then not match, see
+/// `MatchingEngine::would_match`.
+pub fn check_order_allowed(dex_manager: &DexManager, market: &Market, order_type: OrderType, now: i64) -> Result<()> {
+    if dex_manager.paused {
+        return Err(ErrorCode::TradingPaused.into());
+    }
+    match (market.status(), order_type) {
+        (MarketStatus::Active, _) | (MarketStatus::PostOnly, OrderType::Limit) => {},
+        (MarketStatus::Halted, _) => return Err(ErrorCode::MarketHalted.into()),
+        _ => return Err(ErrorCode::MarketNotAcceptingOrders.into()),
+    }
+    if market.circuit_breaker_tripped(now) {
+        return Err(ErrorCode::CircuitBreakerTripped.into());
+    }
+    Ok(())
+}
+
+/// The settlement of fills stops only once a market is halted.
+pub fn check_not_halted(market: &Market) -> Result<()> {
+    if market.status() == MarketStatus::Halted {
+        return Err(ErrorCode::MarketHalted.into());
+    }
+    Ok(())
+}
+
+/// Appends every fill of `result` to the market event log, along with an out
+/// event for any part of the taker order that was handed back instead of resting.
+/// Every fill moves the market's reference price along, and a fill priced
+/// outside the band around the reference price it moved from trips the
+/// market's circuit breaker.
+pub fn record_market_events(market: &mut Market, market_events: &mut MarketEventLog, taker_buy_token: &Pubkey, result: &OrderProcessResult, timestamp: i64) {
+    for res in result {
+        match res {
+            Ok(OrderSuccess::Filled { _who, oppo_user, _order_id, oppo_order_id, sell_quantity, buy_quantity, oppo_filled, .. }) => {
+                let fill = market_events.push_fill(
+                    *oppo_user, *oppo_order_id, *_who, *_order_id,
+                    taker_buy_token, *buy_quantity, *sell_quantity, *oppo_filled, timestamp,
+                );
+                if !market.price_band().contains(fill.price) {
+                    market.trip_circuit_breaker(timestamp);
+                }
+                market.reference_price = fill.price;
+            },
+            Err(OrderFailure::NoMatch { who, _order_id, sell_quantity, buy_quantity, .. })
+            | Err(OrderFailure::TooManyEvents { who, _order_id, sell_quantity, buy_quantity, .. })
+            | Err(OrderFailure::OrderHeapFull { who, _order_id, sell_quantity, buy_quantity, .. })
+            | Err(OrderFailure::PriceBandBreached { who, _order_id, sell_quantity, buy_quantity, .. }) => {
+                market_events.push_out(*who, *_order_id, taker_buy_token, *buy_quantity, *sell_quantity, timestamp);
+            },
+            Err(OrderFailure::OrderNotFound { .. }) => {},
+        }
+    }
+}
+
+#[cfg(feature = "event-cpi")]
+const EVENT_AUTHORITY_SEED: &[u8] = b"__event_authority";
+
+/// Where order lifecycle events are written. By default they go to the program
+/// logs like `emit!`. With the `event-cpi` feature they are passed to the
+/// program itself through a CPI signed by the event authority, the way
+/// `emit_cpi!` does, so indexers read them from the inner instructions and no
+/// event is lost when the logs of a transaction are truncated.
+#[derive(Clone)]
+pub struct EventSink<'info> {
+    #[cfg(feature = "event-cpi")]
+    authority: AccountInfo<'info>,
+    #[cfg(feature = "event-cpi")]
+    bump: u8,
+    #[cfg(not(feature = "event-cpi"))]
+    _logs: std::marker::PhantomData<&'info ()>,
+}
+
+impl<'info> EventSink<'info> {
+    #[cfg(feature = "event-cpi")]
+    pub fn cpi(authority: AccountInfo<'info>, bump: u8) -> Self {
+        Self { authority, bump }
+    }
+
+    #[cfg(not(feature = "event-cpi"))]
+    pub fn logs() -> Self {
+        Self { _logs: std::marker::PhantomData }
+    }
+
+    #[cfg(feature = "event-cpi")]
+    pub fn emit<E: anchor_lang::Event>(&self, event: &E) -> Result<()> {
+        let data: Vec<u8> = anchor_lang::event::EVENT_IX_TAG_LE.iter()
+            .copied()
+            .chain(event.data())
+            .collect();
+        let ix = anchor_lang::solana_program::instruction::Instruction::new_with_bytes(
+            crate::ID,
+            &data,
+            vec![AccountMeta::new_readonly(*self.authority.key, true)],
+        );
+        anchor_lang::solana_program::program::invoke_signed(
+            &ix,
+            std::slice::from_ref(&self.authority),
+            &[&[EVENT_AUTHORITY_SEED, &[self.bump]]],
+        )?;
+        Ok(())
+    }
+
+    #[cfg(not(feature = "event-cpi"))]
+    pub fn emit<E: anchor_lang::Event>(&self, event: &E) -> Result<()> {
+        anchor_lang::solana_program::log::sol_log_data(&[&event.data()]);
+        Ok(())
+    }
+}
+
+/// The `EventSink` of an instruction. Its accounts struct must carry
+/// `#[cfg_attr(feature = "event-cpi", event_cpi)]`.
+#[macro_export]
+macro_rules! event_sink {
+    ($ctx:expr) => {{
+        #[cfg(feature = "event-cpi")]
+        let sink = $crate::instructions::common::EventSink::cpi(
+            $ctx.accounts.event_authority.to_account_info(),
+            $ctx.bumps.event_authority,
+        );
+        #[cfg(not(feature = "event-cpi"))]
+        let sink = $crate::instructions::common::EventSink::logs();
+        sink
+    }};
+}
+
+/// Emits the order lifecycle events of one market, numbered from the DEX-wide
+/// event sequence kept in `DexManager`.
+pub struct OrderEventEmitter<'a, 'info> {
+    dex_manager: &'a mut DexManager,
+    sink: EventSink<'info>,
+    market: Pubkey,
+    base_mint: Pubkey,
+    timestamp: i64,
+}
+
+impl<'a, 'info> OrderEventEmitter<'a, 'info> {
+    pub fn new(dex_manager: &'a mut DexManager, sink: EventSink<'info>, market: Pubkey, base_mint: Pubkey, timestamp: i64) -> Self {
+        Self {
+            dex_manager,
+            sink,
+            market,
+            base_mint,
+            timestamp,
+        }
+    }
+
+    fn header(&mut self, order_id: u64, owner: Pubkey, buy_token: &Pubkey) -> OrderEventHeader {
+        OrderEventHeader {
+            version: ORDER_EVENT_VERSION,
+            seq_num: self.dex_manager.next_event_sequence(),
+            market: self.market,
+            order_id,
+            owner,
+            side: if *buy_token == self.base_mint { OrderSide::Buy } else { OrderSide::Sell },
+            timestamp: self.timestamp,
+        }
+    }
+
+    /// Turns the quantities of an order buying `buy_token` into (base, quote).
+    fn base_quote(&self, buy_token: &Pubkey, buy_quantity: u64, sell_quantity: u64) -> (u64, u64) {
+        if *buy_token == self.base_mint {
+            (buy_quantity, sell_quantity)
+        } else {
+            (sell_quantity, buy_quantity)
+        }
+    }
+
+    pub fn placed(&mut self, order: &OrderRequest) -> Result<()> {
+        let (base_quantity, quote_quantity) = self.base_quote(&order.buy_token, order.buy_quantity, order.sell_quantity);
+        let price = if order.order_type == OrderType::Limit { price_of(base_quantity, quote_quantity) } else { 0.0 };
+        let header = self.header(order.id, order.owner, &order.buy_token);
+        self.sink.emit(&OrderPlacedEvent {
+            header,
+            order_type: order.order_type,
+            price,
+            base_quantity,
+            quote_quantity,
+        })
+    }
+
+    /// Emits both sides of every fill in `result`, and a rollback for any part
+    /// of the taker order that was handed back.
+    pub fn matched(&mut self, taker_buy_token: &Pubkey, taker_sell_token: &Pubkey, result: &OrderProcessResult) -> Result<()> {
+        for res in result {
+            match res {
+                Ok(OrderSuccess::Filled {
+                    _who, oppo_user, _order_id, oppo_order_id, _order_type, sell_quantity, buy_quantity,
+                    remaining_buy_quantity, remaining_sell_quantity,
+                    oppo_remaining_buy_quantity, oppo_remaining_sell_quantity, taker_fee, maker_fee, maker_rebate, referral_fee, ..
+                }) => {
+                    let (base_quantity, quote_quantity) = self.base_quote(taker_buy_token, *buy_quantity, *sell_quantity);
+                    let price = price_of(base_quantity, quote_quantity);
+                    let (remaining_base, remaining_quote) =
+                        self.base_quote(taker_buy_token, *remaining_buy_quantity, *remaining_sell_quantity);
+                    let header = self.header(*_order_id, *_who, taker_buy_token);
+                    self.sink.emit(&OrderFilledEvent {
+                        header,
+                        order_type: *_order_type,
+                        role: OrderRole::Taker,
+                        counterparty: *oppo_user,
+                        counterparty_order_id: *oppo_order_id,
+                        price,
+                        base_quantity,
+                        quote_quantity,
+                        remaining_base,
+                        remaining_quote,
+                        fee: *taker_fee,
+                        fee_mint: *taker_buy_token,
+                        rebate: 0,
+                        referral_fee: *referral_fee,
+                    })?;
+                    // The maker buys what the taker sells
+                    let (remaining_base, remaining_quote) =
+                        self.base_quote(taker_sell_token, *oppo_remaining_buy_quantity, *oppo_remaining_sell_quantity);
+                    let header = self.header(*oppo_order_id, *oppo_user, taker_sell_token);
+                    self.sink.emit(&OrderFilledEvent {
+                        header,
+                        order_type: OrderType::Limit,
+                        role: OrderRole::Maker,
+                        counterparty: *_who,
+                        counterparty_order_id: *_order_id,
+                        price,
+                        base_quantity,
+                        quote_quantity,
+                        remaining_base,
+                        remaining_quote,
+                        fee: *maker_fee,
+                        fee_mint: *taker_sell_token,
+                        rebate: *maker_rebate,
+                        referral_fee: 0,
+                    })?;
+                },
+                Err(failure) => {
+                    let (who, order_id, order_type, sell_quantity, buy_quantity, reason) = match failure {
+                        OrderFailure::NoMatch { who, _order_id, _order_type, sell_quantity, buy_quantity } =>
+                            (who, _order_id, _order_type, sell_quantity, buy_quantity, RollbackReason::NoMatch),
+                        OrderFailure::TooManyEvents { who, _order_id, _order_type, sell_quantity, buy_quantity } =>
+                            (who, _order_id, _order_type, sell_quantity, buy_quantity, RollbackReason::TooManyEvents),
+                        OrderFailure::OrderHeapFull { who, _order_id, _order_type, sell_quantity, buy_quantity } =>
+                            (who, _order_id, _order_type, sell_quantity, buy_quantity, RollbackReason::OrderHeapFull),
+                        OrderFailure::PriceBandBreached { who, _order_id, _order_type, sell_quantity, buy_quantity } =>
+                            (who, _order_id, _order_type, sell_quantity, buy_quantity, RollbackReason::PriceBandBreached),
+                        OrderFailure::OrderNotFound { .. } => continue,
+                    };
+                    let (base_quantity, quote_quantity) = self.base_quote(taker_buy_token, *buy_quantity, *sell_quantity);
+                    let header = self.header(*order_id, *who, taker_buy_token);
+                    self.sink.emit(&OrderRolledBackEvent {
+                        header,
+                        order_type: *order_type,
+                        reason,
+                        base_quantity,
+                        quote_quantity,
+                    })?;
+                },
+            }
+        }
+        Ok(())
+    }
+
+    pub fn rested(&mut self, order: &OrderNode) -> Result<()> {
+        let (base_quantity, quote_quantity) = self.base_quote(&order.buy_token, order.buy_quantity, order.sell_quantity);
+        let header = self.header(order.id, order.owner, &order.buy_token);
+        self.sink.emit(&OrderRestedEvent {
+            header,
+            price: price_of(base_quantity, quote_quantity),
+            base_quantity,
+            quote_quantity,
+        })
+    }
+
+    pub fn cancelled(&mut self, order: &OrderNode) -> Result<()> {
+        let (base_quantity, quote_quantity) = self.base_quote(&order.buy_token, order.buy_quantity, order.sell_quantity);
+        let header = self.header(order.id, order.owner, &order.buy_token);
+        self.sink.emit(&OrderCancelledEvent {
+            header,
+            base_quantity,
+            quote_quantity,
+        })
+    }
+}
@@ -3,45 +3,159 @@ use crate::state::EventList;
 use crate::state::ORDER_EVENTS_SEED;
 use crate::INDIVIDUAL_TOKEN_LEDGER_SEED;
 use crate::state::IndividualTokenLedgerAccount;
+use crate::state::{FeeLedgerAccount, FEE_LEDGER_SEED};
+use crate::state::Market;
+use crate::instructions::common::check_not_halted;
+use crate::instructions::settlement::{retained_taker_fee, LedgerCache};
+use crate::error::ErrorCode;
 
-#[error_code]
-pub enum ErrorCode {
-    #[msg("The opposite user key need to be consistent to next event.")]
-    InconsistentUserKey,
+/// The ledger of the user's referrer in the event's `token_buy` goes through
+/// `remaining_accounts` when a referral fee is due.
+pub fn consume_event_impl<'info>(ctx: Context<'_, '_, 'info, 'info, ConsumeEvents<'info>>, opposite_user_key: Pubkey) -> Result<()> {
+    let mut referrers = LedgerCache::new(ctx.remaining_accounts);
+    consume_next_event(
+        &mut ctx.accounts.event_list,
+        &mut ctx.accounts.user_token_income_ledger,
+        &mut ctx.accounts.user_token_outcome_ledger,
+        ctx.accounts.opposite_user_token_income_ledger.as_deref_mut().map(|ledger| &mut **ledger),
+        ctx.accounts.opposite_user_token_outcome_ledger.as_deref_mut().map(|ledger| &mut **ledger),
+        &mut ctx.accounts.income_fee_ledger,
+        &mut ctx.accounts.outcome_fee_ledger,
+        &mut referrers,
+        &mut ctx.accounts.market,
+        opposite_user_key,
+    )?;
+    referrers.exit()
 }
 
-pub fn consume_event_impl(ctx: Context<ConsumeEvents>, opposite_user_key: Pubkey) -> Result<()> {
-    let event_list: &mut EventList = &mut ctx.accounts.event_list;
-    let user_token_income_ledger: &mut IndividualTokenLedgerAccount = &mut ctx.accounts.user_token_income_ledger;
-    let user_token_outcome_ledger: &mut IndividualTokenLedgerAccount = &mut ctx.accounts.user_token_outcome_ledger;
-    let opposite_user_token_income_ledger: &mut IndividualTokenLedgerAccount = &mut ctx.accounts.opposite_user_token_income_ledger;
-    let opposite_user_token_outcome_ledger: &mut IndividualTokenLedgerAccount = &mut ctx.accounts.opposite_user_token_outcome_ledger;
-    if event_list.is_closed() {
-        return Ok(());
-    }
-
+/// Settles the oldest event of `event_list`. Returns whether an event was consumed.
+/// The fee ledgers are those of the event's `token_buy` and `token_sell`, the
+/// fees the user and the counterparty pay accrue to them, less the rebate paid
+/// to the counterparty and the referral fee paid to the user's referrer, whose
+/// ledger is looked up in `referrers` unless it is the counterparty. Fills
+/// between two users count towards the trailing volume of both. Fills are not
+/// settled while their `market` is halted, and are taken off
+/// its count of pending fills once settled.
+///
+/// Rollbacks only touch the user's ledgers and need no counterparty ledgers.
+/// `opposite_user_key` may only be the user itself for a fill against its own
+/// order: both halves then settle on the user's ledgers, and counterparty
+/// ledgers passed anyway, being the same accounts, are left holding the same
+/// state so that writing them back does not undo the settlement.
+#[allow(clippy::too_many_arguments)]
+pub fn consume_next_event(
+    event_list: &mut EventList,
+    user_token_income_ledger: &mut IndividualTokenLedgerAccount,
+    user_token_outcome_ledger: &mut IndividualTokenLedgerAccount,
+    mut opposite_user_token_income_ledger: Option<&mut IndividualTokenLedgerAccount>,
+    mut opposite_user_token_outcome_ledger: Option<&mut IndividualTokenLedgerAccount>,
+    income_fee_ledger: &mut FeeLedgerAccount,
+    outcome_fee_ledger: &mut FeeLedgerAccount,
+    referrers: &mut LedgerCache,
+    market: &mut Market,
+    opposite_user_key: Pubkey,
+) -> Result<bool> {
     let next_event;
     match event_list.pop() {
         Some(event) => next_event = event,
-        None => return Ok(()),
+        None => return Ok(false),
     };
+    let self_fill = !next_event.rollback && next_event.oppo_user == event_list.user;
+    if opposite_user_key == event_list.user && !self_fill {
+        return Err(ErrorCode::InconsistentUserKey.into());
+    }
+    check_ledger(user_token_income_ledger, &event_list.user, &next_event.token_buy, ErrorCode::IncomeLedgerMintMismatch)?;
+    check_ledger(user_token_outcome_ledger, &event_list.user, &next_event.token_sell, ErrorCode::OutcomeLedgerMintMismatch)?;
 
+    user_token_outcome_ledger.locked_balance = user_token_outcome_ledger.locked_balance
+        .checked_sub(next_event.sell_quantity)
+        .ok_or(ErrorCode::SettlementOverflow)?;
     if next_event.rollback {
-        user_token_outcome_ledger.locked_balance -= next_event.sell_quantity;
-        user_token_outcome_ledger.available_balance += next_event.sell_quantity;
-    }else {
-        if next_event.oppo_user != opposite_user_key {
-            return Err(ErrorCode::InconsistentUserKey.into());
+        user_token_outcome_ledger.available_balance = user_token_outcome_ledger.available_balance
+            .checked_add(next_event.sell_quantity)
+            .ok_or(ErrorCode::SettlementOverflow)?;
+        return Ok(true);
+    }
+    if next_event.oppo_user != opposite_user_key {
+        return Err(ErrorCode::InconsistentUserKey.into());
+    }
+    if !market.is_market(&next_event.token_buy, &next_event.token_sell) {
+        return Err(ErrorCode::EventMarketMismatch.into());
+    }
+    check_not_halted(market)?;
+    market.settle_fill();
+
+    let income = next_event.buy_quantity.checked_sub(next_event.fee).ok_or(ErrorCode::SettlementOverflow)?;
+    user_token_income_ledger.available_balance = user_token_income_ledger.available_balance
+        .checked_add(income)
+        .ok_or(ErrorCode::SettlementOverflow)?;
+    let referrer = user_token_income_ledger.referrer;
+    if !self_fill {
+        let now = Clock::get()?.unix_timestamp;
+        user_token_income_ledger.record_volume(next_event.buy_quantity, now);
+        user_token_outcome_ledger.record_volume(next_event.sell_quantity, now);
+    }
+
+    {
+        // The counterparty receives `token_sell` and pays `token_buy`
+        let (opposite_income, opposite_outcome) = if self_fill {
+            (&mut *user_token_outcome_ledger, &mut *user_token_income_ledger)
+        } else {
+            let opposite_income = opposite_user_token_income_ledger.as_deref_mut().ok_or(ErrorCode::MissingCounterpartyLedger)?;
+            let opposite_outcome = opposite_user_token_outcome_ledger.as_deref_mut().ok_or(ErrorCode::MissingCounterpartyLedger)?;
+            check_ledger(opposite_income, &next_event.oppo_user, &next_event.token_sell, ErrorCode::IncomeLedgerMintMismatch)?;
+            check_ledger(opposite_outcome, &next_event.oppo_user, &next_event.token_buy, ErrorCode::OutcomeLedgerMintMismatch)?;
+            let now = Clock::get()?.unix_timestamp;
+            opposite_income.record_volume(next_event.sell_quantity, now);
+            opposite_outcome.record_volume(next_event.buy_quantity, now);
+            (opposite_income, opposite_outcome)
+        };
+        opposite_outcome.locked_balance = opposite_outcome.locked_balance
+            .checked_sub(next_event.buy_quantity)
+            .ok_or(ErrorCode::SettlementOverflow)?;
+        let referral_paid = if referrer == next_event.oppo_user && next_event.referral_fee > 0 {
+            opposite_outcome.credit_referral(next_event.referral_fee)?;
+            next_event.referral_fee
+        } else {
+            referrers.credit_referrer(&referrer, &next_event.token_buy, next_event.referral_fee)?
+        };
+        income_fee_ledger.accrue(retained_taker_fee(next_event.fee, next_event.oppo_rebate, referral_paid)?)?;
+        let opposite_income_quantity = next_event.sell_quantity.checked_sub(next_event.oppo_fee).ok_or(ErrorCode::SettlementOverflow)?;
+        opposite_income.available_balance = opposite_income.available_balance
+            .checked_add(opposite_income_quantity)
+            .ok_or(ErrorCode::SettlementOverflow)?;
+        outcome_fee_ledger.accrue(next_event.oppo_fee)?;
+        opposite_outcome.available_balance = opposite_outcome.available_balance
+            .checked_add(next_event.oppo_rebate)
+            .ok_or(ErrorCode::SettlementOverflow)?;
+    }
+
+    if self_fill {
+        if let Some(copy) = opposite_user_token_income_ledger {
+            sync_copy(copy, user_token_outcome_ledger);
+        }
+        if let Some(copy) = opposite_user_token_outcome_ledger {
+            sync_copy(copy, user_token_income_ledger);
         }
-        user_token_outcome_ledger.locked_balance -= next_event.sell_quantity;
-        opposite_user_token_outcome_ledger.locked_balance -= next_event.buy_quantity;
-        user_token_income_ledger.available_balance += next_event.buy_quantity;
-        opposite_user_token_income_ledger.available_balance += next_event.sell_quantity;
     }
+    Ok(true)
+}
 
-    if event_list.length() == 0 {
-        event_list.close();
-    } 
+/// Leaves `copy`, a second copy of `ledger`'s account, holding the same state.
+fn sync_copy(copy: &mut IndividualTokenLedgerAccount, ledger: &IndividualTokenLedgerAccount) {
+    if copy.owner == ledger.owner && copy.mint_account == ledger.mint_account {
+        *copy = ledger.clone();
+    }
+}
+
+/// Makes sure a ledger belongs to `owner` and holds `mint` before the event moves funds on it.
+fn check_ledger(ledger: &IndividualTokenLedgerAccount, owner: &Pubkey, mint: &Pubkey, mint_error: ErrorCode) -> Result<()> {
+    if ledger.owner != *owner {
+        return Err(ErrorCode::LedgerOwnerMismatch.into());
+    }
+    if ledger.mint_account != *mint {
+        return Err(mint_error.into());
+    }
     Ok(())
 }
 
@@ -54,7 +168,7 @@ pub struct ConsumeEvents<'info> {
         bump = event_list.bump,
         has_one = user
     )]
-    pub event_list: Account<'info, EventList>,
+    pub event_list: Box<Account<'info, EventList>>,
     #[account(
         mut, 
         seeds = [INDIVIDUAL_TOKEN_LEDGER_SEED, user_token_income_ledger.mint_account.as_ref(), user.key().as_ref()], 
@@ -72,13 +186,28 @@ pub struct ConsumeEvents<'info> {
         seeds = [INDIVIDUAL_TOKEN_LEDGER_SEED, opposite_user_token_income_ledger.mint_account.as_ref(), opposite_user_key.as_ref()],
         bump = opposite_user_token_income_ledger.bump
     )]
-    pub opposite_user_token_income_ledger: Account<'info, IndividualTokenLedgerAccount>,
+    pub opposite_user_token_income_ledger: Option<Box<Account<'info, IndividualTokenLedgerAccount>>>,
     #[account(
         mut,
         seeds = [INDIVIDUAL_TOKEN_LEDGER_SEED, opposite_user_token_outcome_ledger.mint_account.as_ref(), opposite_user_key.as_ref()],
         bump = opposite_user_token_outcome_ledger.bump
     )]
-    pub opposite_user_token_outcome_ledger: Account<'info, IndividualTokenLedgerAccount>,
+    pub opposite_user_token_outcome_ledger: Option<Box<Account<'info, IndividualTokenLedgerAccount>>>,
+    #[account(
+        mut,
+        seeds = [FEE_LEDGER_SEED, user_token_income_ledger.mint_account.as_ref()],
+        bump = income_fee_ledger.bump
+    )]
+    pub income_fee_ledger: Account<'info, FeeLedgerAccount>,
+    #[account(
+        mut,
+        seeds = [FEE_LEDGER_SEED, user_token_outcome_ledger.mint_account.as_ref()],
+        bump = outcome_fee_ledger.bump
+    )]
+    pub outcome_fee_ledger: Account<'info, FeeLedgerAccount>,
+    /// The market of the event, checked against it once popped
+    #[account(mut)]
+    pub market: Account<'info, Market>,
 
     #[account(mut)]
     pub user: Signer<'info>,
@@ -0,0 +1,94 @@
+use anchor_lang::prelude::*;
+use std::cmp::{max, min};
+use crate::instructions::common::check_not_halted;
+use crate::instructions::settlement::{retained_taker_fee, settle_maker_fill, LedgerCache};
+use crate::state::EventList;
+use crate::state::{Market, MARKET_SEED};
+use crate::state::ORDER_EVENTS_SEED;
+use crate::error::ErrorCode;
+
+/// Drains up to `max_events` entries of the user's `EventList` in one go.
+/// Pending events may come from different orders and markets, so the token
+/// ledgers of the user and of every counterparty involved are all passed
+/// through `remaining_accounts`, along with the fee ledger of every mint a
+/// fee is charged in, the referrer's ledger of every mint it earns in and the
+/// writable `Market` of every market a fill is settled in.
+pub fn consume_events_batch_impl<'info>(ctx: Context<'_, '_, 'info, 'info, ConsumeEventsBatch<'info>>, max_events: u8) -> Result<()> {
+    if max_events == 0 {
+        return Err(ErrorCode::InvalidMaxEvents.into());
+    }
+    let user_key = ctx.accounts.user.key();
+    let event_list: &mut EventList = &mut ctx.accounts.event_list;
+    let mut ledgers = LedgerCache::new(ctx.remaining_accounts);
+    let now = Clock::get()?.unix_timestamp;
+
+    let mut consumed = 0;
+    while consumed < max_events {
+        let Some(event) = event_list.pop() else {
+            break;
+        };
+        let user_token_outcome_ledger = ledgers.get_mut(&user_key, &event.token_sell)?;
+        user_token_outcome_ledger.locked_balance = user_token_outcome_ledger.locked_balance
+            .checked_sub(event.sell_quantity)
+            .ok_or(ErrorCode::SettlementOverflow)?;
+        if event.rollback {
+            user_token_outcome_ledger.available_balance = user_token_outcome_ledger.available_balance
+                .checked_add(event.sell_quantity)
+                .ok_or(ErrorCode::SettlementOverflow)?;
+        } else {
+            let mut market = market(ctx.remaining_accounts, &event.token_buy, &event.token_sell)?;
+            check_not_halted(&market)?;
+            market.settle_fill();
+            market.exit(&crate::ID)?;
+            if event.oppo_user != user_key {
+                user_token_outcome_ledger.record_volume(event.sell_quantity, now);
+            }
+            settle_maker_fill(
+                &mut ledgers, &user_key, &event.oppo_user, &event.token_buy, &event.token_sell,
+                event.buy_quantity, event.sell_quantity, event.oppo_fee, event.oppo_rebate,
+            )?;
+            let income = event.buy_quantity.checked_sub(event.fee).ok_or(ErrorCode::SettlementOverflow)?;
+            let user_token_income_ledger = ledgers.get_mut(&user_key, &event.token_buy)?;
+            user_token_income_ledger.available_balance = user_token_income_ledger.available_balance
+                .checked_add(income)
+                .ok_or(ErrorCode::SettlementOverflow)?;
+            if event.oppo_user != user_key {
+                user_token_income_ledger.record_volume(event.buy_quantity, now);
+            }
+            let referrer = user_token_income_ledger.referrer;
+            let referral_paid = ledgers.credit_referrer(&referrer, &event.token_buy, event.referral_fee)?;
+            ledgers.accrue_fee(&event.token_buy, retained_taker_fee(event.fee, event.oppo_rebate, referral_paid)?)?;
+        }
+        consumed += 1;
+    }
+    ledgers.exit()?;
+
+    msg!("Consumed {} events, {} left", consumed, event_list.length());
+    Ok(())
+}
+
+/// Finds the `Market` of `token_a` and `token_b`, whichever of them is its base.
+fn market<'info>(accounts: &'info [AccountInfo<'info>], token_a: &Pubkey, token_b: &Pubkey) -> Result<Account<'info, Market>> {
+    let (address, _) = Pubkey::find_program_address(
+        &[MARKET_SEED, min(token_a, token_b).as_ref(), max(token_a, token_b).as_ref()],
+        &crate::ID,
+    );
+    let info = accounts.iter()
+        .find(|info| info.key == &address)
+        .ok_or(ErrorCode::MissingMarket)?;
+    Account::try_from(info)
+}
+
+#[derive(Accounts)]
+pub struct ConsumeEventsBatch<'info> {
+    #[account(
+        mut,
+        seeds = [ORDER_EVENTS_SEED, user.key().as_ref()],
+        bump = event_list.bump,
+        has_one = user
+    )]
+    pub event_list: Box<Account<'info, EventList>>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+}
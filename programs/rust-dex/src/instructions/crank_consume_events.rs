@@ -0,0 +1,121 @@
+use anchor_lang::prelude::*;
+use std::cmp::{max, min};
+use crate::instructions::consume_events::consume_next_event;
+use crate::instructions::settlement::LedgerCache;
+use crate::state::{CrankPool, EventList, FeeLedgerAccount, IndividualTokenLedgerAccount, Market};
+use crate::state::CRANK_POOL_SEED;
+use crate::state::FEE_LEDGER_SEED;
+use crate::state::INDIVIDUAL_TOKEN_LEDGER_SEED;
+use crate::state::ORDER_EVENTS_SEED;
+use crate::error::ErrorCode;
+
+/// Permissionless `consume_events`: any keeper may settle the oldest event of
+/// `owner`'s event list. Every ledger is bound to the market of that event
+/// through its PDA seeds, so a keeper can only move funds the event entitles. When a crank
+/// pool is passed, the keeper is paid its reward for a consumed fill against
+/// another user as long as the pool stays rent exempt. Rollbacks and self
+/// fills earn nothing, as any user could otherwise mint them to drain the pool.
+///
+/// As with `consume_events`, the ledger of the owner's referrer goes through
+/// `remaining_accounts` when a referral fee is due.
+///
+/// Rollback events only touch the owner's ledgers, the counterparty ledgers may
+/// then be left out. `opposite_user_key` is never the owner but for a fill
+/// against its own order.
+pub fn crank_consume_events_impl<'info>(ctx: Context<'_, '_, 'info, 'info, CrankConsumeEvents<'info>>, owner: Pubkey, opposite_user_key: Pubkey) -> Result<()> {
+    msg!("Cranking events of {} against {}", owner, opposite_user_key);
+    let mut referrers = LedgerCache::new(ctx.remaining_accounts);
+    let rewarded = ctx.accounts.event_list.next_is_counterparty_fill();
+    let consumed = consume_next_event(
+        &mut ctx.accounts.event_list,
+        &mut ctx.accounts.user_token_income_ledger,
+        &mut ctx.accounts.user_token_outcome_ledger,
+        ctx.accounts.opposite_user_token_income_ledger.as_deref_mut().map(|ledger| &mut **ledger),
+        ctx.accounts.opposite_user_token_outcome_ledger.as_deref_mut().map(|ledger| &mut **ledger),
+        &mut ctx.accounts.income_fee_ledger,
+        &mut ctx.accounts.outcome_fee_ledger,
+        &mut referrers,
+        &mut ctx.accounts.market,
+        opposite_user_key,
+    )?;
+    referrers.exit()?;
+    if !consumed || !rewarded {
+        return Ok(());
+    }
+
+    if let Some(crank_pool) = &ctx.accounts.crank_pool {
+        let pool_info = crank_pool.to_account_info();
+        let rent_exempt = Rent::get()?.minimum_balance(pool_info.data_len());
+        let spare = pool_info.lamports().saturating_sub(rent_exempt);
+        let reward = crank_pool.reward_per_event.min(spare);
+        if reward > 0 {
+            **pool_info.try_borrow_mut_lamports()? -= reward;
+            **ctx.accounts.keeper.to_account_info().try_borrow_mut_lamports()? += reward;
+            msg!("Paid keeper {} lamports", reward);
+        }
+    }
+    Ok(())
+}
+
+#[derive(Accounts)]
+#[instruction(owner: Pubkey, opposite_user_key: Pubkey)]
+pub struct CrankConsumeEvents<'info> {
+    #[account(
+        mut,
+        seeds = [ORDER_EVENTS_SEED, owner.as_ref()],
+        bump = event_list.bump,
+        constraint = event_list.user == owner,
+        constraint = !event_list.is_empty() @ ErrorCode::NoPendingEvents
+    )]
+    pub event_list: Box<Account<'info, EventList>>,
+    #[account(
+        mut,
+        seeds = [INDIVIDUAL_TOKEN_LEDGER_SEED, event_list.next_token_buy().as_ref(), owner.as_ref()],
+        bump = user_token_income_ledger.bump
+    )]
+    pub user_token_income_ledger: Box<Account<'info, IndividualTokenLedgerAccount>>,
+    #[account(
+        mut,
+        seeds = [INDIVIDUAL_TOKEN_LEDGER_SEED, event_list.next_token_sell().as_ref(), owner.as_ref()],
+        bump = user_token_outcome_ledger.bump
+    )]
+    pub user_token_outcome_ledger: Box<Account<'info, IndividualTokenLedgerAccount>>,
+    #[account(
+        mut,
+        seeds = [INDIVIDUAL_TOKEN_LEDGER_SEED, event_list.next_token_sell().as_ref(), opposite_user_key.as_ref()],
+        bump = opposite_user_token_income_ledger.bump
+    )]
+    pub opposite_user_token_income_ledger: Option<Box<Account<'info, IndividualTokenLedgerAccount>>>,
+    #[account(
+        mut,
+        seeds = [INDIVIDUAL_TOKEN_LEDGER_SEED, event_list.next_token_buy().as_ref(), opposite_user_key.as_ref()],
+        bump = opposite_user_token_outcome_ledger.bump
+    )]
+    pub opposite_user_token_outcome_ledger: Option<Box<Account<'info, IndividualTokenLedgerAccount>>>,
+    #[account(
+        mut,
+        seeds = [FEE_LEDGER_SEED, event_list.next_token_buy().as_ref()],
+        bump = income_fee_ledger.bump
+    )]
+    pub income_fee_ledger: Box<Account<'info, FeeLedgerAccount>>,
+    #[account(
+        mut,
+        seeds = [FEE_LEDGER_SEED, event_list.next_token_sell().as_ref()],
+        bump = outcome_fee_ledger.bump
+    )]
+    pub outcome_fee_ledger: Box<Account<'info, FeeLedgerAccount>>,
+    /// The market of the event, checked against it once popped
+    #[account(mut)]
+    pub market: Box<Account<'info, Market>>,
+
+    #[account(
+        mut,
+        seeds = [CRANK_POOL_SEED, min(&crank_pool.base_mint, &crank_pool.quote_mint).as_ref(), max(&crank_pool.base_mint, &crank_pool.quote_mint).as_ref()],
+        bump = crank_pool.bump,
+        constraint = crank_pool.is_market(event_list.next_token_buy(), event_list.next_token_sell()) @ ErrorCode::InvalidCrankPool
+    )]
+    pub crank_pool: Option<Account<'info, CrankPool>>,
+
+    #[account(mut)]
+    pub keeper: Signer<'info>,
+}
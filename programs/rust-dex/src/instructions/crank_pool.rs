@@ -0,0 +1,98 @@
+use anchor_lang::prelude::*;
+use std::cmp::{max, min};
+use anchor_lang::system_program;
+use crate::state::{CrankPool, Market};
+use crate::state::{DexManager, DEX_MANAGER_SEED};
+use crate::state::CRANK_POOL_SEED;
+use crate::state::MARKET_SEED;
+use crate::error::ErrorCode;
+
+/// Creates the crank pool of the `base`/`quote` market. The admin picks the
+/// per-event reward and may change it with `set_crank_reward`, anyone may
+/// fund the pool afterwards.
+pub fn init_crank_pool_impl(ctx: Context<InitCrankPool>, base: Pubkey, quote: Pubkey, reward_per_event: u64) -> Result<()> {
+    msg!("Initializing crank pool for {} / {} with reward {}", base, quote, reward_per_event);
+    let crank_pool = &mut ctx.accounts.crank_pool;
+    crank_pool.base_mint = base;
+    crank_pool.quote_mint = quote;
+    crank_pool.reward_per_event = reward_per_event;
+    crank_pool.bump = ctx.bumps.crank_pool;
+    Ok(())
+}
+
+pub fn set_crank_reward_impl(ctx: Context<SetCrankReward>, base: Pubkey, quote: Pubkey, reward_per_event: u64) -> Result<()> {
+    msg!("Setting crank reward of {} / {} to {}", base, quote, reward_per_event);
+    ctx.accounts.crank_pool.reward_per_event = reward_per_event;
+    Ok(())
+}
+
+pub fn fund_crank_pool_impl(ctx: Context<FundCrankPool>, lamports: u64) -> Result<()> {
+    msg!("Funding crank pool {} with {} lamports", ctx.accounts.crank_pool.key(), lamports);
+    if lamports == 0 {
+        return Err(ErrorCode::InvalidAmount.into());
+    }
+    let cpi_ctx = CpiContext::new(
+        ctx.accounts.system_program.to_account_info(),
+        system_program::Transfer {
+            from: ctx.accounts.user.to_account_info(),
+            to: ctx.accounts.crank_pool.to_account_info(),
+        },
+    );
+    system_program::transfer(cpi_ctx, lamports)
+}
+
+#[derive(Accounts)]
+#[instruction(base: Pubkey, quote: Pubkey)]
+pub struct InitCrankPool<'info> {
+    #[account(
+        init,
+        payer = admin,
+        seeds = [CRANK_POOL_SEED, min(&base, &quote).as_ref(), max(&base, &quote).as_ref()],
+        bump,
+        space = CrankPool::SIZE
+    )]
+    pub crank_pool: Account<'info, CrankPool>,
+    /// Only registered markets get a pool
+    #[account(seeds = [MARKET_SEED, min(&base, &quote).as_ref(), max(&base, &quote).as_ref()], bump = market.bump)]
+    pub market: Account<'info, Market>,
+    #[account(
+        seeds = [DEX_MANAGER_SEED],
+        bump = dex_manager.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub dex_manager: Account<'info, DexManager>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(base: Pubkey, quote: Pubkey)]
+pub struct SetCrankReward<'info> {
+    #[account(
+        mut,
+        seeds = [CRANK_POOL_SEED, min(&base, &quote).as_ref(), max(&base, &quote).as_ref()],
+        bump = crank_pool.bump
+    )]
+    pub crank_pool: Account<'info, CrankPool>,
+    #[account(
+        seeds = [DEX_MANAGER_SEED],
+        bump = dex_manager.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub dex_manager: Account<'info, DexManager>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct FundCrankPool<'info> {
+    #[account(
+        mut,
+        seeds = [CRANK_POOL_SEED, min(&crank_pool.base_mint, &crank_pool.quote_mint).as_ref(), max(&crank_pool.base_mint, &crank_pool.quote_mint).as_ref()],
+        bump = crank_pool.bump
+    )]
+    pub crank_pool: Account<'info, CrankPool>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
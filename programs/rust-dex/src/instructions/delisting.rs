@@ -0,0 +1,219 @@
+use anchor_lang::prelude::*;
+use std::cmp::{max, min};
+use crate::common::MarketStatus;
+use crate::instructions::common::OrderEventEmitter;
+use crate::state::{DexManager, IndividualTokenLedgerAccount, Market, MarketEventLog, TokenPairAccount, UserOrderbook};
+use crate::state::CRANK_POOL_SEED;
+use crate::state::DEX_MANAGER_SEED;
+use crate::state::MARKET_SEED;
+use crate::state::MARKET_EVENTS_SEED;
+use crate::state::TOKEN_PAIR_SEED;
+use crate::state::USER_ORDERBOOK_SEED;
+use crate::OrderHeap;
+use crate::error::ErrorCode;
+use crate::event_sink;
+
+/// Permissionless: cancels the orders left on both books of the delisted
+/// `base`/`quote` market whose owner's token ledger of the sold mint is passed,
+/// writable, through `remaining_accounts`, along with the owner's
+/// `UserOrderbook`. The locked tokens go back to the owner's
+/// `available_balance` and the order ids leave the owner's `UserOrderbook`.
+pub fn cancel_delisted_orders_impl<'info>(ctx: Context<'_, '_, 'info, 'info, CancelDelistedOrders<'info>>, base: Pubkey, quote: Pubkey) -> Result<()> {
+    if ctx.accounts.market.status() != MarketStatus::Delisted {
+        return Err(ErrorCode::MarketNotDelisted.into());
+    }
+    let mut market_events = ctx.accounts.market_events.load_mut()?;
+    let (mut ledgers, mut orderbooks) = owner_accounts(ctx.remaining_accounts)?;
+    let timestamp = Clock::get()?.unix_timestamp;
+    let mut lifecycle = OrderEventEmitter::new(
+        &mut ctx.accounts.dex_manager, event_sink!(ctx), ctx.accounts.market.key(), ctx.accounts.market.base_mint, timestamp
+    );
+
+    let mut cancelled = 0;
+    for queue in [&ctx.accounts.base_quote_queue, &ctx.accounts.quote_base_queue] {
+        let mut queue = queue.load_mut()?;
+        let heap = &mut queue.order_heap;
+        let order_ids: Vec<u64> = heap.orders[..heap.len()]
+            .iter()
+            .filter(|order| ledgers.iter().any(|ledger| ledger.owner == order.owner && ledger.mint_account == order.sell_token))
+            .map(|order| order.id)
+            .collect();
+        for order_id in order_ids {
+            let node = heap.remove_order(order_id)?;
+            let ledger = ledgers
+                .iter_mut()
+                .find(|ledger| ledger.owner == node.owner && ledger.mint_account == node.sell_token)
+                .ok_or(ErrorCode::MissingCounterpartyLedger)?;
+            ledger.locked_balance = ledger.locked_balance
+                .checked_sub(node.sell_quantity)
+                .ok_or(ErrorCode::BalanceOverflow)?;
+            ledger.available_balance = ledger.available_balance
+                .checked_add(node.sell_quantity)
+                .ok_or(ErrorCode::BalanceOverflow)?;
+            orderbooks
+                .iter_mut()
+                .find(|(owner, _)| *owner == node.owner)
+                .ok_or(ErrorCode::MissingUserOrderbook)?
+                .1
+                .try_remove_order(node.id as u128);
+            market_events.push_out(node.owner, node.id, &node.buy_token, node.buy_quantity, node.sell_quantity, timestamp);
+            lifecycle.cancelled(&node)?;
+            cancelled += 1;
+        }
+    }
+    for ledger in ledgers.iter() {
+        ledger.exit(&crate::ID)?;
+    }
+    for (_, orderbook) in orderbooks.iter() {
+        orderbook.exit(&crate::ID)?;
+    }
+    msg!("Cancelled {} orders of {} / {}", cancelled, base, quote);
+    Ok(())
+}
+
+type OwnerOrderbook<'info> = (Pubkey, Account<'info, UserOrderbook>);
+
+/// Token ledgers and `UserOrderbook`s passed to `cancel_delisted_orders`, each
+/// loaded once. An orderbook is told apart by its discriminator and kept with
+/// the owner its address is derived from.
+fn owner_accounts<'info>(
+    accounts: &'info [AccountInfo<'info>],
+) -> Result<(Vec<Account<'info, IndividualTokenLedgerAccount>>, Vec<OwnerOrderbook<'info>>)> {
+    let mut ledgers: Vec<Account<'info, IndividualTokenLedgerAccount>> = Vec::with_capacity(accounts.len());
+    let mut orderbooks: Vec<OwnerOrderbook<'info>> = Vec::new();
+    for info in accounts {
+        if ledgers.iter().any(|ledger| ledger.key() == info.key())
+            || orderbooks.iter().any(|(_, orderbook)| orderbook.key() == info.key()) {
+            continue;
+        }
+        if !info.is_writable {
+            return Err(ErrorCode::CounterpartyLedgerNotWritable.into());
+        }
+        if info.try_borrow_data()?.starts_with(UserOrderbook::DISCRIMINATOR) {
+            let orderbook: Account<'info, UserOrderbook> = Account::try_from(info)?;
+            let owner = ledgers
+                .iter()
+                .map(|ledger| ledger.owner)
+                .find(|owner| {
+                    Pubkey::create_program_address(&[USER_ORDERBOOK_SEED, owner.as_ref(), &[orderbook.bump]], &crate::ID)
+                        .is_ok_and(|address| address == info.key())
+                })
+                .ok_or(ErrorCode::MissingUserOrderbook)?;
+            orderbooks.push((owner, orderbook));
+        } else {
+            ledgers.push(Account::try_from(info)?);
+        }
+    }
+    Ok((ledgers, orderbooks))
+}
+
+/// Closes both books, the `Market`, the event log and the crank pool, if any,
+/// of the delisted `base`/`quote` market, refunding their rent and what is left
+/// in the pool to the admin. The books must be empty and every fill of the
+/// market settled, so no user needs the market anymore. The pair may be
+/// registered again afterwards.
+pub fn close_market_impl(ctx: Context<CloseMarket>, base: Pubkey, quote: Pubkey) -> Result<()> {
+    msg!("Closing market {} / {}", base, quote);
+    let market = &ctx.accounts.market;
+    if market.status() != MarketStatus::Delisted {
+        return Err(ErrorCode::MarketNotDelisted.into());
+    }
+    if market.pending_fills > 0 {
+        return Err(ErrorCode::MarketHasPendingFills.into());
+    }
+    if ctx.accounts.base_quote_queue.load()?.order_heap.len() > 0 || ctx.accounts.quote_base_queue.load()?.order_heap.len() > 0 {
+        return Err(ErrorCode::MarketNotEmpty.into());
+    }
+
+    // Markets get a crank pool only once the admin creates one
+    let pool_info = ctx.accounts.crank_pool.to_account_info();
+    if pool_info.owner == &crate::ID {
+        let lamports = pool_info.lamports();
+        **pool_info.try_borrow_mut_lamports()? = 0;
+        **ctx.accounts.admin.to_account_info().try_borrow_mut_lamports()? += lamports;
+        pool_info.assign(&System::id());
+        pool_info.resize(0)?;
+        msg!("Closed crank pool, refunding {} lamports", lamports);
+    }
+    Ok(())
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+#[instruction(base: Pubkey, quote: Pubkey)]
+pub struct CancelDelistedOrders<'info> {
+    #[account(
+        mut,
+        seeds = [TOKEN_PAIR_SEED, base.as_ref(), quote.as_ref()],
+        bump,
+    )]
+    pub base_quote_queue: AccountLoader<'info, TokenPairAccount>,
+    #[account(
+        mut,
+        seeds = [TOKEN_PAIR_SEED, quote.as_ref(), base.as_ref()],
+        bump,
+    )]
+    pub quote_base_queue: AccountLoader<'info, TokenPairAccount>,
+    #[account(
+        seeds = [MARKET_SEED, min(&base, &quote).as_ref(), max(&base, &quote).as_ref()],
+        bump = market.bump,
+    )]
+    pub market: Box<Account<'info, Market>>,
+    #[account(
+        mut,
+        seeds = [MARKET_EVENTS_SEED, min(&base, &quote).as_ref(), max(&base, &quote).as_ref()],
+        bump,
+    )]
+    pub market_events: AccountLoader<'info, MarketEventLog>,
+    #[account(mut, seeds = [DEX_MANAGER_SEED], bump = dex_manager.bump)]
+    pub dex_manager: Account<'info, DexManager>,
+    pub keeper: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(base: Pubkey, quote: Pubkey)]
+pub struct CloseMarket<'info> {
+    #[account(
+        mut,
+        seeds = [TOKEN_PAIR_SEED, base.as_ref(), quote.as_ref()],
+        bump,
+        close = admin
+    )]
+    pub base_quote_queue: AccountLoader<'info, TokenPairAccount>,
+    #[account(
+        mut,
+        seeds = [TOKEN_PAIR_SEED, quote.as_ref(), base.as_ref()],
+        bump,
+        close = admin
+    )]
+    pub quote_base_queue: AccountLoader<'info, TokenPairAccount>,
+    #[account(
+        mut,
+        seeds = [MARKET_SEED, min(&base, &quote).as_ref(), max(&base, &quote).as_ref()],
+        bump = market.bump,
+        close = admin
+    )]
+    pub market: Box<Account<'info, Market>>,
+    #[account(
+        mut,
+        seeds = [MARKET_EVENTS_SEED, min(&base, &quote).as_ref(), max(&base, &quote).as_ref()],
+        bump,
+        close = admin
+    )]
+    pub market_events: AccountLoader<'info, MarketEventLog>,
+    /// CHECK: the market's crank pool, closed by `close_market` when the admin created one
+    #[account(
+        mut,
+        seeds = [CRANK_POOL_SEED, min(&base, &quote).as_ref(), max(&base, &quote).as_ref()],
+        bump,
+    )]
+    pub crank_pool: UncheckedAccount<'info>,
+    #[account(
+        seeds = [DEX_MANAGER_SEED],
+        bump = dex_manager.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub dex_manager: Account<'info, DexManager>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+}
@@ -4,9 +4,13 @@ use anchor_spl::token;
 use crate::state::{VaultTokenLedgerAccount, IndividualTokenLedgerAccount};
 use crate::state::INDIVIDUAL_TOKEN_LEDGER_SEED;
 use crate::state::VAULT_TOKEN_LEDGER_SEED;
+use crate::error::ErrorCode;
 
 pub fn deposit_impl(ctx: Context<Deposit>, _mint_account: Pubkey, amount: u64) -> Result<()> {
     msg!("Deposit amount: {}", amount);
+    if amount == 0 {
+        return Err(ErrorCode::InvalidAmount.into());
+    }
     let cpi_accounts = anchor_spl::token::Transfer {
         from: ctx.accounts.user_token_account.to_account_info(),
         to: ctx.accounts.vault_token_account.to_account_info(),
@@ -18,8 +22,12 @@ pub fn deposit_impl(ctx: Context<Deposit>, _mint_account: Pubkey, amount: u64) -
     // Update ledger balances
     let user_ledger = &mut ctx.accounts.user_token_ledger;
     let vault_ledger = &mut ctx.accounts.vault_token_ledger;
-    user_ledger.available_balance = user_ledger.available_balance.checked_add(amount).unwrap();
-    vault_ledger.total_balance = vault_ledger.total_balance.checked_add(amount).unwrap();
+    user_ledger.available_balance = user_ledger.available_balance
+        .checked_add(amount)
+        .ok_or(ErrorCode::BalanceOverflow)?;
+    vault_ledger.total_balance = vault_ledger.total_balance
+        .checked_add(amount)
+        .ok_or(ErrorCode::BalanceOverflow)?;
     Ok(())
 }
 
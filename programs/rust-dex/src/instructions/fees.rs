@@ -0,0 +1,194 @@
+use anchor_lang::prelude::*;
+use std::cmp::{max, min};
+use anchor_spl::token::{Token, TokenAccount};
+use crate::common::{FeeRates, FeesCollectedEvent, MAX_FEE_BPS, MAX_REFERRAL_SHARE_BPS};
+use crate::state::{DexManager, FeeLedgerAccount, FeeTier, FeeTierTable, Market, VaultTokenLedgerAccount};
+use crate::market_seeds;
+use crate::state::DEX_MANAGER_SEED;
+use crate::state::FEE_LEDGER_SEED;
+use crate::state::FEE_TIERS_SEED;
+use crate::state::MARKET_SEED;
+use crate::state::VAULT_TOKEN_LEDGER_SEED;
+use crate::error::ErrorCode;
+
+/// Sets the fee rates of the `base`/`quote` market. They apply to fills of
+/// orders placed from now on, fills already waiting in event lists keep the
+/// fees they were matched with.
+pub fn set_market_fees_impl(ctx: Context<SetMarketFees>, base: Pubkey, quote: Pubkey, maker_fee_bps: u16, taker_fee_bps: u16) -> Result<()> {
+    msg!("Setting fees of {} / {}: maker {} bps, taker {} bps", base, quote, maker_fee_bps, taker_fee_bps);
+    if maker_fee_bps > MAX_FEE_BPS || taker_fee_bps > MAX_FEE_BPS {
+        return Err(ErrorCode::InvalidFeeRate.into());
+    }
+    ctx.accounts.market.set_fee_rates(FeeRates { maker_fee_bps, taker_fee_bps });
+    Ok(())
+}
+
+/// Sets the share of referred users' taker fees, after maker rebates, that is
+/// paid to their referrer. Like fee rates, it applies to orders placed from now on.
+pub fn set_referral_share_impl(ctx: Context<SetReferralShare>, share_bps: u16) -> Result<()> {
+    msg!("Setting referral share to {} bps", share_bps);
+    if share_bps > MAX_REFERRAL_SHARE_BPS {
+        return Err(ErrorCode::InvalidReferralShare.into());
+    }
+    ctx.accounts.dex_manager.referral_share_bps = share_bps;
+    Ok(())
+}
+
+/// Creates the fee tier table of the markets quoted in `quote_mint`.
+pub fn init_fee_tiers_impl(ctx: Context<InitFeeTiers>, quote_mint: Pubkey, tiers: Vec<FeeTier>) -> Result<()> {
+    msg!("Initializing {} fee tiers for {}", tiers.len(), quote_mint);
+    let fee_tiers = &mut ctx.accounts.fee_tiers;
+    fee_tiers.quote_mint = quote_mint;
+    fee_tiers.bump = ctx.bumps.fee_tiers;
+    fee_tiers.set_tiers(&tiers)
+}
+
+/// Replaces the fee tiers of the markets quoted in `quote_mint`. Like base
+/// rate changes, they apply to orders placed from now on.
+pub fn set_fee_tiers_impl(ctx: Context<SetFeeTiers>, quote_mint: Pubkey, tiers: Vec<FeeTier>) -> Result<()> {
+    msg!("Setting {} fee tiers for {}", tiers.len(), quote_mint);
+    ctx.accounts.fee_tiers.set_tiers(&tiers)
+}
+
+/// Sends every fee accrued in `mint` from the vault to `destination`.
+pub fn collect_fees_impl(ctx: Context<CollectFees>, mint: Pubkey) -> Result<()> {
+    let amount = ctx.accounts.fee_ledger.accrued_fees;
+    msg!("Collecting {} of {} in fees", amount, mint);
+    if amount == 0 {
+        return Err(ErrorCode::NoFeesToCollect.into());
+    }
+    let fee_ledger = &mut ctx.accounts.fee_ledger;
+    fee_ledger.accrued_fees = 0;
+    fee_ledger.collected_fees = fee_ledger.collected_fees
+        .checked_add(amount)
+        .ok_or(ErrorCode::BalanceOverflow)?;
+    let vault_ledger = &mut ctx.accounts.vault_token_ledger;
+    vault_ledger.total_balance = vault_ledger.total_balance
+        .checked_sub(amount)
+        .ok_or(ErrorCode::InsufficientVaultBalance)?;
+
+    let signer_seeds: &[&[&[u8]]] = &[market_seeds!(ctx.accounts.vault_token_ledger, mint)];
+    let cpi_accounts = anchor_spl::token::Transfer {
+        from: ctx.accounts.vault_token_account.to_account_info(),
+        to: ctx.accounts.destination.to_account_info(),
+        authority: ctx.accounts.vault_token_authority.to_account_info(),
+    };
+    let cpi_ctx = CpiContext::new_with_signer(
+        ctx.accounts.token_program.to_account_info(), cpi_accounts, signer_seeds
+    );
+    anchor_spl::token::transfer(cpi_ctx, amount)?;
+
+    emit!(FeesCollectedEvent {
+        mint,
+        amount,
+        destination: ctx.accounts.destination.key(),
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+    Ok(())
+}
+
+#[derive(Accounts)]
+#[instruction(base: Pubkey, quote: Pubkey)]
+pub struct SetMarketFees<'info> {
+    #[account(
+        mut,
+        seeds = [MARKET_SEED, min(&base, &quote).as_ref(), max(&base, &quote).as_ref()],
+        bump = market.bump,
+    )]
+    pub market: Account<'info, Market>,
+    #[account(
+        seeds = [DEX_MANAGER_SEED],
+        bump = dex_manager.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub dex_manager: Account<'info, DexManager>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetReferralShare<'info> {
+    #[account(
+        mut,
+        seeds = [DEX_MANAGER_SEED],
+        bump = dex_manager.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub dex_manager: Account<'info, DexManager>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(quote_mint: Pubkey)]
+pub struct InitFeeTiers<'info> {
+    #[account(
+        init,
+        payer = admin,
+        seeds = [FEE_TIERS_SEED, quote_mint.as_ref()],
+        bump,
+        space = FeeTierTable::SIZE
+    )]
+    pub fee_tiers: Account<'info, FeeTierTable>,
+    #[account(
+        seeds = [DEX_MANAGER_SEED],
+        bump = dex_manager.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub dex_manager: Account<'info, DexManager>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(quote_mint: Pubkey)]
+pub struct SetFeeTiers<'info> {
+    #[account(
+        mut,
+        seeds = [FEE_TIERS_SEED, quote_mint.as_ref()],
+        bump = fee_tiers.bump,
+    )]
+    pub fee_tiers: Account<'info, FeeTierTable>,
+    #[account(
+        seeds = [DEX_MANAGER_SEED],
+        bump = dex_manager.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub dex_manager: Account<'info, DexManager>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(mint: Pubkey)]
+pub struct CollectFees<'info> {
+    #[account(
+        mut,
+        seeds = [FEE_LEDGER_SEED, mint.as_ref()],
+        bump = fee_ledger.bump,
+    )]
+    pub fee_ledger: Account<'info, FeeLedgerAccount>,
+    #[account(
+        mut,
+        seeds = [VAULT_TOKEN_LEDGER_SEED, mint.as_ref()],
+        bump = vault_token_ledger.bump,
+        has_one = vault_token_account
+    )]
+    pub vault_token_ledger: Account<'info, VaultTokenLedgerAccount>,
+    /// CHECK: This is a PDA used as token authority, derived from seeds
+    #[account(
+        seeds = [b"vault_token_account", mint.as_ref()],
+        bump = vault_token_ledger.authority_bump
+    )]
+    pub vault_token_authority: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub vault_token_account: Account<'info, TokenAccount>,
+    #[account(mut, token::mint = mint)]
+    pub destination: Account<'info, TokenAccount>,
+    #[account(
+        seeds = [DEX_MANAGER_SEED],
+        bump = dex_manager.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub dex_manager: Account<'info, DexManager>,
+    pub admin: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
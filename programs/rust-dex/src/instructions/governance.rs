@@ -0,0 +1,196 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::program::invoke_signed;
+use anchor_lang::Discriminator;
+use crate::state::{DexManager, GovernanceConfig, GovernanceProposal, ProposalAccountMeta};
+use crate::state::DEX_MANAGER_SEED;
+use crate::state::{GOVERNANCE_AUTHORITY_SEED, GOVERNANCE_PROPOSAL_SEED, GOVERNANCE_SEED};
+use crate::error::ErrorCode;
+
+/// Hands the admin authority to an M-of-N multisig of `signers`. From then on
+/// admin instructions only run through proposals, see `execute_proposal_impl`.
+pub fn init_governance_impl(ctx: Context<InitGovernance>, signers: Vec<Pubkey>, threshold: u8, timelock_secs: i64) -> Result<()> {
+    msg!("Handing the DEX admin to a {} of {} governance with a {}s timelock", threshold, signers.len(), timelock_secs);
+    let governance = &mut ctx.accounts.governance;
+    governance.configure(&signers, threshold, timelock_secs)?;
+    governance.proposal_count = 0;
+    governance.authority_bump = ctx.bumps.governance_authority;
+    governance.bump = ctx.bumps.governance;
+
+    let dex_manager = &mut ctx.accounts.dex_manager;
+    dex_manager.admin = ctx.accounts.governance_authority.key();
+    dex_manager.pending_admin = Pubkey::default();
+    Ok(())
+}
+
+/// Replaces the signers, threshold and timelock. Only runs as a proposal, and
+/// voids every proposal not executed yet.
+pub fn set_governance_impl(ctx: Context<SetGovernance>, signers: Vec<Pubkey>, threshold: u8, timelock_secs: i64) -> Result<()> {
+    msg!("Reconfiguring governance to {} of {} with a {}s timelock", threshold, signers.len(), timelock_secs);
+    ctx.accounts.governance.configure(&signers, threshold, timelock_secs)
+}
+
+/// Proposes calling another instruction of this program with `accounts` and
+/// `data`. The proposer's approval counts towards the threshold.
+pub fn create_proposal_impl(ctx: Context<CreateProposal>, accounts: Vec<ProposalAccountMeta>, data: Vec<u8>) -> Result<()> {
+    GovernanceProposal::check_instruction(&accounts, &data)?;
+    // Executing a proposal from a proposal would run before the outer one is marked executed
+    if data.starts_with(crate::instruction::ExecuteProposal::DISCRIMINATOR) {
+        return Err(ErrorCode::InvalidProposal.into());
+    }
+    let governance = &mut ctx.accounts.governance;
+    let index = governance.signer_index(&ctx.accounts.proposer.key())?;
+    let now = Clock::get()?.unix_timestamp;
+
+    let proposal = &mut ctx.accounts.proposal;
+    proposal.id = governance.proposal_count;
+    proposal.generation = governance.generation;
+    proposal.proposer = ctx.accounts.proposer.key();
+    proposal.approvals = 0;
+    proposal.approval_count = 0;
+    proposal.created_at = now;
+    proposal.approved_at = 0;
+    proposal.executed = false;
+    proposal.accounts = accounts;
+    proposal.data = data;
+    proposal.bump = ctx.bumps.proposal;
+    proposal.approve(index, governance.threshold, now)?;
+    msg!("Created proposal {}", proposal.id);
+
+    governance.proposal_count = governance.proposal_count
+        .checked_add(1)
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+    Ok(())
+}
+
+pub fn approve_proposal_impl(ctx: Context<ApproveProposal>) -> Result<()> {
+    let governance = &ctx.accounts.governance;
+    let proposal = &mut ctx.accounts.proposal;
+    msg!("Approving proposal {}", proposal.id);
+    if proposal.executed {
+        return Err(ErrorCode::ProposalAlreadyExecuted.into());
+    }
+    if proposal.generation != governance.generation {
+        return Err(ErrorCode::StaleProposal.into());
+    }
+    let index = governance.signer_index(&ctx.accounts.signer.key())?;
+    proposal.approve(index, governance.threshold, Clock::get()?.unix_timestamp)
+}
+
+/// Runs an approved proposal whose timelock elapsed, signed by the governance
+/// authority. Anyone may execute it; the proposal's accounts are passed as
+/// remaining accounts, with any keypair it names signing the transaction.
+pub fn execute_proposal_impl<'info>(ctx: Context<'_, '_, 'info, 'info, ExecuteProposal<'info>>) -> Result<()> {
+    let proposal = &mut ctx.accounts.proposal;
+    msg!("Executing proposal {}", proposal.id);
+    proposal.check_executable(&ctx.accounts.governance, Clock::get()?.unix_timestamp)?;
+    proposal.executed = true;
+
+    let authority = ctx.accounts.governance_authority.to_account_info();
+    let mut account_infos = Vec::with_capacity(proposal.accounts.len() + 1);
+    for meta in proposal.accounts.iter() {
+        let info = ctx.remaining_accounts
+            .iter()
+            .chain(std::iter::once(&authority))
+            .find(|info| info.key == &meta.pubkey)
+            .ok_or(ErrorCode::MissingProposalAccount)?;
+        account_infos.push(info.clone());
+    }
+    account_infos.push(ctx.accounts.rust_dex_program.to_account_info());
+
+    let ix = Instruction {
+        program_id: crate::ID,
+        accounts: proposal.accounts
+            .iter()
+            .map(|meta| AccountMeta { pubkey: meta.pubkey, is_signer: meta.is_signer, is_writable: meta.is_writable })
+            .collect(),
+        data: proposal.data.clone(),
+    };
+    invoke_signed(
+        &ix,
+        &account_infos,
+        &[&[GOVERNANCE_AUTHORITY_SEED, &[ctx.accounts.governance.authority_bump]]],
+    )?;
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct InitGovernance<'info> {
+    #[account(
+        init,
+        payer = admin,
+        seeds = [GOVERNANCE_SEED],
+        bump,
+        space = GovernanceConfig::SIZE
+    )]
+    pub governance: Account<'info, GovernanceConfig>,
+    /// CHECK: Data-less PDA that becomes the DEX admin, signing only for executed proposals
+    #[account(seeds = [GOVERNANCE_AUTHORITY_SEED], bump)]
+    pub governance_authority: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [DEX_MANAGER_SEED],
+        bump = dex_manager.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub dex_manager: Account<'info, DexManager>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetGovernance<'info> {
+    #[account(mut, seeds = [GOVERNANCE_SEED], bump = governance.bump)]
+    pub governance: Account<'info, GovernanceConfig>,
+    #[account(seeds = [GOVERNANCE_AUTHORITY_SEED], bump = governance.authority_bump)]
+    pub governance_authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(accounts: Vec<ProposalAccountMeta>, data: Vec<u8>)]
+pub struct CreateProposal<'info> {
+    #[account(mut, seeds = [GOVERNANCE_SEED], bump = governance.bump)]
+    pub governance: Account<'info, GovernanceConfig>,
+    #[account(
+        init,
+        payer = proposer,
+        seeds = [GOVERNANCE_PROPOSAL_SEED, governance.proposal_count.to_le_bytes().as_ref()],
+        bump,
+        space = GovernanceProposal::size(&accounts, &data)
+    )]
+    pub proposal: Account<'info, GovernanceProposal>,
+    #[account(mut)]
+    pub proposer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ApproveProposal<'info> {
+    #[account(seeds = [GOVERNANCE_SEED], bump = governance.bump)]
+    pub governance: Account<'info, GovernanceConfig>,
+    #[account(
+        mut,
+        seeds = [GOVERNANCE_PROPOSAL_SEED, proposal.id.to_le_bytes().as_ref()],
+        bump = proposal.bump
+    )]
+    pub proposal: Account<'info, GovernanceProposal>,
+    pub signer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteProposal<'info> {
+    /// Not writable, so a proposal reconfiguring governance is not overwritten on exit
+    #[account(seeds = [GOVERNANCE_SEED], bump = governance.bump)]
+    pub governance: Account<'info, GovernanceConfig>,
+    #[account(
+        mut,
+        seeds = [GOVERNANCE_PROPOSAL_SEED, proposal.id.to_le_bytes().as_ref()],
+        bump = proposal.bump
+    )]
+    pub proposal: Account<'info, GovernanceProposal>,
+    /// CHECK: The DEX admin PDA, signs the proposed instruction and pays its rent
+    #[account(mut, seeds = [GOVERNANCE_AUTHORITY_SEED], bump = governance.authority_bump)]
+    pub governance_authority: UncheckedAccount<'info>,
+    pub rust_dex_program: Program<'info, crate::program::RustDex>,
+}
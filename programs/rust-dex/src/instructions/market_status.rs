@@ -0,0 +1,121 @@
+use anchor_lang::prelude::*;
+use std::cmp::{max, min};
+use crate::common::{MarketStatus, MAX_PRICE_BAND_BPS};
+use crate::state::{DexManager, Market};
+use crate::state::DEX_MANAGER_SEED;
+use crate::state::MARKET_SEED;
+use crate::error::ErrorCode;
+
+/// Switches the `base`/`quote` market to `status`. Orders already resting on
+/// the book stay there, only what the market accepts from now on changes.
+/// Delisting is final, see `cancel_delisted_orders_impl` and `close_market_impl`.
+pub fn set_market_status_impl(ctx: Context<SetMarketStatus>, base: Pubkey, quote: Pubkey, status: MarketStatus) -> Result<()> {
+    msg!("Setting status of {} / {} to {:?}", base, quote, status);
+    let market = &mut ctx.accounts.market;
+    if market.status() == MarketStatus::Delisted {
+        return Err(ErrorCode::MarketDelisted.into());
+    }
+    market.set_status(status);
+    Ok(())
+}
+
+/// Keeps fills of the `base`/`quote` market within `band_bps` of its reference
+/// price, 0 lifting the band. Limit orders priced outside the band are
+/// rejected, and matching into a resting order outside it keeps new orders out
+/// of the market for `halt_cooldown_secs`.
+pub fn set_price_band_impl(ctx: Context<SetPriceBand>, base: Pubkey, quote: Pubkey, band_bps: u16, halt_cooldown_secs: i64) -> Result<()> {
+    msg!("Setting price band of {} / {} to {} bps with a {}s cooldown", base, quote, band_bps, halt_cooldown_secs);
+    if band_bps > MAX_PRICE_BAND_BPS || halt_cooldown_secs < 0 {
+        return Err(ErrorCode::InvalidPriceBand.into());
+    }
+    ctx.accounts.market.set_price_band(band_bps, halt_cooldown_secs);
+    Ok(())
+}
+
+/// Moves the reference price of the `base`/`quote` market, in quote per base,
+/// as an oracle would. Trades move it along afterwards. A running circuit
+/// breaker cooldown ends, since it tripped against the old reference.
+pub fn set_reference_price_impl(ctx: Context<SetReferencePrice>, base: Pubkey, quote: Pubkey, reference_price: f64) -> Result<()> {
+    msg!("Setting reference price of {} / {} to {}", base, quote, reference_price);
+    if !reference_price.is_finite() || reference_price <= 0.0 {
+        return Err(ErrorCode::InvalidPrice.into());
+    }
+    let market = &mut ctx.accounts.market;
+    market.reference_price = reference_price;
+    market.halted_until = 0;
+    Ok(())
+}
+
+/// Stops, or resumes, new orders and swaps on every market at once. Cancels,
+/// settlement and withdrawals keep working while trading is paused.
+pub fn set_trading_paused_impl(ctx: Context<SetTradingPaused>, paused: bool) -> Result<()> {
+    msg!("Setting trading paused to {}", paused);
+    ctx.accounts.dex_manager.paused = paused;
+    Ok(())
+}
+
+#[derive(Accounts)]
+#[instruction(base: Pubkey, quote: Pubkey)]
+pub struct SetMarketStatus<'info> {
+    #[account(
+        mut,
+        seeds = [MARKET_SEED, min(&base, &quote).as_ref(), max(&base, &quote).as_ref()],
+        bump = market.bump,
+    )]
+    pub market: Account<'info, Market>,
+    #[account(
+        seeds = [DEX_MANAGER_SEED],
+        bump = dex_manager.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub dex_manager: Account<'info, DexManager>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(base: Pubkey, quote: Pubkey)]
+pub struct SetPriceBand<'info> {
+    #[account(
+        mut,
+        seeds = [MARKET_SEED, min(&base, &quote).as_ref(), max(&base, &quote).as_ref()],
+        bump = market.bump,
+    )]
+    pub market: Account<'info, Market>,
+    #[account(
+        seeds = [DEX_MANAGER_SEED],
+        bump = dex_manager.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub dex_manager: Account<'info, DexManager>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(base: Pubkey, quote: Pubkey)]
+pub struct SetReferencePrice<'info> {
+    #[account(
+        mut,
+        seeds = [MARKET_SEED, min(&base, &quote).as_ref(), max(&base, &quote).as_ref()],
+        bump = market.bump,
+    )]
+    pub market: Account<'info, Market>,
+    #[account(
+        seeds = [DEX_MANAGER_SEED],
+        bump = dex_manager.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub dex_manager: Account<'info, DexManager>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetTradingPaused<'info> {
+    #[account(
+        mut,
+        seeds = [DEX_MANAGER_SEED],
+        bump = dex_manager.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub dex_manager: Account<'info, DexManager>,
+    pub admin: Signer<'info>,
+}
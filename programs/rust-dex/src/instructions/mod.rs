@@ -9,6 +9,20 @@ pub mod place_market_order;
 pub mod common;
 pub mod consume_events;
 pub mod cancel_order;
+pub mod quote_market_order;
+pub mod settlement;
+pub mod swap_route;
+pub mod swap;
+pub mod crank_pool;
+pub mod crank_consume_events;
+pub mod consume_events_batch;
+pub mod audit_vault;
+pub mod fees;
+pub mod admin;
+pub mod governance;
+pub mod market_status;
+pub mod delisting;
+pub mod close_user;
 
 
 pub use register_vault_token_ledger::*;
@@ -20,4 +34,17 @@ pub use withdraw::*;
 pub use place_limit_order::*;
 pub use place_market_order::*;
 pub use consume_events::*;
-pub use cancel_order::*;
\ No newline at end of file
+pub use cancel_order::*;
+pub use quote_market_order::*;
+pub use swap_route::*;
+pub use swap::*;
+pub use crank_pool::*;
+pub use crank_consume_events::*;
+pub use consume_events_batch::*;
+pub use audit_vault::*;
+pub use fees::*;
+pub use admin::*;
+pub use governance::*;
+pub use market_status::*;
+pub use delisting::*;
+pub use close_user::*;
\ No newline at end of file
@@ -1,42 +1,53 @@
 use anchor_lang::prelude::*;
-use crate::{common::{OrderRequest, OrderSide, OrderType}, 
+use std::cmp::{max, min};
+use crate::{common::{amount_at_price, MarketStatus, OrderRequest, OrderSide, OrderType}, 
     matching_engine::MatchingEngine, 
-    state::{EventList, OrderHeapImpl}, DexManager, UserOrderbook};
+    state::{EventList, OrderHeapImpl}, DexManager, OrderHeap, UserOrderbook};
 use crate::state::{IndividualTokenLedgerAccount, TokenPairAccount};
-use crate::instructions::common::{token_pair_queue_logging, convert_to_event_list};
+use crate::instructions::common::{charge_fees, check_order_allowed, referral_share, tier_discount, token_pair_queue_logging, convert_to_event_list, record_market_events, OrderEventEmitter};
+use crate::instructions::settlement::{settle_order_immediately, LedgerCache};
 use crate::state::ORDER_EVENTS_SEED;
+use crate::state::{Market, MARKET_SEED};
+use crate::state::{MarketEventLog, MARKET_EVENTS_SEED};
 use crate::state::DEX_MANAGER_SEED;
+use crate::state::FEE_TIERS_SEED;
 use crate::state::TOKEN_PAIR_SEED;
+use crate::state::INDIVIDUAL_TOKEN_LEDGER_SEED;
+use crate::state::USER_ORDERBOOK_SEED;
+use crate::error::ErrorCode;
+use crate::event_sink;
 
-#[error_code]
-pub enum ErrorCode {
-    InvalidOrderSide,
-    InsufficientBalance,
-}
-
-pub fn place_limit_order_impl(ctx: Context<PlaceLimitOrder>, base: Pubkey, quote: Pubkey, side: String, price: f64, amount: u64) -> Result<()> {
-    msg!("Placing limit order: {} for amount {}", side, amount);
-    if side != "buy" && side != "sell" {
-        return Err(ErrorCode::InvalidOrderSide.into());
+/// When the makers' token ledgers are passed through `remaining_accounts`, every
+/// fill is settled within this instruction and nothing is queued for `consume_events`.
+pub fn place_limit_order_impl<'info>(ctx: Context<'_, '_, 'info, 'info, PlaceLimitOrder<'info>>, base: Pubkey, quote: Pubkey, side: OrderSide, price: f64, amount: u64) -> Result<()> {
+    msg!("Placing limit order: {:?} for amount {}", side, amount);
+    if !price.is_finite() || price <= 0.0 {
+        return Err(ErrorCode::InvalidPrice.into());
+    }
+    if amount == 0 {
+        return Err(ErrorCode::InvalidAmount.into());
     }
+    let quote_amount = amount_at_price(amount, price).ok_or(ErrorCode::InvalidAmount)?;
+    check_order_allowed(&ctx.accounts.dex_manager, &ctx.accounts.market, OrderType::Limit, Clock::get()?.unix_timestamp)?;
+    let post_only = ctx.accounts.market.status() == MarketStatus::PostOnly;
     
-    let buy_amount = if side == "buy" { 
+    let buy_amount = if side == OrderSide::Buy { 
         amount 
     } else { 
-        (amount as f64 * price) as u64
+        quote_amount
     };
     
-    let sell_amount = if side == "sell" { 
+    let sell_amount = if side == OrderSide::Sell { 
         amount 
     } else { 
-        (amount as f64 * price) as u64
+        quote_amount
     };
     
-    let token_buy = if side == "buy" { base } else { quote };
-    let token_sell = if side == "sell" { base } else { quote };
+    let token_buy = if side == OrderSide::Buy { base } else { quote };
+    let token_sell = if side == OrderSide::Sell { base } else { quote };
     
     
-    let selling_token_ledger = if side == "sell" {
+    let selling_token_ledger = if side == OrderSide::Sell {
         &mut ctx.accounts.user_base_token_ledger
     } else {
         &mut ctx.accounts.user_quote_token_ledger
@@ -44,15 +55,19 @@ pub fn place_limit_order_impl(ctx: Context<PlaceLimitOrder>, base: Pubkey, quote
     if selling_token_ledger.available_balance < sell_amount {
         return Err(ErrorCode::InsufficientBalance.into());
     }
-    selling_token_ledger.available_balance -= sell_amount;
-    selling_token_ledger.locked_balance += sell_amount;
+    selling_token_ledger.available_balance = selling_token_ledger.available_balance
+        .checked_sub(sell_amount)
+        .ok_or(ErrorCode::InsufficientBalance)?;
+    selling_token_ledger.locked_balance = selling_token_ledger.locked_balance
+        .checked_add(sell_amount)
+        .ok_or(ErrorCode::BalanceOverflow)?;
 
-    let mut buy_queue_account = if side == "buy" {
+    let mut buy_queue_account = if side == OrderSide::Buy {
         ctx.accounts.base_quote_queue.load_mut()?
     } else {
         ctx.accounts.quote_base_queue.load_mut()?
     };
-    let mut sell_queue_account = if side == "sell" {
+    let mut sell_queue_account = if side == OrderSide::Sell {
         ctx.accounts.base_quote_queue.load_mut()?
     } else {
         ctx.accounts.quote_base_queue.load_mut()?
@@ -66,11 +81,13 @@ pub fn place_limit_order_impl(ctx: Context<PlaceLimitOrder>, base: Pubkey, quote
     let sell_queue: &mut OrderHeapImpl = &mut sell_queue_account.order_heap;
     let event_list: &mut EventList = &mut ctx.accounts.order_events;
 
-    let next_order_id = ctx.accounts.dex_manager.next_sequence_number();
-    if let Err(e) = event_list.open(ctx.accounts.user.key(), token_buy, token_sell, next_order_id) {
-        return Err(e);
+    if ctx.remaining_accounts.is_empty() {
+        event_list.reserve_order()?;
     }
+    let next_order_id = ctx.accounts.dex_manager.next_sequence_number();
 
+    let fee_discount = tier_discount(&ctx.accounts.fee_tiers, &ctx.accounts.user_quote_token_ledger, Clock::get()?.unix_timestamp)?;
+    let referral_share_bps = referral_share(&ctx.accounts.dex_manager, &ctx.accounts.user_quote_token_ledger);
     let order_request = OrderRequest::new(
         next_order_id,
         buy_amount,
@@ -80,8 +97,12 @@ pub fn place_limit_order_impl(ctx: Context<PlaceLimitOrder>, base: Pubkey, quote
         ctx.accounts.user.key(),
         Clock::get()?.unix_timestamp,
         OrderType::Limit,
-        if side == "buy" { OrderSide::Buy } else { OrderSide::Sell }
+        fee_discount,
     );
+    let mut lifecycle = OrderEventEmitter::new(
+        &mut ctx.accounts.dex_manager, event_sink!(ctx), ctx.accounts.market.key(), ctx.accounts.market.base_mint, Clock::get()?.unix_timestamp
+    );
+    lifecycle.placed(&order_request)?;
     let user_orderbook: &mut UserOrderbook = &mut ctx.accounts.user_orderbook;    
     let mut order_book = MatchingEngine::new(
         token_buy,
@@ -89,21 +110,40 @@ pub fn place_limit_order_impl(ctx: Context<PlaceLimitOrder>, base: Pubkey, quote
         buy_queue,
         sell_queue,
         user_orderbook,
-    );
-    
-    let result = order_book.process_order(
-        order_request, side == "sell"
-    );
+    ).with_price_band(ctx.accounts.market.price_band());
+    if post_only && order_book.would_match(&order_request) {
+        return Err(ErrorCode::PostOnlyWouldMatch.into());
+    }
 
-    convert_to_event_list(event_list, result);
-    // token_pair_queue_logging(buy_queue, sell_queue);
-    if event_list.length() == 0 {
-        event_list.close();
+    let mut result = order_book.process_order(
+        order_request, side == OrderSide::Sell
+    )?;
+    charge_fees(&mut result, ctx.accounts.market.fee_rates(), fee_discount.taker_bps, referral_share_bps);
+    record_market_events(&mut ctx.accounts.market, &mut *ctx.accounts.market_events.load_mut()?, &token_buy, &result, Clock::get()?.unix_timestamp);
+    lifecycle.matched(&token_buy, &token_sell, &result)?;
+    if let Some(rested) = buy_queue_account.order_heap.get_order_by_id(next_order_id) {
+        lifecycle.rested(rested)?;
     }
 
+    if ctx.remaining_accounts.is_empty() {
+        let fills = convert_to_event_list(event_list, next_order_id, token_buy, token_sell, result)?;
+        ctx.accounts.market.queue_fills(fills);
+    } else {
+        let (income_ledger, outcome_ledger) = if side == OrderSide::Buy {
+            (&mut ctx.accounts.user_base_token_ledger, &mut ctx.accounts.user_quote_token_ledger)
+        } else {
+            (&mut ctx.accounts.user_quote_token_ledger, &mut ctx.accounts.user_base_token_ledger)
+        };
+        let mut makers = LedgerCache::new(ctx.remaining_accounts);
+        settle_order_immediately(&mut makers, result, &ctx.accounts.user.key(), &token_buy, &token_sell, income_ledger, outcome_ledger)?;
+        makers.exit()?;
+    }
+    // token_pair_queue_logging(buy_queue, sell_queue);
+
     Ok(())
 }
 
+#[cfg_attr(feature = "event-cpi", event_cpi)]
 #[derive(Accounts)]
 #[instruction(base: Pubkey, quote: Pubkey)]
 pub struct PlaceLimitOrder<'info> {
@@ -119,6 +159,19 @@ pub struct PlaceLimitOrder<'info> {
         bump,
     )]
     pub quote_base_queue: AccountLoader<'info, TokenPairAccount>,
+    #[account(
+        mut,
+        seeds = [MARKET_SEED, min(&base, &quote).as_ref(), max(&base, &quote).as_ref()],
+        bump = market.bump,
+        constraint = market.base_mint == base && market.quote_mint == quote @ ErrorCode::MarketMintsMismatch,
+    )]
+    pub market: Box<Account<'info, Market>>,
+    #[account(
+        mut,
+        seeds = [MARKET_EVENTS_SEED, min(&base, &quote).as_ref(), max(&base, &quote).as_ref()],
+        bump,
+    )]
+    pub market_events: AccountLoader<'info, MarketEventLog>,
     #[account(
         mut,
         seeds = [DEX_MANAGER_SEED],
@@ -133,12 +186,30 @@ pub struct PlaceLimitOrder<'info> {
         // space = 8 + (MAX_EVENTS * (32 + 8 + 8) + 32 + 32 + 8 + 8) // Adjust size based on EventList struct size
     )]
     pub order_events: Box<Account<'info, EventList>>,
-    #[account(mut)]
+    #[account(
+        mut,
+        seeds = [INDIVIDUAL_TOKEN_LEDGER_SEED, base.as_ref(), user.key().as_ref()],
+        bump = user_base_token_ledger.bump,
+    )]
     pub user_base_token_ledger: Box<Account<'info, IndividualTokenLedgerAccount>>,
-    #[account(mut)]
+    #[account(
+        mut,
+        seeds = [INDIVIDUAL_TOKEN_LEDGER_SEED, quote.as_ref(), user.key().as_ref()],
+        bump = user_quote_token_ledger.bump,
+    )]
     pub user_quote_token_ledger: Box<Account<'info, IndividualTokenLedgerAccount>>,
-    #[account(mut)]
+    #[account(
+        mut,
+        seeds = [USER_ORDERBOOK_SEED, user.key().as_ref()],
+        bump = user_orderbook.bump,
+    )]
     pub user_orderbook: Box<Account<'info, UserOrderbook>>,
+    /// CHECK: fee tier table of the market's quote mint, read by `tier_discount` only once it is initialized
+    #[account(
+        seeds = [FEE_TIERS_SEED, market.quote_mint.as_ref()],
+        bump,
+    )]
+    pub fee_tiers: UncheckedAccount<'info>,
 
     #[account(mut)]
     pub user: Signer<'info>,
@@ -1,53 +1,74 @@
 use anchor_lang::prelude::*;
+use std::cmp::{max, min};
 use crate::{
     common::{OrderRequest, OrderSide, OrderType}, 
     instructions::common::token_pair_queue_logging, 
-    matching_engine::MatchingEngine, 
+    matching_engine::{MatchingEngine, OrderFailure, OrderProcessResult, OrderSuccess}, 
     state::{EventList, IndividualTokenLedgerAccount, OrderHeapImpl, TokenPairAccount}, 
     DexManager, UserOrderbook
 };
-use crate::instructions::common::convert_to_event_list;
+use crate::instructions::common::{charge_fees, check_order_allowed, referral_share, tier_discount, convert_to_event_list, record_market_events, OrderEventEmitter};
+use crate::instructions::settlement::{settle_order_immediately, LedgerCache};
 use crate::state::ORDER_EVENTS_SEED;
+use crate::state::{Market, MARKET_SEED};
+use crate::state::{MarketEventLog, MARKET_EVENTS_SEED};
 use crate::state::DEX_MANAGER_SEED;
+use crate::state::FEE_TIERS_SEED;
 use crate::state::INDIVIDUAL_TOKEN_LEDGER_SEED;
 use crate::state::TOKEN_PAIR_SEED;
+use crate::state::USER_ORDERBOOK_SEED;
+use crate::error::ErrorCode;
+use crate::event_sink;
 
 
-#[error_code]
-pub enum ErrorCode {
-    InvalidOrderSide,
-    InsufficientBalance,
+/// How much of the taker's locked balance `result` hands to fills or rollbacks.
+fn committed_sell_quantity(result: &OrderProcessResult) -> Result<u64> {
+    let mut committed: u64 = 0;
+    for res in result {
+        let sell_quantity = match res {
+            Ok(OrderSuccess::Filled { sell_quantity, .. })
+            | Err(OrderFailure::NoMatch { sell_quantity, .. })
+            | Err(OrderFailure::TooManyEvents { sell_quantity, .. })
+            | Err(OrderFailure::OrderHeapFull { sell_quantity, .. })
+            | Err(OrderFailure::PriceBandBreached { sell_quantity, .. }) => *sell_quantity,
+            Err(OrderFailure::OrderNotFound { .. }) => 0,
+        };
+        committed = committed.checked_add(sell_quantity).ok_or(ErrorCode::BalanceOverflow)?;
+    }
+    Ok(committed)
 }
 
-pub fn place_market_order_impl(ctx: Context<PlaceMarketOrder>, base: Pubkey, quote: Pubkey, side: String, amount: u64) -> Result<()> {
-    msg!("Placing market order: {} for amount {}", side, amount);
-    if side != "buy" && side != "sell" {
-        return Err(ErrorCode::InvalidOrderSide.into());
+/// When the makers' token ledgers are passed through `remaining_accounts`, every
+/// fill is settled within this instruction and nothing is queued for `consume_events`.
+pub fn place_market_order_impl<'info>(ctx: Context<'_, '_, 'info, 'info, PlaceMarketOrder<'info>>, base: Pubkey, quote: Pubkey, side: OrderSide, amount: u64) -> Result<()> {
+    msg!("Placing market order: {:?} for amount {}", side, amount);
+    if amount == 0 {
+        return Err(ErrorCode::InvalidAmount.into());
     }
+    check_order_allowed(&ctx.accounts.dex_manager, &ctx.accounts.market, OrderType::Market, Clock::get()?.unix_timestamp)?;
     
-    let token_buy: Pubkey = if side == "buy" { base } else { quote };
-    let token_sell: Pubkey = if side == "sell" { base } else { quote };
+    let token_buy: Pubkey = if side == OrderSide::Buy { base } else { quote };
+    let token_sell: Pubkey = if side == OrderSide::Sell { base } else { quote };
     
-    let selling_token_ledger = if side == "sell" {
+    let selling_token_ledger = if side == OrderSide::Sell {
         &mut ctx.accounts.user_base_token_ledger
     } else {
         &mut ctx.accounts.user_quote_token_ledger
     };
     let available_balance = selling_token_ledger.available_balance;
     
-    let (buy_amount, sell_amount) = match side.as_str() {
-        "buy" => (amount, available_balance),
-        "sell" => (0, amount),
-        _ => unreachable!(),
+    let (buy_amount, sell_amount) = match side {
+        OrderSide::Buy => (amount, available_balance),
+        OrderSide::Sell => (0, amount),
     };
         
-    let mut buy_queue_account = if side == "buy" {
+    let mut buy_queue_account = if side == OrderSide::Buy {
         ctx.accounts.base_quote_queue.load_mut()?
     } else {
         ctx.accounts.quote_base_queue.load_mut()?
     };
     
-    let mut sell_queue_account = if side == "sell" {
+    let mut sell_queue_account = if side == OrderSide::Sell {
         ctx.accounts.base_quote_queue.load_mut()?
     } else {
         ctx.accounts.quote_base_queue.load_mut()?
@@ -65,20 +86,25 @@ pub fn place_market_order_impl(ctx: Context<PlaceMarketOrder>, base: Pubkey, quo
     if selling_token_ledger.available_balance < sell_amount {
         return Err(ErrorCode::InsufficientBalance.into());
     }
-    if side == "buy" && available_balance == 0 {
+    if side == OrderSide::Buy && available_balance == 0 {
         return Err(ErrorCode::InsufficientBalance.into());
     }
     // event_list
     let event_list: &mut EventList = &mut ctx.accounts.order_events;
-    let next_order_id = ctx.accounts.dex_manager.next_sequence_number();
-    event_list.order_id = next_order_id;
-    if let Err(error) = event_list.open(ctx.accounts.user.key(), base, quote, next_order_id) {
-        return Err(error);
+    if ctx.remaining_accounts.is_empty() {
+        event_list.reserve_order()?;
     }
+    let next_order_id = ctx.accounts.dex_manager.next_sequence_number();
     
-    selling_token_ledger.available_balance -= sell_amount;
-    selling_token_ledger.locked_balance += sell_amount;
+    selling_token_ledger.available_balance = selling_token_ledger.available_balance
+        .checked_sub(sell_amount)
+        .ok_or(ErrorCode::InsufficientBalance)?;
+    selling_token_ledger.locked_balance = selling_token_ledger.locked_balance
+        .checked_add(sell_amount)
+        .ok_or(ErrorCode::BalanceOverflow)?;
     
+    let fee_discount = tier_discount(&ctx.accounts.fee_tiers, &ctx.accounts.user_quote_token_ledger, Clock::get()?.unix_timestamp)?;
+    let referral_share_bps = referral_share(&ctx.accounts.dex_manager, &ctx.accounts.user_quote_token_ledger);
     let order_request = OrderRequest::new(
         next_order_id,
         buy_amount,
@@ -88,8 +114,12 @@ pub fn place_market_order_impl(ctx: Context<PlaceMarketOrder>, base: Pubkey, quo
         ctx.accounts.user.key(),
         Clock::get()?.unix_timestamp,
         OrderType::Market,  // 使用市价单类型
-        if side == "buy" { OrderSide::Buy } else { OrderSide::Sell }
+        fee_discount,
     );
+    let mut lifecycle = OrderEventEmitter::new(
+        &mut ctx.accounts.dex_manager, event_sink!(ctx), ctx.accounts.market.key(), ctx.accounts.market.base_mint, Clock::get()?.unix_timestamp
+    );
+    lifecycle.placed(&order_request)?;
     
     
     // 处理订单
@@ -100,23 +130,53 @@ pub fn place_market_order_impl(ctx: Context<PlaceMarketOrder>, base: Pubkey, quo
         buy_queue,
         sell_queue,
         user_orderbook,
-    );
-    
-    let result = order_book.process_order(
-        order_request, side == "sell"
-    );
-    
-    // 转换结果到事件列表
-    convert_to_event_list(event_list, result);
+    ).with_price_band(ctx.accounts.market.price_band());
+    
+    let mut result = order_book.process_order(
+        order_request, side == OrderSide::Sell
+    )?;
+    charge_fees(&mut result, ctx.accounts.market.fee_rates(), fee_discount.taker_bps, referral_share_bps);
+    record_market_events(&mut ctx.accounts.market, &mut *ctx.accounts.market_events.load_mut()?, &token_buy, &result, Clock::get()?.unix_timestamp);
+    lifecycle.matched(&token_buy, &token_sell, &result)?;
+    
+    // Nothing of a market order rests on the book, so whatever the fills and
+    // rollbacks do not use is unlocked right away
+    let released = if ctx.remaining_accounts.is_empty() {
+        let committed = committed_sell_quantity(&result)?;
+        // 转换结果到事件列表
+        let fills = convert_to_event_list(event_list, next_order_id, token_buy, token_sell, result)?;
+        ctx.accounts.market.queue_fills(fills);
+        committed
+    } else {
+        let (income_ledger, outcome_ledger) = if side == OrderSide::Buy {
+            (&mut ctx.accounts.user_base_token_ledger, &mut ctx.accounts.user_quote_token_ledger)
+        } else {
+            (&mut ctx.accounts.user_quote_token_ledger, &mut ctx.accounts.user_base_token_ledger)
+        };
+        let mut makers = LedgerCache::new(ctx.remaining_accounts);
+        let released = settle_order_immediately(&mut makers, result, &ctx.accounts.user.key(), &token_buy, &token_sell, income_ledger, outcome_ledger)?;
+        makers.exit()?;
+        released
+    };
+    let unused = sell_amount.checked_sub(released).ok_or(ErrorCode::BalanceOverflow)?;
+    let outcome_ledger = if side == OrderSide::Sell {
+        &mut ctx.accounts.user_base_token_ledger
+    } else {
+        &mut ctx.accounts.user_quote_token_ledger
+    };
+    outcome_ledger.locked_balance = outcome_ledger.locked_balance
+        .checked_sub(unused)
+        .ok_or(ErrorCode::BalanceOverflow)?;
+    outcome_ledger.available_balance = outcome_ledger.available_balance
+        .checked_add(unused)
+        .ok_or(ErrorCode::BalanceOverflow)?;
     // token_pair_queue_logging(buy_queue, sell_queue);
-    if event_list.length() == 0 {
-        event_list.close();
-    } 
 
     Ok(())
 }
 
 
+#[cfg_attr(feature = "event-cpi", event_cpi)]
 #[derive(Accounts)]
 #[instruction(base: Pubkey, quote: Pubkey)]
 pub struct PlaceMarketOrder<'info> {
@@ -126,10 +186,21 @@ pub struct PlaceMarketOrder<'info> {
     #[account(mut, seeds = [TOKEN_PAIR_SEED, quote.as_ref(), base.as_ref()], bump)]
     pub quote_base_queue: AccountLoader<'info, TokenPairAccount>,
     
+    #[account(
+        mut,
+        seeds = [MARKET_SEED, min(&base, &quote).as_ref(), max(&base, &quote).as_ref()],
+        bump = market.bump,
+        constraint = market.base_mint == base && market.quote_mint == quote @ ErrorCode::MarketMintsMismatch,
+    )]
+    pub market: Box<Account<'info, Market>>,
+    
+    #[account(mut, seeds = [MARKET_EVENTS_SEED, min(&base, &quote).as_ref(), max(&base, &quote).as_ref()], bump)]
+    pub market_events: AccountLoader<'info, MarketEventLog>,
+    
     #[account(mut, seeds = [DEX_MANAGER_SEED], bump)]
     pub dex_manager: Account<'info, DexManager>,
     
-    #[account(mut, seeds = [ORDER_EVENTS_SEED, user.key().as_ref()], bump = order_events.bump)]
+    #[account(mut, seeds = [ORDER_EVENTS_SEED, user.key().as_ref()], bump = order_events.bump, has_one = user)]
     pub order_events: Box<Account<'info, EventList>>,
     
     #[account(mut, seeds = [INDIVIDUAL_TOKEN_LEDGER_SEED, base.as_ref(), user.key().as_ref()], bump)]
@@ -138,9 +209,13 @@ pub struct PlaceMarketOrder<'info> {
     #[account(mut, seeds = [INDIVIDUAL_TOKEN_LEDGER_SEED, quote.as_ref(), user.key().as_ref()], bump)]
     pub user_quote_token_ledger: Box<Account<'info, IndividualTokenLedgerAccount>>,
     
-    #[account(mut)]
+    #[account(mut, seeds = [USER_ORDERBOOK_SEED, user.key().as_ref()], bump = user_orderbook.bump)]
     pub user_orderbook: Box<Account<'info, UserOrderbook>>,
     
+    /// CHECK: fee tier table of the market's quote mint, read by `tier_discount` only once it is initialized
+    #[account(seeds = [FEE_TIERS_SEED, market.quote_mint.as_ref()], bump)]
+    pub fee_tiers: UncheckedAccount<'info>,
+    
     #[account(mut)]
     pub user: Signer<'info>,
     pub system_program: Program<'info, System>,
@@ -0,0 +1,298 @@
+use anchor_lang::prelude::*;
+use std::cmp::{max, min};
+use crate::{
+    common::{FeeDiscount, FeeRates, OrderRequest, OrderSide, OrderType, PriceBand, MAX_EVENTS},
+    matching_engine::{MatchingEngine, OrderFailure, OrderSuccess},
+    instructions::common::{charge_fees, check_order_allowed},
+    state::{OrderHeap, OrderHeapImpl, TokenPairAccount},
+    DexManager, UserOrderbook
+};
+use crate::state::{Market, MARKET_SEED};
+use crate::state::DEX_MANAGER_SEED;
+use crate::state::TOKEN_PAIR_SEED;
+use crate::error::ErrorCode;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct QuoteFill {
+    pub maker: Pubkey,
+    pub maker_order_id: u64,
+    pub base_quantity: u64,
+    pub quote_quantity: u64,
+    pub price: f64,
+}
+
+/// `fills` are what the makers trade. `filled_base` and `filled_quote` are what
+/// the taker gets and gives once `taker_fee` is taken, at the market's base
+/// rate, out of the token it buys, so `average_price` is the all-in price.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct MarketOrderQuote {
+    pub fills: Vec<QuoteFill>,
+    pub filled_base: u64,
+    pub filled_quote: u64,
+    pub average_price: f64,
+    pub worst_price: f64,
+    pub unfilled_base: u64,
+    pub taker_fee: u64,
+}
+
+/// Fails as `place_market_order` would while trading is paused or the market
+/// does not take market orders, instead of quoting fills it would not make.
+pub fn quote_market_order_impl(ctx: Context<QuoteMarketOrder>, base: Pubkey, quote: Pubkey, side: OrderSide, amount: u64) -> Result<MarketOrderQuote> {
+    if amount == 0 {
+        return Err(ErrorCode::InvalidAmount.into());
+    }
+    check_order_allowed(&ctx.accounts.dex_manager, &ctx.accounts.market, OrderType::Market, Clock::get()?.unix_timestamp)?;
+    let base_quote_queue = ctx.accounts.base_quote_queue.load()?;
+    let quote_base_queue = ctx.accounts.quote_base_queue.load()?;
+
+    // Match against heap copies so the book accounts are never written to
+    let (bids, asks) = if side == OrderSide::Buy {
+        (&base_quote_queue.order_heap, &quote_base_queue.order_heap)
+    } else {
+        (&quote_base_queue.order_heap, &base_quote_queue.order_heap)
+    };
+    let mut buy_queue = boxed_copy(bids);
+    let mut sell_queue = boxed_copy(asks);
+    let price_band = ctx.accounts.market.price_band();
+    let fee_rates = ctx.accounts.market.fee_rates();
+    let quote_result = simulate_market_order(buy_queue.as_mut(), sell_queue.as_mut(), base, quote, side, amount, price_band, fee_rates)?;
+
+    msg!("Quoted market {:?} of {}: filled_base={}, filled_quote={}, unfilled_base={}",
+        side, amount, quote_result.filled_base, quote_result.filled_quote, quote_result.unfilled_base);
+    Ok(quote_result)
+}
+
+/// Copies `heap` into a heap allocation without building it on the stack
+/// first, the order heap is larger than an SBF stack frame.
+fn boxed_copy(heap: &OrderHeapImpl) -> Box<OrderHeapImpl> {
+    let mut copy: Box<OrderHeapImpl> = bytemuck::zeroed_box();
+    bytemuck::bytes_of_mut(copy.as_mut()).copy_from_slice(bytemuck::bytes_of(heap));
+    copy
+}
+
+/// Runs a market order of `amount` base tokens through the matching engine,
+/// within the market's `price_band`, and summarizes the fills it would produce
+/// and the taker fee `fee_rates` charges on them.
+/// The heaps passed in are consumed by the simulation, so callers must hand in
+/// copies of the on-chain book.
+#[allow(clippy::too_many_arguments)]
+pub fn simulate_market_order(
+    buy_queue: &mut dyn OrderHeap,
+    sell_queue: &mut dyn OrderHeap,
+    base: Pubkey,
+    quote: Pubkey,
+    side: OrderSide,
+    amount: u64,
+    price_band: PriceBand,
+    fee_rates: FeeRates,
+) -> Result<MarketOrderQuote> {
+    let (token_buy, token_sell) = if side == OrderSide::Buy { (base, quote) } else { (quote, base) };
+    // A market buy spends whatever it takes, so the quote is not capped by a balance
+    let (buy_amount, sell_amount) = if side == OrderSide::Buy { (amount, u64::MAX) } else { (0, amount) };
+    let order_request = OrderRequest::new(
+        0,
+        buy_amount,
+        sell_amount,
+        token_buy,
+        token_sell,
+        Pubkey::default(),
+        0,
+        OrderType::Market,
+        FeeDiscount::default(),
+    );
+    let mut scratch_orderbook = Box::new(UserOrderbook::default());
+    let mut engine = MatchingEngine::new(
+        token_buy,
+        token_sell,
+        buy_queue,
+        sell_queue,
+        scratch_orderbook.as_mut(),
+    ).with_price_band(price_band);
+    let mut result = engine.simulate_market_order(order_request, side == OrderSide::Sell)?;
+    charge_fees(&mut result, fee_rates, 0, 0);
+
+    let mut fills: Vec<QuoteFill> = Vec::with_capacity(MAX_EVENTS);
+    let mut filled_base: u64 = 0;
+    let mut filled_quote: u64 = 0;
+    let mut worst_price: f64 = 0.0;
+    let mut unfilled_base: u64 = 0;
+    let mut total_taker_fee: u64 = 0;
+    for res in result {
+        match res {
+            Ok(OrderSuccess::Filled { oppo_user, oppo_order_id, sell_quantity, buy_quantity, taker_fee, .. }) => {
+                total_taker_fee = total_taker_fee.saturating_add(taker_fee);
+                let (base_quantity, quote_quantity) = if side == OrderSide::Buy {
+                    (buy_quantity, sell_quantity)
+                } else {
+                    (sell_quantity, buy_quantity)
+                };
+                let price = if base_quantity == 0 { 0.0 } else { quote_quantity as f64 / base_quantity as f64 };
+                let is_worse = if side == OrderSide::Buy { price > worst_price } else { worst_price == 0.0 || price < worst_price };
+                if is_worse {
+                    worst_price = price;
+                }
+                filled_base = filled_base.saturating_add(base_quantity);
+                filled_quote = filled_quote.saturating_add(quote_quantity);
+                fills.push(QuoteFill {
+                    maker: oppo_user,
+                    maker_order_id: oppo_order_id,
+                    base_quantity,
+                    quote_quantity,
+                    price,
+                });
+            },
+            Err(OrderFailure::NoMatch { sell_quantity, buy_quantity, .. })
+            | Err(OrderFailure::TooManyEvents { sell_quantity, buy_quantity, .. })
+            | Err(OrderFailure::OrderHeapFull { sell_quantity, buy_quantity, .. })
+            | Err(OrderFailure::PriceBandBreached { sell_quantity, buy_quantity, .. }) => {
+                let remaining = if side == OrderSide::Buy { buy_quantity } else { sell_quantity };
+                unfilled_base = unfilled_base.saturating_add(remaining);
+            },
+            Err(OrderFailure::OrderNotFound { .. }) => {},
+        }
+    }
+    // The taker fee comes out of the token the taker buys
+    if side == OrderSide::Buy {
+        filled_base = filled_base.saturating_sub(total_taker_fee);
+    } else {
+        filled_quote = filled_quote.saturating_sub(total_taker_fee);
+    }
+    let average_price = if filled_base == 0 { 0.0 } else { filled_quote as f64 / filled_base as f64 };
+
+    Ok(MarketOrderQuote {
+        fills,
+        filled_base,
+        filled_quote,
+        average_price,
+        worst_price,
+        unfilled_base,
+        taker_fee: total_taker_fee,
+    })
+}
+
+#[derive(Accounts)]
+#[instruction(base: Pubkey, quote: Pubkey)]
+pub struct QuoteMarketOrder<'info> {
+    #[account(seeds = [TOKEN_PAIR_SEED, base.as_ref(), quote.as_ref()], bump)]
+    pub base_quote_queue: AccountLoader<'info, TokenPairAccount>,
+
+    #[account(seeds = [TOKEN_PAIR_SEED, quote.as_ref(), base.as_ref()], bump)]
+    pub quote_base_queue: AccountLoader<'info, TokenPairAccount>,
+
+    #[account(
+        seeds = [MARKET_SEED, min(&base, &quote).as_ref(), max(&base, &quote).as_ref()],
+        bump = market.bump,
+        constraint = market.base_mint == base && market.quote_mint == quote @ ErrorCode::MarketMintsMismatch,
+    )]
+    pub market: Box<Account<'info, Market>>,
+
+    #[account(seeds = [DEX_MANAGER_SEED], bump = dex_manager.bump)]
+    pub dex_manager: Account<'info, DexManager>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::OrderNode;
+
+    fn ask(id: u64, base_quantity: u64, quote_quantity: u64) -> OrderNode {
+        // An ask sells base for quote
+        OrderNode {
+            id,
+            sell_quantity: base_quantity,
+            buy_quantity: quote_quantity,
+            ..OrderNode::default()
+        }
+    }
+
+    #[test]
+    fn test_quote_buy_sweeps_asks() {
+        let mut bids = OrderHeapImpl::new();
+        let mut asks = OrderHeapImpl::new();
+        asks.add_order(ask(1, 10, 1000)).unwrap();
+        asks.add_order(ask(2, 10, 2000)).unwrap();
+
+        let quote = simulate_market_order(&mut bids, &mut asks, Pubkey::new_unique(), Pubkey::new_unique(), OrderSide::Buy, 15, PriceBand::default(), FeeRates::default()).unwrap();
+        assert_eq!(quote.fills.len(), 2);
+        assert_eq!(quote.fills[0].maker_order_id, 1);
+        assert_eq!(quote.filled_base, 15);
+        assert_eq!(quote.filled_quote, 2000);
+        assert_eq!(quote.worst_price, 200.0);
+        assert_eq!(quote.unfilled_base, 0);
+        // The simulation consumes the heaps it is handed
+        assert_eq!(asks.len(), 1);
+    }
+
+    #[test]
+    fn test_quote_reports_unfilled_remainder() {
+        let mut bids = OrderHeapImpl::new();
+        let mut asks = OrderHeapImpl::new();
+        asks.add_order(ask(1, 10, 1000)).unwrap();
+
+        let quote = simulate_market_order(&mut bids, &mut asks, Pubkey::new_unique(), Pubkey::new_unique(), OrderSide::Buy, 25, PriceBand::default(), FeeRates::default()).unwrap();
+        assert_eq!(quote.filled_base, 10);
+        assert_eq!(quote.average_price, 100.0);
+        assert_eq!(quote.unfilled_base, 15);
+    }
+
+    #[test]
+    fn test_quote_empty_book() {
+        let mut bids = OrderHeapImpl::new();
+        let mut asks = OrderHeapImpl::new();
+
+        let quote = simulate_market_order(&mut asks, &mut bids, Pubkey::new_unique(), Pubkey::new_unique(), OrderSide::Sell, 5, PriceBand::default(), FeeRates::default()).unwrap();
+        assert!(quote.fills.is_empty());
+        assert_eq!(quote.average_price, 0.0);
+        assert_eq!(quote.unfilled_base, 5);
+    }
+
+    #[test]
+    fn test_quote_sell_pays_fractional_price() {
+        let mut bids = OrderHeapImpl::new();
+        let mut asks = OrderHeapImpl::new();
+        // A bid buys base for quote, 4 base for 10 quote is a price of 2.5
+        bids.add_order(OrderNode { id: 1, buy_quantity: 4, sell_quantity: 10, ..OrderNode::default() }).unwrap();
+
+        let quote = simulate_market_order(&mut asks, &mut bids, Pubkey::new_unique(), Pubkey::new_unique(), OrderSide::Sell, 2, PriceBand::default(), FeeRates::default()).unwrap();
+        assert_eq!(quote.filled_base, 2);
+        assert_eq!(quote.filled_quote, 5);
+        assert_eq!(bids.get_best_order().unwrap().sell_quantity, 5);
+    }
+
+    #[test]
+    fn test_quote_stops_at_the_price_band() {
+        let (base, quote) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut bids = OrderHeapImpl::new();
+        let mut asks = OrderHeapImpl::new();
+        asks.add_order(ask(1, 10, 1000)).unwrap();
+        // Priced at 200, outside a 10% band around 100
+        asks.add_order(ask(2, 10, 2000)).unwrap();
+        let price_band = PriceBand { base_mint: base, reference_price: 100.0, band_bps: 1000 };
+
+        let quote = simulate_market_order(&mut bids, &mut asks, base, quote, OrderSide::Buy, 15, price_band, FeeRates::default()).unwrap();
+        assert_eq!(quote.fills.len(), 1);
+        assert_eq!(quote.filled_base, 10);
+        assert_eq!(quote.unfilled_base, 5);
+    }
+
+    #[test]
+    fn test_quote_takes_the_taker_fee_out_of_what_the_taker_buys() {
+        let rates = FeeRates { maker_fee_bps: 0, taker_fee_bps: 100 };
+        let mut bids = OrderHeapImpl::new();
+        let mut asks = OrderHeapImpl::new();
+        asks.add_order(ask(1, 10, 1000)).unwrap();
+
+        let quote = simulate_market_order(&mut bids, &mut asks, Pubkey::new_unique(), Pubkey::new_unique(), OrderSide::Buy, 10, PriceBand::default(), rates).unwrap();
+        assert_eq!(quote.taker_fee, 1);
+        assert_eq!(quote.filled_base, 9);
+        assert_eq!(quote.filled_quote, 1000);
+        assert_eq!(quote.fills[0].base_quantity, 10);
+
+        let mut bids = OrderHeapImpl::new();
+        bids.add_order(OrderNode { id: 2, buy_quantity: 10, sell_quantity: 1000, ..OrderNode::default() }).unwrap();
+        let quote = simulate_market_order(&mut asks, &mut bids, Pubkey::new_unique(), Pubkey::new_unique(), OrderSide::Sell, 10, PriceBand::default(), rates).unwrap();
+        assert_eq!(quote.taker_fee, 10);
+        assert_eq!(quote.filled_quote, 990);
+        assert_eq!(quote.average_price, 99.0);
+    }
+}
@@ -1,13 +1,13 @@
 use anchor_lang::prelude::*;
+use std::cmp::{max, min};
 use crate::state::{OrderHeapImpl};
 use crate::TokenPairAccount;
+use crate::state::{Market, MARKET_SEED};
+use crate::state::{MarketEventLog, MARKET_EVENTS_SEED};
+use crate::state::{DexManager, DEX_MANAGER_SEED};
 // pub const ORDER_HEAP_CAPACITY: usize = 1024; // Capacity of the order heap
 use crate::state::TOKEN_PAIR_SEED;
-
-#[error_code]
-pub enum ErrorCode {
-    InvalidTokenPair,
-}
+use crate::error::ErrorCode;
 
 pub fn register_token_pair_impl(ctx: Context<RegisterTokenPair>, token1: Pubkey, token2: Pubkey) -> Result<()> {
     msg!("Registering token pair with base: {:?} and quote: {:?}", token1, token2);
@@ -26,18 +26,29 @@ pub fn register_token_pair_impl(ctx: Context<RegisterTokenPair>, token1: Pubkey,
     opposite_pair.sell_token = token1;
     opposite_pair.order_heap = OrderHeapImpl::new();
 
+    ctx.accounts.market.init(token1, token2, ctx.bumps.market);
+
+    let market_events = &mut ctx.accounts.market_events.load_init()?;
+    market_events.init(token1, token2, ctx.bumps.market_events);
+
     Ok(())
 }
 
 #[derive(Accounts)]
 #[instruction(token1: Pubkey, token2: Pubkey)]
 pub struct RegisterTokenPair<'info> {
+    #[account(
+        seeds = [DEX_MANAGER_SEED],
+        bump = dex_manager.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub dex_manager: Account<'info, DexManager>,
     #[account(mut)]
-    pub user: Signer<'info>,
+    pub admin: Signer<'info>,
     pub system_program: Program<'info, System>,
     #[account(
         init,
-        payer = user,
+        payer = admin,
         seeds = [TOKEN_PAIR_SEED, token1.as_ref(), token2.as_ref()],
         bump,
         space = 10 * (1024 as usize) // Adjust size based on TokenPairAccount struct size
@@ -46,11 +57,27 @@ pub struct RegisterTokenPair<'info> {
     pub token_pair: AccountLoader<'info, TokenPairAccount>,
     #[account(
         init,
-        payer = user,
+        payer = admin,
         seeds = [TOKEN_PAIR_SEED, token2.as_ref(), token1.as_ref()],
         bump,
         space = 10 * (1024 as usize) // Adjust size based on TokenPairAccount struct size
         // space = 8 + 32 + 32 + 8 + ((104 + 1) * ORDER_HEAP_CAPACITY + 8) // Adjust size based on TokenPairAccount struct size
     )]
     pub opposite_pair: AccountLoader<'info, TokenPairAccount>,
+    #[account(
+        init,
+        payer = admin,
+        seeds = [MARKET_SEED, min(&token1, &token2).as_ref(), max(&token1, &token2).as_ref()],
+        bump,
+        space = Market::SIZE
+    )]
+    pub market: Box<Account<'info, Market>>,
+    #[account(
+        init,
+        payer = admin,
+        seeds = [MARKET_EVENTS_SEED, min(&token1, &token2).as_ref(), max(&token1, &token2).as_ref()],
+        bump,
+        space = 8 + std::mem::size_of::<MarketEventLog>()
+    )]
+    pub market_events: AccountLoader<'info, MarketEventLog>,
 }
\ No newline at end of file
@@ -6,9 +6,15 @@ use crate::state::UserOrderbook;
 use crate::state::ORDER_EVENTS_SEED;
 use crate::state::INDIVIDUAL_LEDGER_SEED;
 use crate::state::USER_ORDERBOOK_SEED;
+use crate::error::ErrorCode;
 
-pub fn register_user_impl(ctx: Context<RegisterUser>) -> Result<()> {
-    msg!("Registering user with key: {:?}", ctx.accounts.user.key());
+/// A `referrer` gets a share of the taker fees of the user from then on, see
+/// `set_referral_share`. It cannot be changed later.
+pub fn register_user_impl(ctx: Context<RegisterUser>, referrer: Option<Pubkey>) -> Result<()> {
+    msg!("Registering user with key: {:?}, referrer: {:?}", ctx.accounts.user.key(), referrer);
+    if referrer == Some(ctx.accounts.user.key()) || referrer == Some(Pubkey::default()) {
+        return Err(ErrorCode::InvalidReferrer.into());
+    }
     let individual_ledger: &mut IndividualLedgerAccount = &mut ctx.accounts.individual_ledger;
     let user_order_book: &mut UserOrderbook = &mut ctx.accounts.user_order_book;
     let user_events: &mut EventList = &mut ctx.accounts.order_events;
@@ -16,6 +22,7 @@ pub fn register_user_impl(ctx: Context<RegisterUser>) -> Result<()> {
     // Initialize individual_ledger
     individual_ledger.next_index = 0;
     individual_ledger.bump = ctx.bumps.individual_ledger;
+    individual_ledger.referrer = referrer.unwrap_or_default();
     for i in 0..MAX_TOKEN_MINTS {
         individual_ledger.tokens[i] = Pubkey::default();
         individual_ledger.bitmap[i] = 0;
@@ -31,12 +38,7 @@ pub fn register_user_impl(ctx: Context<RegisterUser>) -> Result<()> {
 
     // Initialize user_events
     user_events.bump = ctx.bumps.order_events;
-    user_events.init(
-        ctx.accounts.user.key(),
-        Pubkey::default(), // token_buy
-        Pubkey::default(), // token_sell
-        0, // order_id
-    );
+    user_events.init(ctx.accounts.user.key());
     
     Ok(())
 }
@@ -50,7 +52,7 @@ pub struct RegisterUser<'info> {
         payer = user,
         seeds = [INDIVIDUAL_LEDGER_SEED, user.key().as_ref()],
         bump,
-        space = 8 + MAX_TOKEN_MINTS * 32 + MAX_TOKEN_MINTS + 2 + 1 // Adjust size based on IndividualLedgerAccount struct size
+        space = 8 + MAX_TOKEN_MINTS * 32 + MAX_TOKEN_MINTS + 2 + 1 + 32 // Adjust size based on IndividualLedgerAccount struct size
     )]
     pub individual_ledger: Box<Account<'info, IndividualLedgerAccount>>,
     #[account(
@@ -66,7 +68,7 @@ pub struct RegisterUser<'info> {
         payer = user,
         seeds = [ORDER_EVENTS_SEED, user.key().as_ref()],
         bump,
-        space = 10240 as usize
+        space = EventList::SPACE
     )]
     pub order_events: Box<Account<'info, EventList>>,
 
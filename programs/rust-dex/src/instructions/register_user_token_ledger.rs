@@ -4,19 +4,27 @@ use crate::state::IndividualTokenLedgerAccount;
 use crate::state::INDIVIDUAL_TOKEN_LEDGER_SEED;
 use crate::IndividualLedgerAccount;
 use crate::INDIVIDUAL_LEDGER_SEED;
+use crate::error::ErrorCode;
 
 pub fn register_user_token_ledger_impl(ctx: Context<RegisterUserTokenLedger>, mint_account: Pubkey) -> Result<()> {
     msg!("Registering user token ledger with mint: {:?} and user account: {:?}", 
         mint_account, ctx.accounts.user_token_account.key());
+    require_keys_eq!(ctx.accounts.mint_account.key(), mint_account, ErrorCode::MintAccountMismatch);
     let user_token_ledger: &mut IndividualTokenLedgerAccount = &mut ctx.accounts.user_token_ledger;
 
     user_token_ledger.mint_account = mint_account;
     user_token_ledger.user_token_account = ctx.accounts.user_token_account.key();
+    user_token_ledger.owner = ctx.accounts.user.key();
     user_token_ledger.available_balance = 0;
     user_token_ledger.locked_balance = 0;
+    user_token_ledger.volume_window_start = 0;
+    user_token_ledger.window_volume = 0;
+    user_token_ledger.prior_window_volume = 0;
     user_token_ledger.bump = ctx.bumps.user_token_ledger;
 
     let individual_ledger: &mut IndividualLedgerAccount = &mut ctx.accounts.individual_ledger;
+    user_token_ledger.referrer = individual_ledger.referrer;
+    user_token_ledger.referral_earnings = 0;
     individual_ledger.add_token(mint_account)?;
 
     Ok(())
@@ -30,7 +38,7 @@ pub struct RegisterUserTokenLedger<'info> {
         payer = user,
         seeds = [INDIVIDUAL_TOKEN_LEDGER_SEED, mint_account.key().as_ref(), user.key().as_ref()],
         bump,
-        space = 8 + 16 + 16 + 32 + 32 + 1
+        space = IndividualTokenLedgerAccount::SIZE
     )]
     pub user_token_ledger: Account<'info, IndividualTokenLedgerAccount>,
     #[account(
@@ -40,6 +48,10 @@ pub struct RegisterUserTokenLedger<'info> {
     )]
     pub individual_ledger: Box<Account<'info, IndividualLedgerAccount>>,
     pub mint_account: Account<'info, Mint>,
+    #[account(
+        token::mint = mint_account,
+        token::authority = user,
+    )]
     pub user_token_account: Account<'info, TokenAccount>,
 
     #[account(mut)]
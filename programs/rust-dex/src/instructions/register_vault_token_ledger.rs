@@ -1,7 +1,10 @@
 use anchor_lang::prelude::*;
 use anchor_spl::token::{Mint, TokenAccount, Token};
-use crate::state::VaultTokenLedgerAccount;
+use crate::state::{FeeLedgerAccount, VaultTokenLedgerAccount};
+use crate::state::{DexManager, DEX_MANAGER_SEED};
+use crate::state::FEE_LEDGER_SEED;
 use crate::state::VAULT_TOKEN_LEDGER_SEED;
+use crate::error::ErrorCode;
 
 pub fn register_vault_token_ledger_impl(ctx: Context<RegisterVaultTokenLedger>) -> Result<()> {
     
@@ -14,6 +17,12 @@ pub fn register_vault_token_ledger_impl(ctx: Context<RegisterVaultTokenLedger>)
     vault_token_ledger.bump = ctx.bumps.vault_token_ledger;
     vault_token_ledger.total_balance = 0;
     vault_token_ledger.authority_bump = ctx.bumps.vault_token_authority;
+
+    let fee_ledger: &mut FeeLedgerAccount = &mut ctx.accounts.fee_ledger;
+    fee_ledger.mint_account = ctx.accounts.mint_account.key();
+    fee_ledger.accrued_fees = 0;
+    fee_ledger.collected_fees = 0;
+    fee_ledger.bump = ctx.bumps.fee_ledger;
     Ok(())  
 }
 
@@ -22,12 +31,20 @@ pub fn register_vault_token_ledger_impl(ctx: Context<RegisterVaultTokenLedger>)
 pub struct RegisterVaultTokenLedger<'info> {
     #[account(
         init, 
-        payer = user,
+        payer = admin,
         seeds = [VAULT_TOKEN_LEDGER_SEED, mint_account.key().as_ref()],
         bump,
         space = 8 + 16 + 32 + 32 + 1 + 1 
     )]
     pub vault_token_ledger: Account<'info, VaultTokenLedgerAccount>,
+    #[account(
+        init,
+        payer = admin,
+        seeds = [FEE_LEDGER_SEED, mint_account.key().as_ref()],
+        bump,
+        space = FeeLedgerAccount::SIZE
+    )]
+    pub fee_ledger: Account<'info, FeeLedgerAccount>,
     /// CHECK: This is a PDA used as token authority, derived from seeds
     #[account(
         seeds = [b"vault_token_account", mint_account.key().as_ref()],
@@ -37,14 +54,20 @@ pub struct RegisterVaultTokenLedger<'info> {
     pub mint_account: Account<'info, Mint>,
     #[account(
         init,
-        payer = user,
+        payer = admin,
         token::mint = mint_account,
         token::authority = vault_token_authority       
         // use vault_token_ledger as authority, so user can withdraw without other authorities
     )]
     pub vault_token_account: Account<'info, TokenAccount>,
+    #[account(
+        seeds = [DEX_MANAGER_SEED],
+        bump = dex_manager.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub dex_manager: Account<'info, DexManager>,
     #[account(mut)]
-    pub user: Signer<'info>,
+    pub admin: Signer<'info>,
     pub system_program: Program<'info, System>,
     pub token_program: Program<'info, Token>, // needed by vault_token_account's initialization
 }
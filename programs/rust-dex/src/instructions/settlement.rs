@@ -0,0 +1,265 @@
+use anchor_lang::prelude::*;
+use crate::matching_engine::{OrderFailure, OrderProcessResult, OrderSuccess};
+use crate::state::{FeeLedgerAccount, IndividualTokenLedgerAccount};
+use crate::state::FEE_LEDGER_SEED;
+use crate::state::INDIVIDUAL_TOKEN_LEDGER_SEED;
+use crate::error::ErrorCode;
+
+/// What a taker gave and got across all fills of one order. `bought` is net
+/// of the taker fee.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TakerFill {
+    pub sold: u64,
+    pub bought: u64,
+}
+
+/// Counterparty token ledgers passed through `remaining_accounts`, along with
+/// the fee ledgers of the mints a fee is charged in. Ledgers are deserialized
+/// on first use and kept around, so several fills against the same maker see
+/// each other's updates. Call `exit` once settlement is done.
+pub struct LedgerCache<'info> {
+    accounts: &'info [AccountInfo<'info>],
+    loaded: Vec<Account<'info, IndividualTokenLedgerAccount>>,
+    fee_ledgers: Vec<Account<'info, FeeLedgerAccount>>,
+}
+
+impl<'info> LedgerCache<'info> {
+    pub fn new(accounts: &'info [AccountInfo<'info>]) -> Self {
+        Self {
+            accounts,
+            loaded: Vec::new(),
+            fee_ledgers: Vec::new(),
+        }
+    }
+
+    /// Adds `fee` to the fee ledger of `mint`. Fee ledgers are only required
+    /// once a fee is actually charged in their mint.
+    pub fn accrue_fee(&mut self, mint: &Pubkey, fee: u64) -> Result<()> {
+        if fee == 0 {
+            return Ok(());
+        }
+        let (address, _) = Pubkey::find_program_address(&[FEE_LEDGER_SEED, mint.as_ref()], &crate::ID);
+        if let Some(fee_ledger) = self.fee_ledgers.iter_mut().find(|fee_ledger| fee_ledger.key() == address) {
+            return fee_ledger.accrue(fee);
+        }
+        let info = self.accounts.iter()
+            .find(|info| info.key() == address && info.is_writable)
+            .ok_or(ErrorCode::MissingFeeLedger)?;
+        let mut fee_ledger = Account::<FeeLedgerAccount>::try_from(info)?;
+        fee_ledger.accrue(fee)?;
+        self.fee_ledgers.push(fee_ledger);
+        Ok(())
+    }
+
+    /// Pays `referral_fee` to `referrer`'s ledger of `mint`, which has to be
+    /// passed once there is a fee to pay. A referrer that has no ledger of
+    /// `mint` yet earns nothing, the fee then stays with the DEX. Returns what
+    /// was paid.
+    pub fn credit_referrer(&mut self, referrer: &Pubkey, mint: &Pubkey, referral_fee: u64) -> Result<u64> {
+        if referral_fee == 0 {
+            return Ok(0);
+        }
+        let (address, _) = Pubkey::find_program_address(
+            &[INDIVIDUAL_TOKEN_LEDGER_SEED, mint.as_ref(), referrer.as_ref()],
+            &crate::ID,
+        );
+        let info = self.accounts.iter()
+            .find(|info| info.key() == address)
+            .ok_or(ErrorCode::MissingReferrerLedger)?;
+        if info.owner != &crate::ID {
+            return Ok(0);
+        }
+        self.get_mut(referrer, mint)?.credit_referral(referral_fee)?;
+        Ok(referral_fee)
+    }
+
+    pub fn get_mut(&mut self, owner: &Pubkey, mint: &Pubkey) -> Result<&mut Account<'info, IndividualTokenLedgerAccount>> {
+        let (address, _) = Pubkey::find_program_address(
+            &[INDIVIDUAL_TOKEN_LEDGER_SEED, mint.as_ref(), owner.as_ref()],
+            &crate::ID,
+        );
+        if let Some(index) = self.loaded.iter().position(|ledger| ledger.key() == address) {
+            return Ok(&mut self.loaded[index]);
+        }
+        let info = self.accounts.iter()
+            .find(|info| info.key() == address)
+            .ok_or(ErrorCode::MissingCounterpartyLedger)?;
+        if !info.is_writable {
+            return Err(ErrorCode::CounterpartyLedgerNotWritable.into());
+        }
+        let ledger = Account::<IndividualTokenLedgerAccount>::try_from(info)?;
+        if ledger.owner != *owner {
+            return Err(ErrorCode::LedgerOwnerMismatch.into());
+        }
+        if ledger.mint_account != *mint {
+            return Err(ErrorCode::LedgerMintMismatch.into());
+        }
+        self.loaded.push(ledger);
+        let last = self.loaded.len() - 1;
+        Ok(&mut self.loaded[last])
+    }
+
+    /// Writes every touched ledger back to its account.
+    pub fn exit(&self) -> Result<()> {
+        for ledger in self.loaded.iter() {
+            ledger.exit(&crate::ID)?;
+        }
+        for fee_ledger in self.fee_ledgers.iter() {
+            fee_ledger.exit(&crate::ID)?;
+        }
+        Ok(())
+    }
+}
+
+/// Settles the maker half of a fill: the maker's sold tokens leave its locked
+/// balance and the tokens paid by the taker, less the maker fee, are credited
+/// as available, along with the maker rebate in the sold token. Both amounts
+/// count towards the maker's trailing volume, unless `taker` is the maker itself.
+#[allow(clippy::too_many_arguments)]
+pub fn settle_maker_fill(
+    makers: &mut LedgerCache,
+    taker: &Pubkey,
+    maker: &Pubkey,
+    maker_sell_token: &Pubkey,
+    maker_buy_token: &Pubkey,
+    maker_sell_quantity: u64,
+    maker_buy_quantity: u64,
+    maker_fee: u64,
+    maker_rebate: u64,
+) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    let outcome_ledger = makers.get_mut(maker, maker_sell_token)?;
+    outcome_ledger.locked_balance = outcome_ledger.locked_balance
+        .checked_sub(maker_sell_quantity)
+        .ok_or(ErrorCode::SettlementOverflow)?;
+    outcome_ledger.available_balance = outcome_ledger.available_balance
+        .checked_add(maker_rebate)
+        .ok_or(ErrorCode::SettlementOverflow)?;
+    if maker != taker {
+        outcome_ledger.record_volume(maker_sell_quantity, now);
+    }
+
+    let income = maker_buy_quantity.checked_sub(maker_fee).ok_or(ErrorCode::SettlementOverflow)?;
+    let income_ledger = makers.get_mut(maker, maker_buy_token)?;
+    income_ledger.available_balance = income_ledger.available_balance
+        .checked_add(income)
+        .ok_or(ErrorCode::SettlementOverflow)?;
+    if maker != taker {
+        income_ledger.record_volume(maker_buy_quantity, now);
+    }
+    makers.accrue_fee(maker_buy_token, maker_fee)
+}
+
+/// The part of a taker fee left for the fee ledger once the maker rebate and
+/// the referral fee paid out of it are taken off.
+pub fn retained_taker_fee(taker_fee: u64, maker_rebate: u64, referral_paid: u64) -> Result<u64> {
+    taker_fee
+        .checked_sub(maker_rebate)
+        .and_then(|fee| fee.checked_sub(referral_paid))
+        .ok_or(ErrorCode::SettlementOverflow.into())
+}
+
+/// Settles the maker side of every fill in `result` and totals the taker side.
+/// Any unfilled remainder fails the whole order, so the taker either trades
+/// its full size or nothing.
+pub fn settle_fills_against_makers(
+    makers: &mut LedgerCache,
+    result: OrderProcessResult,
+    taker: &Pubkey,
+    taker_buy_token: &Pubkey,
+    taker_sell_token: &Pubkey,
+) -> Result<TakerFill> {
+    let mut fill = TakerFill::default();
+    for res in result {
+        match res {
+            Ok(OrderSuccess::Filled { oppo_user, sell_quantity, buy_quantity, taker_fee, maker_fee, maker_rebate, .. }) => {
+                if oppo_user == *taker {
+                    return Err(ErrorCode::SelfTrade.into());
+                }
+                settle_maker_fill(makers, taker, &oppo_user, taker_buy_token, taker_sell_token, buy_quantity, sell_quantity, maker_fee, maker_rebate)?;
+                makers.accrue_fee(taker_buy_token, retained_taker_fee(taker_fee, maker_rebate, 0)?)?;
+                let bought = buy_quantity.checked_sub(taker_fee).ok_or(ErrorCode::SettlementOverflow)?;
+                fill.sold = fill.sold.checked_add(sell_quantity).ok_or(ErrorCode::SettlementOverflow)?;
+                fill.bought = fill.bought.checked_add(bought).ok_or(ErrorCode::SettlementOverflow)?;
+            },
+            Err(failure) => {
+                msg!("Order Failure {}: {:?}", u32::from(failure.error_code()), failure);
+                // An exhausted book is reported as a liquidity shortfall of the swap
+                return Err(match failure {
+                    OrderFailure::NoMatch { .. } => ErrorCode::InsufficientLiquidity,
+                    other => other.error_code(),
+                }.into());
+            },
+        }
+    }
+    Ok(fill)
+}
+
+/// Settles both halves of every entry in `result` right away instead of
+/// queueing them in the taker's `EventList`. Fills against the taker's own
+/// resting orders are applied to the taker ledgers, since those are already
+/// loaded by the instruction, and count towards neither side's trailing volume.
+/// Fees accrue to the fee ledgers passed along with the makers, as does the
+/// ledger of the taker's referrer when a referral fee is due. Returns how much
+/// of the taker's locked balance was released, so callers can unlock whatever
+/// the order did not use.
+pub fn settle_order_immediately(
+    makers: &mut LedgerCache,
+    result: OrderProcessResult,
+    taker: &Pubkey,
+    taker_buy_token: &Pubkey,
+    taker_sell_token: &Pubkey,
+    taker_income_ledger: &mut IndividualTokenLedgerAccount,
+    taker_outcome_ledger: &mut IndividualTokenLedgerAccount,
+) -> Result<u64> {
+    let now = Clock::get()?.unix_timestamp;
+    let mut released: u64 = 0;
+    for res in result {
+        let (sell_quantity, buy_quantity, rollback) = match res {
+            Ok(OrderSuccess::Filled { oppo_user, sell_quantity, buy_quantity, taker_fee, maker_fee, maker_rebate, referral_fee, .. }) => {
+                if oppo_user == *taker {
+                    taker_income_ledger.locked_balance = taker_income_ledger.locked_balance
+                        .checked_sub(buy_quantity)
+                        .ok_or(ErrorCode::SettlementOverflow)?;
+                    let maker_income = sell_quantity.checked_sub(maker_fee).ok_or(ErrorCode::SettlementOverflow)?;
+                    taker_outcome_ledger.available_balance = taker_outcome_ledger.available_balance
+                        .checked_add(maker_income)
+                        .ok_or(ErrorCode::SettlementOverflow)?;
+                    makers.accrue_fee(taker_sell_token, maker_fee)?;
+                } else {
+                    settle_maker_fill(makers, taker, &oppo_user, taker_buy_token, taker_sell_token, buy_quantity, sell_quantity, maker_fee, maker_rebate)?;
+                    taker_income_ledger.record_volume(buy_quantity, now);
+                    taker_outcome_ledger.record_volume(sell_quantity, now);
+                }
+                let referral_paid = makers.credit_referrer(&taker_income_ledger.referrer, taker_buy_token, referral_fee)?;
+                makers.accrue_fee(taker_buy_token, retained_taker_fee(taker_fee, maker_rebate, referral_paid)?)?;
+                // A maker rebate of a self-trade is paid in the token the taker buys
+                let rebate = if oppo_user == *taker { maker_rebate } else { 0 };
+                let bought = buy_quantity.checked_sub(taker_fee).and_then(|net| net.checked_add(rebate)).ok_or(ErrorCode::SettlementOverflow)?;
+                (sell_quantity, bought, false)
+            },
+            Err(OrderFailure::NoMatch { sell_quantity, .. })
+            | Err(OrderFailure::TooManyEvents { sell_quantity, .. })
+            | Err(OrderFailure::OrderHeapFull { sell_quantity, .. })
+            | Err(OrderFailure::PriceBandBreached { sell_quantity, .. }) => (sell_quantity, 0, true),
+            Err(failure) => {
+                msg!("Order Failure {}: {:?}", u32::from(failure.error_code()), failure);
+                continue;
+            },
+        };
+        taker_outcome_ledger.locked_balance = taker_outcome_ledger.locked_balance
+            .checked_sub(sell_quantity)
+            .ok_or(ErrorCode::SettlementOverflow)?;
+        if rollback {
+            taker_outcome_ledger.available_balance = taker_outcome_ledger.available_balance
+                .checked_add(sell_quantity)
+                .ok_or(ErrorCode::SettlementOverflow)?;
+        } else {
+            taker_income_ledger.available_balance = taker_income_ledger.available_balance
+                .checked_add(buy_quantity)
+                .ok_or(ErrorCode::SettlementOverflow)?;
+        }
+        released = released.checked_add(sell_quantity).ok_or(ErrorCode::SettlementOverflow)?;
+    }
+    Ok(released)
+}
@@ -0,0 +1,211 @@
+use anchor_lang::prelude::*;
+use std::cmp::{max, min};
+use anchor_spl::token::{Token, TokenAccount};
+use crate::{
+    common::{FeeDiscount, OrderRequest, OrderType},
+    instructions::common::{charge_fees, check_order_allowed, record_market_events, OrderEventEmitter},
+    instructions::settlement::{settle_fills_against_makers, LedgerCache, TakerFill},
+    matching_engine::MatchingEngine,
+    state::{Market, MarketEventLog, TokenPairAccount, VaultTokenLedgerAccount},
+    DexManager, UserOrderbook
+};
+use crate::market_seeds;
+use crate::state::DEX_MANAGER_SEED;
+use crate::state::MARKET_SEED;
+use crate::state::MARKET_EVENTS_SEED;
+use crate::state::TOKEN_PAIR_SEED;
+use crate::state::VAULT_TOKEN_LEDGER_SEED;
+use crate::error::ErrorCode;
+use crate::event_sink;
+
+/// Sells exactly `amount_in` of `token_in` from the user's wallet against the
+/// book and sends the proceeds straight to the user's `token_out` account.
+/// Maker ledgers are passed through `remaining_accounts` and settled in place,
+/// along with the fee ledgers of both tokens when the market charges fees.
+pub fn swap_exact_in_impl<'info>(
+    mut ctx: Context<'_, '_, 'info, 'info, Swap<'info>>,
+    token_in: Pubkey,
+    token_out: Pubkey,
+    amount_in: u64,
+    min_amount_out: u64,
+) -> Result<()> {
+    msg!("Swap exact in: {} of {} for {}", amount_in, token_in, token_out);
+    if amount_in == 0 {
+        return Err(ErrorCode::InvalidAmount.into());
+    }
+    let fill = match_swap(&mut ctx, token_in, token_out, 0, amount_in, true)?;
+    if fill.bought < min_amount_out {
+        return Err(ErrorCode::SlippageExceeded.into());
+    }
+    transfer_swap_funds(&mut ctx, token_in, token_out, fill)
+}
+
+/// Buys exactly `amount_out` of `token_out` for at most `max_amount_in` of
+/// `token_in`, pulling the input from and paying the output to the user's wallet.
+/// The order buys enough on top of `amount_out` to cover the taker fee, and
+/// sells no more than `max_amount_in` doing so.
+pub fn swap_exact_out_impl<'info>(
+    mut ctx: Context<'_, '_, 'info, 'info, Swap<'info>>,
+    token_in: Pubkey,
+    token_out: Pubkey,
+    amount_out: u64,
+    max_amount_in: u64,
+) -> Result<()> {
+    msg!("Swap exact out: {} of {} for {}", amount_out, token_out, token_in);
+    if amount_out == 0 {
+        return Err(ErrorCode::InvalidAmount.into());
+    }
+    let buy_amount = ctx.accounts.market.fee_rates()
+        .gross_up_taker(amount_out)
+        .ok_or(ErrorCode::InvalidAmount)?;
+    // The order stops buying once `max_amount_in` is spent, short of `amount_out`
+    let fill = match_swap(&mut ctx, token_in, token_out, buy_amount, max_amount_in, false)?;
+    if fill.bought < amount_out {
+        return Err(ErrorCode::MaxAmountInExceeded.into());
+    }
+    transfer_swap_funds(&mut ctx, token_in, token_out, fill)
+}
+
+fn match_swap<'info>(
+    ctx: &mut Context<'_, '_, 'info, 'info, Swap<'info>>,
+    token_in: Pubkey,
+    token_out: Pubkey,
+    buy_amount: u64,
+    sell_amount: u64,
+    is_sell: bool,
+) -> Result<TakerFill> {
+    if token_in == token_out {
+        return Err(ErrorCode::InvalidTokenPair.into());
+    }
+    check_order_allowed(&ctx.accounts.dex_manager, &ctx.accounts.market, OrderType::Market, Clock::get()?.unix_timestamp)?;
+    let mut buy_queue_account = ctx.accounts.out_in_queue.load_mut()?;
+    let mut sell_queue_account = ctx.accounts.in_out_queue.load_mut()?;
+    let user_key = ctx.accounts.user.key();
+
+    let order_request = OrderRequest::new(
+        ctx.accounts.dex_manager.next_sequence_number(),
+        buy_amount,
+        sell_amount,
+        token_out,
+        token_in,
+        user_key,
+        Clock::get()?.unix_timestamp,
+        OrderType::Market,
+        FeeDiscount::default(),
+    );
+    let base_mint = ctx.accounts.market.base_mint;
+    let mut lifecycle = OrderEventEmitter::new(
+        &mut ctx.accounts.dex_manager, event_sink!(ctx), ctx.accounts.market.key(), base_mint, Clock::get()?.unix_timestamp
+    );
+    lifecycle.placed(&order_request)?;
+
+    // Market orders never rest on the book, so the taker's orderbook is not needed
+    let mut scratch_orderbook = Box::new(UserOrderbook::default());
+    let mut engine = MatchingEngine::new(
+        token_out,
+        token_in,
+        &mut buy_queue_account.order_heap,
+        &mut sell_queue_account.order_heap,
+        scratch_orderbook.as_mut(),
+    ).with_price_band(ctx.accounts.market.price_band());
+    let mut result = engine.process_order(order_request, is_sell)?;
+    charge_fees(&mut result, ctx.accounts.market.fee_rates(), 0, 0);
+    record_market_events(&mut ctx.accounts.market, &mut *ctx.accounts.market_events.load_mut()?, &token_out, &result, Clock::get()?.unix_timestamp);
+    lifecycle.matched(&token_out, &token_in, &result)?;
+
+    let mut makers = LedgerCache::new(ctx.remaining_accounts);
+    let fill = settle_fills_against_makers(&mut makers, result, &user_key, &token_out, &token_in)?;
+    makers.exit()?;
+    Ok(fill)
+}
+
+fn transfer_swap_funds(ctx: &mut Context<Swap>, token_in: Pubkey, token_out: Pubkey, fill: TakerFill) -> Result<()> {
+    let cpi_accounts = anchor_spl::token::Transfer {
+        from: ctx.accounts.user_in_token_account.to_account_info(),
+        to: ctx.accounts.vault_in_token_account.to_account_info(),
+        authority: ctx.accounts.user.to_account_info(),
+    };
+    let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+    anchor_spl::token::transfer(cpi_ctx, fill.sold)?;
+
+    let signer_seeds: &[&[&[u8]]] = &[market_seeds!(ctx.accounts.vault_out_ledger, token_out)];
+    let cpi_accounts = anchor_spl::token::Transfer {
+        from: ctx.accounts.vault_out_token_account.to_account_info(),
+        to: ctx.accounts.user_out_token_account.to_account_info(),
+        authority: ctx.accounts.vault_out_authority.to_account_info(),
+    };
+    let cpi_ctx = CpiContext::new_with_signer(
+        ctx.accounts.token_program.to_account_info(), cpi_accounts, signer_seeds
+    );
+    anchor_spl::token::transfer(cpi_ctx, fill.bought)?;
+
+    // The input now backs the makers' ledger credits and fees, the output was
+    // their locked balance less the taker fee, which stays in the vault
+    let vault_in_ledger = &mut ctx.accounts.vault_in_ledger;
+    vault_in_ledger.total_balance = vault_in_ledger.total_balance
+        .checked_add(fill.sold)
+        .ok_or(ErrorCode::BalanceOverflow)?;
+    let vault_out_ledger = &mut ctx.accounts.vault_out_ledger;
+    vault_out_ledger.total_balance = vault_out_ledger.total_balance
+        .checked_sub(fill.bought)
+        .ok_or(ErrorCode::BalanceOverflow)?;
+
+    msg!("Swapped {} of {} for {} of {}", fill.sold, token_in, fill.bought, token_out);
+    Ok(())
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+#[instruction(token_in: Pubkey, token_out: Pubkey)]
+pub struct Swap<'info> {
+    /// Resting orders that buy `token_in`, matched by the swap
+    #[account(mut, seeds = [TOKEN_PAIR_SEED, token_in.as_ref(), token_out.as_ref()], bump)]
+    pub in_out_queue: AccountLoader<'info, TokenPairAccount>,
+
+    #[account(mut, seeds = [TOKEN_PAIR_SEED, token_out.as_ref(), token_in.as_ref()], bump)]
+    pub out_in_queue: AccountLoader<'info, TokenPairAccount>,
+
+    #[account(mut, seeds = [MARKET_SEED, min(&token_in, &token_out).as_ref(), max(&token_in, &token_out).as_ref()], bump = market.bump)]
+    pub market: Box<Account<'info, Market>>,
+
+    #[account(mut, seeds = [MARKET_EVENTS_SEED, min(&token_in, &token_out).as_ref(), max(&token_in, &token_out).as_ref()], bump)]
+    pub market_events: AccountLoader<'info, MarketEventLog>,
+
+    #[account(mut, seeds = [DEX_MANAGER_SEED], bump)]
+    pub dex_manager: Account<'info, DexManager>,
+
+    #[account(
+        mut,
+        seeds = [VAULT_TOKEN_LEDGER_SEED, token_in.as_ref()],
+        bump = vault_in_ledger.bump,
+        constraint = vault_in_ledger.vault_token_account == vault_in_token_account.key()
+    )]
+    pub vault_in_ledger: Box<Account<'info, VaultTokenLedgerAccount>>,
+    #[account(mut)]
+    pub vault_in_token_account: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [VAULT_TOKEN_LEDGER_SEED, token_out.as_ref()],
+        bump = vault_out_ledger.bump,
+        constraint = vault_out_ledger.vault_token_account == vault_out_token_account.key()
+    )]
+    pub vault_out_ledger: Box<Account<'info, VaultTokenLedgerAccount>>,
+    /// CHECK: This is a PDA used as token authority, derived from seeds
+    #[account(
+        seeds = [b"vault_token_account", token_out.as_ref()],
+        bump = vault_out_ledger.authority_bump
+    )]
+    pub vault_out_authority: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub vault_out_token_account: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut, token::mint = token_in, token::authority = user)]
+    pub user_in_token_account: Box<Account<'info, TokenAccount>>,
+    #[account(mut, token::mint = token_out)]
+    pub user_out_token_account: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
@@ -0,0 +1,158 @@
+use anchor_lang::prelude::*;
+use crate::{
+    common::{FeeDiscount, OrderRequest, OrderType, MAX_ROUTE_HOPS},
+    instructions::common::{charge_fees, check_order_allowed, record_market_events, OrderEventEmitter},
+    instructions::settlement::{settle_fills_against_makers, LedgerCache},
+    matching_engine::MatchingEngine,
+    state::{IndividualTokenLedgerAccount, Market, MarketEventLog, TokenPairAccount},
+    DexManager, UserOrderbook
+};
+use crate::state::DEX_MANAGER_SEED;
+use crate::state::INDIVIDUAL_TOKEN_LEDGER_SEED;
+use crate::error::ErrorCode;
+use crate::event_sink;
+
+// Per hop: both token pair accounts, the market and its event log
+const ACCOUNTS_PER_HOP: usize = 4;
+
+/// Sells `amount_in` of `path[0]` through consecutive market orders until it is
+/// converted into `path[path.len() - 1]`.
+///
+/// `remaining_accounts` holds, for every hop `path[i] -> path[i + 1]`, the
+/// `[path[i], path[i + 1]]` and `[path[i + 1], path[i]]` token pair accounts, the
+/// `Market` and the market event log of that pair, writable, followed by the
+/// token ledgers of every maker the route fills against. Makers
+/// are settled in place, and the intermediate tokens are only carried between
+/// hops, so they never leave the vault. Markets that charge fees also need the
+/// fee ledgers of the tokens they charge in after the maker ledgers.
+pub fn swap_route_impl<'info>(
+    ctx: Context<'_, '_, 'info, 'info, SwapRoute<'info>>,
+    path: Vec<Pubkey>,
+    amount_in: u64,
+    min_amount_out: u64,
+) -> Result<()> {
+    msg!("Swapping {} along route of {} tokens", amount_in, path.len());
+    if amount_in == 0 {
+        return Err(ErrorCode::InvalidAmount.into());
+    }
+    if path.len() < 2 || path.len() > MAX_ROUTE_HOPS + 1 {
+        return Err(ErrorCode::InvalidRoute.into());
+    }
+    let token_in = path[0];
+    let token_out = path[path.len() - 1];
+    if token_in == token_out || path.windows(2).any(|hop| hop[0] == hop[1]) {
+        return Err(ErrorCode::InvalidRoute.into());
+    }
+    if ctx.accounts.user_token_in_ledger.mint_account != token_in
+        || ctx.accounts.user_token_out_ledger.mint_account != token_out {
+        return Err(ErrorCode::InvalidRoute.into());
+    }
+    if ctx.accounts.user_token_in_ledger.available_balance < amount_in {
+        return Err(ErrorCode::InsufficientBalance.into());
+    }
+
+    let hops = path.len() - 1;
+    if ctx.remaining_accounts.len() < hops * ACCOUNTS_PER_HOP {
+        return Err(ErrorCode::InvalidTokenPair.into());
+    }
+    let (hop_accounts, maker_ledgers) = ctx.remaining_accounts.split_at(hops * ACCOUNTS_PER_HOP);
+    let mut makers = LedgerCache::new(maker_ledgers);
+    let user_key = ctx.accounts.user.key();
+
+    let mut carried = amount_in;
+    for hop in 0..hops {
+        let token_sell = path[hop];
+        let token_buy = path[hop + 1];
+
+        // Resting orders that buy what this hop sells
+        let accounts = &hop_accounts[hop * ACCOUNTS_PER_HOP..(hop + 1) * ACCOUNTS_PER_HOP];
+        let sell_queue_loader = AccountLoader::<TokenPairAccount>::try_from(&accounts[0])?;
+        let buy_queue_loader = AccountLoader::<TokenPairAccount>::try_from(&accounts[1])?;
+        let mut market = Account::<Market>::try_from(&accounts[2])?;
+        let market_events_loader = AccountLoader::<MarketEventLog>::try_from(&accounts[3])?;
+        let mut sell_queue_account = sell_queue_loader.load_mut()?;
+        let mut buy_queue_account = buy_queue_loader.load_mut()?;
+        if sell_queue_account.buy_token != token_sell || sell_queue_account.sell_token != token_buy
+            || buy_queue_account.buy_token != token_buy || buy_queue_account.sell_token != token_sell {
+            return Err(ErrorCode::InvalidTokenPair.into());
+        }
+        let mut market_events = market_events_loader.load_mut()?;
+        if !market.is_market(&token_sell, &token_buy) || !market_events.is_market(&token_sell, &token_buy) {
+            return Err(ErrorCode::InvalidTokenPair.into());
+        }
+        check_order_allowed(&ctx.accounts.dex_manager, &market, OrderType::Market, Clock::get()?.unix_timestamp)?;
+
+        let order_request = OrderRequest::new(
+            ctx.accounts.dex_manager.next_sequence_number(),
+            0,
+            carried,
+            token_buy,
+            token_sell,
+            user_key,
+            Clock::get()?.unix_timestamp,
+            OrderType::Market,
+            FeeDiscount::default(),
+        );
+        let mut lifecycle = OrderEventEmitter::new(
+            &mut ctx.accounts.dex_manager, event_sink!(ctx), market.key(), market.base_mint, Clock::get()?.unix_timestamp
+        );
+        lifecycle.placed(&order_request)?;
+        // Market orders never rest on the book, so the taker's orderbook is not needed
+        let mut scratch_orderbook = Box::new(UserOrderbook::default());
+        let mut engine = MatchingEngine::new(
+            token_buy,
+            token_sell,
+            &mut buy_queue_account.order_heap,
+            &mut sell_queue_account.order_heap,
+            scratch_orderbook.as_mut(),
+        ).with_price_band(market.price_band());
+        let mut result = engine.process_order(order_request, true)?;
+        charge_fees(&mut result, market.fee_rates(), 0, 0);
+        record_market_events(&mut market, &mut market_events, &token_buy, &result, Clock::get()?.unix_timestamp);
+        lifecycle.matched(&token_buy, &token_sell, &result)?;
+
+        let fill = settle_fills_against_makers(&mut makers, result, &user_key, &token_buy, &token_sell)?;
+        carried = fill.bought;
+        market.exit(&crate::ID)?;
+    }
+
+    if carried < min_amount_out {
+        return Err(ErrorCode::SlippageExceeded.into());
+    }
+    let user_token_in_ledger = &mut ctx.accounts.user_token_in_ledger;
+    user_token_in_ledger.available_balance = user_token_in_ledger.available_balance
+        .checked_sub(amount_in)
+        .ok_or(ErrorCode::InsufficientBalance)?;
+    let user_token_out_ledger = &mut ctx.accounts.user_token_out_ledger;
+    user_token_out_ledger.available_balance = user_token_out_ledger.available_balance
+        .checked_add(carried)
+        .ok_or(ErrorCode::BalanceOverflow)?;
+    makers.exit()?;
+
+    msg!("Route filled: {} in, {} out", amount_in, carried);
+    Ok(())
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct SwapRoute<'info> {
+    #[account(mut, seeds = [DEX_MANAGER_SEED], bump)]
+    pub dex_manager: Account<'info, DexManager>,
+
+    #[account(
+        mut,
+        seeds = [INDIVIDUAL_TOKEN_LEDGER_SEED, user_token_in_ledger.mint_account.as_ref(), user.key().as_ref()],
+        bump = user_token_in_ledger.bump
+    )]
+    pub user_token_in_ledger: Box<Account<'info, IndividualTokenLedgerAccount>>,
+
+    #[account(
+        mut,
+        seeds = [INDIVIDUAL_TOKEN_LEDGER_SEED, user_token_out_ledger.mint_account.as_ref(), user.key().as_ref()],
+        bump = user_token_out_ledger.bump
+    )]
+    pub user_token_out_ledger: Box<Account<'info, IndividualTokenLedgerAccount>>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+}
@@ -5,18 +5,27 @@ use crate::state::IndividualTokenLedgerAccount;
 use crate::market_seeds;
 use crate::state::INDIVIDUAL_TOKEN_LEDGER_SEED;
 use crate::state::VAULT_TOKEN_LEDGER_SEED;
-
-#[error_code]
-pub enum ErrorCode {
-    InsufficientBalance,
-}
+use crate::error::ErrorCode;
 
 pub fn withdraw_impl(ctx: Context<Withdraw>, _mint_account: Pubkey, amount: u64) -> Result<()> {
     msg!("Withdraw amount: {}", amount);
+    if amount == 0 {
+        return Err(ErrorCode::InvalidAmount.into());
+    }
     let user_balance = ctx.accounts.user_token_ledger.available_balance;
     if user_balance < amount {
         return Err(ErrorCode::InsufficientBalance.into());
     }
+    // Update ledger balances before the transfer leaves the vault
+    let user_ledger = &mut ctx.accounts.user_token_ledger;
+    let vault_ledger = &mut ctx.accounts.vault_token_ledger;
+    user_ledger.available_balance = user_ledger.available_balance
+        .checked_sub(amount)
+        .ok_or(ErrorCode::InsufficientBalance)?;
+    vault_ledger.total_balance = vault_ledger.total_balance
+        .checked_sub(amount)
+        .ok_or(ErrorCode::InsufficientVaultBalance)?;
+
     // Transfer tokens from vault token account to user token account
     let signer_seeds: &[&[&[u8]]] = &[market_seeds!(ctx.accounts.vault_token_ledger, _mint_account)];
     let cpi_accounts = anchor_spl::token::Transfer {
@@ -5,8 +5,16 @@ pub use crate::state::*;
 mod matching_engine;
 mod instructions;
 mod common;
+mod error;
 mod state;
 
+pub use crate::error::ErrorCode;
+pub use crate::common::{MarketStatus, OrderSide, OrderType};
+pub use crate::common::{
+    OrderCancelledEvent, OrderEventHeader, OrderFilledEvent, OrderPlacedEvent, OrderRestedEvent,
+    OrderRole, OrderRolledBackEvent, RollbackReason, ORDER_EVENT_VERSION,
+};
+
 declare_id!("FbCipEZbUmmQt5C9AvcvyMewWt3PtkL5RCLB5McmY2AJ");
 
 #[program]
@@ -18,6 +26,11 @@ pub mod rust_dex {
         msg!("Greetings from: {:?}", ctx.program_id);
         let dex_manager = &mut ctx.accounts.dex_manager;
         dex_manager.sequence_number = 0;
+        dex_manager.event_sequence = 0;
+        dex_manager.admin = ctx.accounts.user.key();
+        dex_manager.pending_admin = Pubkey::default();
+        dex_manager.referral_share_bps = 0;
+        dex_manager.paused = false;
         dex_manager.bump = ctx.bumps.dex_manager;
         Ok(())
     }
@@ -30,8 +43,16 @@ pub mod rust_dex {
         instructions::register_user_token_ledger_impl(ctx, mint_account)
     }
 
-    pub fn register_user (ctx: Context<RegisterUser>) -> Result<()> {
-        instructions::register_user_impl(ctx)
+    pub fn register_user (ctx: Context<RegisterUser>, referrer: Option<Pubkey>) -> Result<()> {
+        instructions::register_user_impl(ctx, referrer)
+    }
+
+    pub fn close_user_token_ledger<'info>(ctx: Context<'_, '_, 'info, 'info, CloseUserTokenLedger<'info>>, mint: Pubkey) -> Result<()> {
+        instructions::close_user_token_ledger_impl(ctx, mint)
+    }
+
+    pub fn deregister_user<'info>(ctx: Context<'_, '_, 'info, 'info, DeregisterUser<'info>>) -> Result<()> {
+        instructions::deregister_user_impl(ctx)
     }
 
     pub fn register_token_pair(ctx: Context<RegisterTokenPair>, token1: Pubkey, token2: Pubkey) -> Result<()> {
@@ -46,17 +67,129 @@ pub mod rust_dex {
         instructions::withdraw_impl(ctx, _mint_account, amount)
     }
 
-    pub fn place_limit_order(ctx: Context<PlaceLimitOrder>, base: Pubkey, quote: Pubkey, side: String, price: f64, amount: u64) -> Result<()> {
+    pub fn audit_vault(ctx: Context<AuditVault>, mint: Pubkey) -> Result<()> {
+        instructions::audit_vault_impl(ctx, mint)
+    }
+
+    pub fn place_limit_order<'info>(ctx: Context<'_, '_, 'info, 'info, PlaceLimitOrder<'info>>, base: Pubkey, quote: Pubkey, side: OrderSide, price: f64, amount: u64) -> Result<()> {
         instructions::place_limit_order_impl(ctx, base, quote, side, price, amount)
     }
-    pub fn place_market_order(ctx: Context<PlaceMarketOrder>, base: Pubkey, quote: Pubkey, side: String, amount: u64) -> Result<()> {
+    pub fn place_market_order<'info>(ctx: Context<'_, '_, 'info, 'info, PlaceMarketOrder<'info>>, base: Pubkey, quote: Pubkey, side: OrderSide, amount: u64) -> Result<()> {
         instructions::place_market_order_impl(ctx, base, quote, side, amount)
     }
 
-    pub fn consume_events(ctx: Context<ConsumeEvents>, opposite_user_key: Pubkey) -> Result<()> {
+    pub fn quote_market_order(ctx: Context<QuoteMarketOrder>, base: Pubkey, quote: Pubkey, side: OrderSide, amount: u64) -> Result<MarketOrderQuote> {
+        instructions::quote_market_order_impl(ctx, base, quote, side, amount)
+    }
+
+    pub fn swap_route<'info>(ctx: Context<'_, '_, 'info, 'info, SwapRoute<'info>>, path: Vec<Pubkey>, amount_in: u64, min_amount_out: u64) -> Result<()> {
+        instructions::swap_route_impl(ctx, path, amount_in, min_amount_out)
+    }
+
+    pub fn swap_exact_in<'info>(ctx: Context<'_, '_, 'info, 'info, Swap<'info>>, token_in: Pubkey, token_out: Pubkey, amount_in: u64, min_amount_out: u64) -> Result<()> {
+        instructions::swap_exact_in_impl(ctx, token_in, token_out, amount_in, min_amount_out)
+    }
+
+    pub fn swap_exact_out<'info>(ctx: Context<'_, '_, 'info, 'info, Swap<'info>>, token_in: Pubkey, token_out: Pubkey, amount_out: u64, max_amount_in: u64) -> Result<()> {
+        instructions::swap_exact_out_impl(ctx, token_in, token_out, amount_out, max_amount_in)
+    }
+
+    pub fn consume_events<'info>(ctx: Context<'_, '_, 'info, 'info, ConsumeEvents<'info>>, opposite_user_key: Pubkey) -> Result<()> {
         instructions::consume_event_impl(ctx, opposite_user_key)
     }
 
+    pub fn consume_events_batch<'info>(ctx: Context<'_, '_, 'info, 'info, ConsumeEventsBatch<'info>>, max_events: u8) -> Result<()> {
+        instructions::consume_events_batch_impl(ctx, max_events)
+    }
+
+    pub fn init_crank_pool(ctx: Context<InitCrankPool>, base: Pubkey, quote: Pubkey, reward_per_event: u64) -> Result<()> {
+        instructions::init_crank_pool_impl(ctx, base, quote, reward_per_event)
+    }
+
+    pub fn set_crank_reward(ctx: Context<SetCrankReward>, base: Pubkey, quote: Pubkey, reward_per_event: u64) -> Result<()> {
+        instructions::set_crank_reward_impl(ctx, base, quote, reward_per_event)
+    }
+
+    pub fn fund_crank_pool(ctx: Context<FundCrankPool>, lamports: u64) -> Result<()> {
+        instructions::fund_crank_pool_impl(ctx, lamports)
+    }
+
+    pub fn crank_consume_events<'info>(ctx: Context<'_, '_, 'info, 'info, CrankConsumeEvents<'info>>, owner: Pubkey, opposite_user_key: Pubkey) -> Result<()> {
+        instructions::crank_consume_events_impl(ctx, owner, opposite_user_key)
+    }
+
+    pub fn set_market_fees(ctx: Context<SetMarketFees>, base: Pubkey, quote: Pubkey, maker_fee_bps: u16, taker_fee_bps: u16) -> Result<()> {
+        instructions::set_market_fees_impl(ctx, base, quote, maker_fee_bps, taker_fee_bps)
+    }
+
+    pub fn set_referral_share(ctx: Context<SetReferralShare>, share_bps: u16) -> Result<()> {
+        instructions::set_referral_share_impl(ctx, share_bps)
+    }
+
+    pub fn set_market_status(ctx: Context<SetMarketStatus>, base: Pubkey, quote: Pubkey, status: MarketStatus) -> Result<()> {
+        instructions::set_market_status_impl(ctx, base, quote, status)
+    }
+
+    pub fn set_price_band(ctx: Context<SetPriceBand>, base: Pubkey, quote: Pubkey, band_bps: u16, halt_cooldown_secs: i64) -> Result<()> {
+        instructions::set_price_band_impl(ctx, base, quote, band_bps, halt_cooldown_secs)
+    }
+
+    pub fn set_reference_price(ctx: Context<SetReferencePrice>, base: Pubkey, quote: Pubkey, reference_price: f64) -> Result<()> {
+        instructions::set_reference_price_impl(ctx, base, quote, reference_price)
+    }
+
+    pub fn set_trading_paused(ctx: Context<SetTradingPaused>, paused: bool) -> Result<()> {
+        instructions::set_trading_paused_impl(ctx, paused)
+    }
+
+    pub fn cancel_delisted_orders<'info>(ctx: Context<'_, '_, 'info, 'info, CancelDelistedOrders<'info>>, base: Pubkey, quote: Pubkey) -> Result<()> {
+        instructions::cancel_delisted_orders_impl(ctx, base, quote)
+    }
+
+    pub fn close_market(ctx: Context<CloseMarket>, base: Pubkey, quote: Pubkey) -> Result<()> {
+        instructions::close_market_impl(ctx, base, quote)
+    }
+
+    pub fn init_fee_tiers(ctx: Context<InitFeeTiers>, quote_mint: Pubkey, tiers: Vec<FeeTier>) -> Result<()> {
+        instructions::init_fee_tiers_impl(ctx, quote_mint, tiers)
+    }
+
+    pub fn set_fee_tiers(ctx: Context<SetFeeTiers>, quote_mint: Pubkey, tiers: Vec<FeeTier>) -> Result<()> {
+        instructions::set_fee_tiers_impl(ctx, quote_mint, tiers)
+    }
+
+    pub fn collect_fees(ctx: Context<CollectFees>, mint: Pubkey) -> Result<()> {
+        instructions::collect_fees_impl(ctx, mint)
+    }
+
+    pub fn propose_admin(ctx: Context<ProposeAdmin>, new_admin: Pubkey) -> Result<()> {
+        instructions::propose_admin_impl(ctx, new_admin)
+    }
+
+    pub fn accept_admin(ctx: Context<AcceptAdmin>) -> Result<()> {
+        instructions::accept_admin_impl(ctx)
+    }
+
+    pub fn init_governance(ctx: Context<InitGovernance>, signers: Vec<Pubkey>, threshold: u8, timelock_secs: i64) -> Result<()> {
+        instructions::init_governance_impl(ctx, signers, threshold, timelock_secs)
+    }
+
+    pub fn set_governance(ctx: Context<SetGovernance>, signers: Vec<Pubkey>, threshold: u8, timelock_secs: i64) -> Result<()> {
+        instructions::set_governance_impl(ctx, signers, threshold, timelock_secs)
+    }
+
+    pub fn create_proposal(ctx: Context<CreateProposal>, accounts: Vec<ProposalAccountMeta>, data: Vec<u8>) -> Result<()> {
+        instructions::create_proposal_impl(ctx, accounts, data)
+    }
+
+    pub fn approve_proposal(ctx: Context<ApproveProposal>) -> Result<()> {
+        instructions::approve_proposal_impl(ctx)
+    }
+
+    pub fn execute_proposal<'info>(ctx: Context<'_, '_, 'info, 'info, ExecuteProposal<'info>>) -> Result<()> {
+        instructions::execute_proposal_impl(ctx)
+    }
+
     pub fn close_dex_manager(_ctx: Context<CloseDexManager>) -> Result<()> {
         msg!("Closing DEX manager account");
         Ok(())
@@ -75,11 +208,12 @@ pub struct CloseDexManager<'info> {
         mut,
         seeds = [DEX_MANAGER_SEED],
         bump = dex_manager.bump,
-        close = user
+        close = admin,
+        has_one = admin @ ErrorCode::Unauthorized
     )]
     pub dex_manager: Account<'info, DexManager>,
     #[account(mut)]
-    pub user: Signer<'info>,
+    pub admin: Signer<'info>,
     pub system_program: Program<'info, System>,
 }
 
@@ -90,7 +224,7 @@ pub struct Initialize<'info> {
         payer = user,
         seeds = [DEX_MANAGER_SEED], 
         bump,
-        space = 8 + 8 + 1 // discriminator + sequence_number + bump
+        space = 8 + 8 + 8 + 32 + 32 + 2 + 1 + 1 // discriminator + sequence_number + event_sequence + admin + pending_admin + referral_share_bps + paused + bump
     )]
     pub dex_manager: Account<'info, DexManager>,
     #[account(mut)]
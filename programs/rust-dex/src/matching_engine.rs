@@ -1,14 +1,20 @@
 use std::{fmt::Debug, result::Result};
 
-use anchor_lang::error_code;
 use anchor_lang::{emit, prelude::Pubkey};
-use crate::common::NoMatchedOrderEvent;
 use crate::OrderHeap;
-use crate::{common::{AcceptedOrderEvent, FilledOrderEvent, OrderRequest, OrderType, PartiallyFilledOrderEvent, InternalErrorEvent, MAX_EVENTS}, state::OrderNode, UserOrderbook};
+use crate::{common::{OrderRequest, OrderType, InternalErrorEvent, PriceBand, MAX_EVENTS}, state::OrderNode, UserOrderbook};
+use crate::error::ErrorCode;
 
 
 #[derive(Debug, Clone)]
 pub enum OrderSuccess {
+    /// The `remaining_*` quantities are what the taker and the maker
+    /// (`oppo_*`) still have open once the fill is done. The engine leaves
+    /// the fees at 0, `charge_fees` sets them from the market's rates: the
+    /// taker fee is in `buy_quantity`'s token, the maker fee in `sell_quantity`'s
+    /// and the maker rebate and referral fee, paid out of the taker fee, in
+    /// `buy_quantity`'s.
+    /// `maker_discount_bps` is the fee tier discount the maker order rests with.
     Filled{
         _who: Pubkey,
         oppo_user: Pubkey,
@@ -17,18 +23,38 @@ pub enum OrderSuccess {
         _order_type: OrderType,
         sell_quantity: u64,
         buy_quantity: u64,
+        remaining_buy_quantity: u64,
+        remaining_sell_quantity: u64,
+        oppo_remaining_buy_quantity: u64,
+        oppo_remaining_sell_quantity: u64,
         filled: bool,
         oppo_filled: bool,
+        taker_fee: u64,
+        maker_fee: u64,
+        maker_rebate: u64,
+        referral_fee: u64,
+        maker_discount_bps: u16,
     },
 }
 
-#[error_code]
-pub enum ErrorCode {
-    OrderNotFound,
-    OrderHeapFull,
-    TooManyEvents,
-    NoMatch,
-    
+/// `a * b / c` rounded down, the share of `b` that `a` out of `c` is worth.
+fn mul_div(a: u64, b: u64, c: u64) -> anchor_lang::Result<u64> {
+    if c == 0 {
+        return Err(ErrorCode::ArithmeticOverflow.into());
+    }
+    u64::try_from(a as u128 * b as u128 / c as u128).map_err(|_| ErrorCode::ArithmeticOverflow.into())
+}
+
+/// `a * b / c` rounded up, so that whoever pays the share never pays less than it is worth.
+fn mul_div_ceil(a: u64, b: u64, c: u64) -> anchor_lang::Result<u64> {
+    if c == 0 {
+        return Err(ErrorCode::ArithmeticOverflow.into());
+    }
+    u64::try_from((a as u128 * b as u128).div_ceil(c as u128)).map_err(|_| ErrorCode::ArithmeticOverflow.into())
+}
+
+fn checked_sub(a: u64, b: u64) -> anchor_lang::Result<u64> {
+    a.checked_sub(b).ok_or(ErrorCode::ArithmeticOverflow.into())
 }
 
 #[derive(Debug, Clone)]
@@ -57,9 +83,31 @@ pub enum OrderFailure {
     OrderNotFound{
         _order_id: u64,
     },
+    /// Matching stopped at a resting order priced outside the market's price
+    /// band, the quantities are what the taker order still had open.
+    PriceBandBreached{
+        who: Pubkey,
+        _order_id: u64,
+        _order_type: OrderType,
+        sell_quantity: u64,
+        buy_quantity: u64,
+    },
 }
 
-type OrderProcessResult = Vec<Result<OrderSuccess, OrderFailure>>;
+impl OrderFailure {
+    /// The program error a failure surfaces as when it aborts an instruction.
+    pub fn error_code(&self) -> ErrorCode {
+        match self {
+            OrderFailure::TooManyEvents { .. } => ErrorCode::TooManyEvents,
+            OrderFailure::OrderHeapFull { .. } => ErrorCode::OrderHeapFull,
+            OrderFailure::NoMatch { .. } => ErrorCode::NoMatch,
+            OrderFailure::OrderNotFound { .. } => ErrorCode::OrderNotFound,
+            OrderFailure::PriceBandBreached { .. } => ErrorCode::PriceBandBreached,
+        }
+    }
+}
+
+pub type OrderProcessResult = Vec<Result<OrderSuccess, OrderFailure>>;
 
 pub struct MatchingEngine<'a> {
     pub _buy_token: Pubkey,
@@ -67,6 +115,7 @@ pub struct MatchingEngine<'a> {
     pub buy_queue: &'a mut dyn OrderHeap,
     pub sell_queue: &'a mut dyn OrderHeap,
     pub user_orderbook: &'a mut UserOrderbook,
+    pub price_band: PriceBand,
 }
 
 impl<'a> MatchingEngine<'a> {
@@ -77,10 +126,40 @@ impl<'a> MatchingEngine<'a> {
             buy_queue,
             sell_queue,
             user_orderbook,
+            price_band: PriceBand::default(),
         }
     }
+
+    /// Enforces `price_band` on the orders this engine processes: limit orders
+    /// priced outside it are rejected, and matching skips resting orders priced
+    /// beyond it in the taker's favour and stops at those priced beyond it
+    /// against the taker.
+    pub fn with_price_band(mut self, price_band: PriceBand) -> Self {
+        self.price_band = price_band;
+        self
+    }
+
+    /// Matches a market order without emitting any order events, used to quote
+    /// an order against copies of the order heaps.
+    pub fn simulate_market_order(&mut self, order: OrderRequest, is_sell: bool) -> anchor_lang::Result<OrderProcessResult> {
+        let mut result: OrderProcessResult = Vec::new();
+        let order_node = OrderNode::new(
+            order.id,
+            order.buy_quantity,
+            order.sell_quantity,
+            order.buy_token,
+            order.sell_token,
+            order.owner,
+            order.timestamp,
+            order.fee_discount.maker_bps,
+        );
+        let mut skipped = Vec::new();
+        Self::process_market_order(self.buy_queue, self.sell_queue, order_node, &mut result, is_sell, &self.price_band, &mut skipped)?;
+        Self::restore_skipped(self.sell_queue, skipped)?;
+        Ok(result)
+    }
     
-    pub fn process_order(&mut self, order: OrderRequest, is_sell: bool) -> OrderProcessResult {
+    pub fn process_order(&mut self, order: OrderRequest, is_sell: bool) -> anchor_lang::Result<OrderProcessResult> {
         let mut result: OrderProcessResult = Vec::new();
         let order_node = OrderNode::new(
             order.id,
@@ -90,20 +169,43 @@ impl<'a> MatchingEngine<'a> {
             order.sell_token,
             order.owner,
             order.timestamp,
+            order.fee_discount.maker_bps,
         );
+        let mut skipped = Vec::new();
         match order.order_type {
             OrderType::Limit => {
-                emit!(AcceptedOrderEvent::from_order_request(&order));
-                Self::process_limit_order(self.buy_queue, self.sell_queue, order_node, &mut result, self.user_orderbook, is_sell);
+                let price = self.price_band.price_of(&order_node.buy_token, order_node.buy_quantity, order_node.sell_quantity);
+                if !self.price_band.contains(price) {
+                    return Err(ErrorCode::PriceOutsideBand.into());
+                }
+                Self::process_limit_order(self.buy_queue, self.sell_queue, order_node, &mut result, self.user_orderbook, is_sell, true, &self.price_band, &mut skipped)?;
             },
             OrderType::Market => {
-                emit!(AcceptedOrderEvent::from_order_request(&order));
-                Self::process_market_order(self.buy_queue, self.sell_queue, order_node, &mut result, is_sell);
+                Self::process_market_order(self.buy_queue, self.sell_queue, order_node, &mut result, is_sell, &self.price_band, &mut skipped)?;
             }
         }
-        result
+        Self::restore_skipped(self.sell_queue, skipped)?;
+        Ok(result)
     }
 
+    /// Whether a limit order would match the best opposite order right away.
+    pub fn would_match(&self, order: &OrderRequest) -> bool {
+        let order_node = OrderNode::new(
+            order.id,
+            order.buy_quantity,
+            order.sell_quantity,
+            order.buy_token,
+            order.sell_token,
+            order.owner,
+            order.timestamp,
+            order.fee_discount.maker_bps,
+        );
+        self.sell_queue
+            .get_best_order()
+            .is_some_and(|best| best.buy_price() >= order_node.sell_price())
+    }
+
+    #[allow(clippy::too_many_arguments)]
     fn process_limit_order(
         buy_queue: &mut dyn OrderHeap,
         sell_queue: &mut dyn OrderHeap,
@@ -111,9 +213,23 @@ impl<'a> MatchingEngine<'a> {
         result: &mut OrderProcessResult,
         user_orderbook: &mut UserOrderbook,
         is_sell: bool,
-    ) { 
+        emit_events: bool,
+        price_band: &PriceBand,
+        skipped: &mut Vec<OrderNode>,
+    ) -> anchor_lang::Result<()> {
+        Self::skip_makers_beyond_band(sell_queue, price_band, skipped)?;
         if let Some(sell_order) = sell_queue.get_best_order() {
             let match_available = sell_order.buy_price() >= order.sell_price();
+            if match_available && !Self::within_band(price_band, sell_order) {
+                result.push(Result::Err(OrderFailure::PriceBandBreached {
+                    who: order.owner,
+                    _order_id: order.id,
+                    _order_type: OrderType::Limit,
+                    sell_quantity: order.sell_quantity,
+                    buy_quantity: order.buy_quantity,
+                }));
+                return Ok(());
+            }
             if result.len() + 2 > MAX_EVENTS  {
                 result.push(Result::Err(OrderFailure::TooManyEvents{
                     who: order.owner,
@@ -122,13 +238,13 @@ impl<'a> MatchingEngine<'a> {
                     sell_quantity: order.sell_quantity,
                     buy_quantity: order.buy_quantity,
                 }));
-                return; 
+                return Ok(());
             }
             if match_available {
-                let completed = Self::order_match(&mut order, sell_queue, result, OrderType::Limit, is_sell);
+                let completed = Self::order_match(&mut order, sell_queue, result, OrderType::Limit, is_sell)?;
 
                 if !completed {
-                    Self::process_limit_order(buy_queue, sell_queue, order, result, user_orderbook, is_sell);
+                    Self::process_limit_order(buy_queue, sell_queue, order, result, user_orderbook, is_sell, emit_events, price_band, skipped)?;
                 }
             }else {
                 match user_orderbook.add_order(order.id as u128) {
@@ -139,7 +255,9 @@ impl<'a> MatchingEngine<'a> {
                         }
                     },
                     Err(e) => {
-                        emit!(InternalErrorEvent::new(format!("Failed to add order to user orderbook: {}", e)));
+                        if emit_events {
+                            emit!(InternalErrorEvent::new(format!("Failed to add order to user orderbook: {}", e)));
+                        }
                     }
                 }
             }
@@ -151,18 +269,21 @@ impl<'a> MatchingEngine<'a> {
                     }
                 },
                 Err(e) => {
-                    emit!(InternalErrorEvent::new(format!("Failed to add order to user orderbook: {}", e)));
+                    if emit_events {
+                        emit!(InternalErrorEvent::new(format!("Failed to add order to user orderbook: {}", e)));
+                    }
                 }
             }
         }
+        Ok(())
     }
 
     fn order_match_sell(
         order: &mut OrderNode,
         sell_queue: &mut dyn OrderHeap,
         result: &mut OrderProcessResult,
-        order_type: OrderType
-    ) -> bool {
+        order_type: OrderType,
+    ) -> anchor_lang::Result<bool> {
         let best_sell_result = sell_queue.get_best_order();
         let best_sell_order: &OrderNode;
         match best_sell_result {
@@ -170,23 +291,24 @@ impl<'a> MatchingEngine<'a> {
                 best_sell_order = best_sell_inner;
             },
             None => {
-                return false;
+                return Ok(false);
             }
         }
         let oppo_buy_quantity = best_sell_order.buy_quantity;
         if order.sell_quantity < oppo_buy_quantity {
-            let buy_quantity = order.sell_quantity * best_sell_order.buy_price() as u64;
+            // The maker pays in proportion to the part of its order that is filled
+            let buy_quantity = mul_div(order.sell_quantity, best_sell_order.sell_quantity, best_sell_order.buy_quantity)?;
             let oppo_sell_order_mut: &mut OrderNode;
             match sell_queue.get_best_order_mut() {
                 Some(oppo_sell_inner) => {
                     oppo_sell_order_mut = oppo_sell_inner;
                 },
                 None => {
-                    return false;
+                    return Ok(false);
                 }
             }
-            oppo_sell_order_mut.sell_quantity -= buy_quantity;
-            oppo_sell_order_mut.buy_quantity -= order.sell_quantity;
+            oppo_sell_order_mut.sell_quantity = checked_sub(oppo_sell_order_mut.sell_quantity, buy_quantity)?;
+            oppo_sell_order_mut.buy_quantity = checked_sub(oppo_sell_order_mut.buy_quantity, order.sell_quantity)?;
             result.push(Result::Ok(OrderSuccess::Filled { 
                 _who: order.owner,
                 oppo_user: oppo_sell_order_mut.owner,
@@ -195,27 +317,32 @@ impl<'a> MatchingEngine<'a> {
                 _order_type: order_type,
                 sell_quantity: order.sell_quantity,
                 buy_quantity: buy_quantity,
+                remaining_buy_quantity: 0,
+                remaining_sell_quantity: 0,
+                oppo_remaining_buy_quantity: oppo_sell_order_mut.buy_quantity,
+                oppo_remaining_sell_quantity: oppo_sell_order_mut.sell_quantity,
                 filled: true,
                 oppo_filled: false,  // 对方订单未完全成交
+                taker_fee: 0,
+                maker_fee: 0,
+                maker_rebate: 0,
+                referral_fee: 0,
+                maker_discount_bps: oppo_sell_order_mut.maker_discount_bps,
             }));
-            emit!(FilledOrderEvent::from_order_node(order, order_type));
-            emit!(PartiallyFilledOrderEvent::from_order_node(
-                oppo_sell_order_mut.id,
-                oppo_sell_order_mut.owner,
-                oppo_sell_order_mut.buy_token,
-                oppo_sell_order_mut.sell_token,
-                order.sell_quantity,
-                buy_quantity,
-                OrderType::Limit,
-            ));
-            return true;
+            return Ok(true);
         } else if order.sell_quantity > oppo_buy_quantity {
             let oppo_sell_order_mut: &mut OrderNode;
             match sell_queue.get_best_order_mut() {
                 Some(order) => oppo_sell_order_mut = order,
-                None => return false,
+                None => return Ok(false),
             }
 
+            let remaining_sell = checked_sub(order.sell_quantity, oppo_sell_order_mut.buy_quantity)?;
+            if order_type == OrderType::Limit {
+                // The maker may pay more than asked, the rest keeps the limit price
+                order.buy_quantity = mul_div(remaining_sell, order.buy_quantity, order.sell_quantity)?;
+            }
+            order.sell_quantity = remaining_sell;
             result.push(Result::Ok(OrderSuccess::Filled {
                 _who: order.owner,
                 oppo_user: oppo_sell_order_mut.owner,
@@ -224,33 +351,28 @@ impl<'a> MatchingEngine<'a> {
                 _order_type: order_type,
                 sell_quantity: oppo_sell_order_mut.buy_quantity,
                 buy_quantity: oppo_sell_order_mut.sell_quantity,
+                remaining_buy_quantity: order.buy_quantity,
+                remaining_sell_quantity: order.sell_quantity,
+                oppo_remaining_buy_quantity: 0,
+                oppo_remaining_sell_quantity: 0,
                 filled: false,  // 当前订单未完全成交
                 oppo_filled: true,  // 对方订单已完全成交
+                taker_fee: 0,
+                maker_fee: 0,
+                maker_rebate: 0,
+                referral_fee: 0,
+                maker_discount_bps: oppo_sell_order_mut.maker_discount_bps,
             }));
-            order.sell_quantity -= oppo_sell_order_mut.buy_quantity;
-            if order_type == OrderType::Limit {
-                order.buy_quantity -= oppo_sell_order_mut.sell_quantity;
-            }
             let opposite_order_id = oppo_sell_order_mut.id;
-            emit!(FilledOrderEvent::from_order_node(oppo_sell_order_mut, OrderType::Limit));
-            emit!(PartiallyFilledOrderEvent::from_order_node(
-                order.id,
-                order.owner,
-                order.buy_token,
-                order.sell_token,
-                oppo_sell_order_mut.sell_quantity,
-                oppo_sell_order_mut.buy_quantity,
-                order_type,
-            ));
             if let Err(_) = sell_queue.remove_order(opposite_order_id) {
                 result.push(Result::Err(OrderFailure::OrderNotFound{_order_id: opposite_order_id}));
             }
-            return false;
+            return Ok(false);
         } else {
             let oppo_order_mut: &mut OrderNode;
             match sell_queue.get_best_order_mut() {
                 Some(order) => oppo_order_mut = order,
-                None => return false,
+                None => return Ok(false),
             }
             result.push(Result::Ok(OrderSuccess::Filled {
                 _who: order.owner,
@@ -260,16 +382,23 @@ impl<'a> MatchingEngine<'a> {
                 _order_type: order_type,
                 sell_quantity: oppo_order_mut.buy_quantity,
                 buy_quantity: oppo_order_mut.sell_quantity,
+                remaining_buy_quantity: 0,
+                remaining_sell_quantity: 0,
+                oppo_remaining_buy_quantity: 0,
+                oppo_remaining_sell_quantity: 0,
                 filled: true,
                 oppo_filled: true,  // 双方订单完全成交
+                taker_fee: 0,
+                maker_fee: 0,
+                maker_rebate: 0,
+                referral_fee: 0,
+                maker_discount_bps: oppo_order_mut.maker_discount_bps,
             }));
-            emit!(FilledOrderEvent::from_order_node(oppo_order_mut, OrderType::Limit));
-            emit!(FilledOrderEvent::from_order_node(order, order_type));
             let opposite_order_id = oppo_order_mut.id;
             if let Err(_) = sell_queue.remove_order(opposite_order_id) {
                 result.push(Result::Err(OrderFailure::OrderNotFound{_order_id: opposite_order_id}));
             }
-            return true;
+            return Ok(true);
         }
     }
 
@@ -277,31 +406,47 @@ impl<'a> MatchingEngine<'a> {
         order: &mut OrderNode,
         sell_queue: &mut dyn OrderHeap,
         result: &mut OrderProcessResult,
-        order_type: OrderType
-    ) -> bool {
+        order_type: OrderType,
+    ) -> anchor_lang::Result<bool> {
         let best_sell_order: &OrderNode;
         match sell_queue.get_best_order() {
             Some(best_sell_inner) => {
                 best_sell_order = best_sell_inner;
             },
             None => {
-                return false;
+                return Ok(false);
             }
         }
         let oppo_sell_quantity = best_sell_order.sell_quantity;
+        if order_type == OrderType::Market {
+            // A market buy stops once what is left of its locked balance runs out
+            let wanted = order.buy_quantity.min(oppo_sell_quantity);
+            if best_sell_order.buy_quantity > 0 {
+                let affordable = order.sell_quantity as u128 * best_sell_order.sell_quantity as u128
+                    / best_sell_order.buy_quantity as u128;
+                if affordable == 0 {
+                    return Ok(true);
+                }
+                if affordable < wanted as u128 {
+                    order.buy_quantity = affordable as u64;
+                }
+            }
+        }
         if order.buy_quantity < oppo_sell_quantity {
-            let sell_quantity = order.buy_quantity * best_sell_order.sell_price() as u64;
+            // The taker pays in proportion to the part of the maker order it fills,
+            // rounded up so that the resting order never sells below its price
+            let sell_quantity = mul_div_ceil(order.buy_quantity, best_sell_order.buy_quantity, best_sell_order.sell_quantity)?;
             let oppo_sell_order_mut: &mut OrderNode;
             match sell_queue.get_best_order_mut() {
                 Some(oppo_sell_inner) => {
                     oppo_sell_order_mut = oppo_sell_inner;
                 },
                 None => {
-                    return false;
+                    return Ok(false);
                 }
             }
-            oppo_sell_order_mut.buy_quantity -= sell_quantity;
-            oppo_sell_order_mut.sell_quantity -= order.buy_quantity;
+            oppo_sell_order_mut.buy_quantity = checked_sub(oppo_sell_order_mut.buy_quantity, sell_quantity)?;
+            oppo_sell_order_mut.sell_quantity = checked_sub(oppo_sell_order_mut.sell_quantity, order.buy_quantity)?;
             result.push(Result::Ok(OrderSuccess::Filled { 
                 _who: order.owner,
                 oppo_user: oppo_sell_order_mut.owner,
@@ -310,27 +455,28 @@ impl<'a> MatchingEngine<'a> {
                 _order_type: order_type,
                 sell_quantity: sell_quantity,
                 buy_quantity: order.buy_quantity,
+                remaining_buy_quantity: 0,
+                remaining_sell_quantity: 0,
+                oppo_remaining_buy_quantity: oppo_sell_order_mut.buy_quantity,
+                oppo_remaining_sell_quantity: oppo_sell_order_mut.sell_quantity,
                 filled: true,
                 oppo_filled: false,  // 对方订单未完全成交
+                taker_fee: 0,
+                maker_fee: 0,
+                maker_rebate: 0,
+                referral_fee: 0,
+                maker_discount_bps: oppo_sell_order_mut.maker_discount_bps,
             }));
-            emit!(FilledOrderEvent::from_order_node(order, order_type));
-            emit!(PartiallyFilledOrderEvent::from_order_node(
-                oppo_sell_order_mut.id,
-                oppo_sell_order_mut.owner,
-                oppo_sell_order_mut.buy_token,
-                oppo_sell_order_mut.sell_token,
-                sell_quantity,
-                order.buy_quantity,
-                OrderType::Limit,
-            ));
-            return true;
+            return Ok(true);
         } else if order.buy_quantity > oppo_sell_quantity {
             let oppo_sell_order_mut: &mut OrderNode;
             match sell_queue.get_best_order_mut() {
                 Some(order) => oppo_sell_order_mut = order,
-                None => return false,
+                None => return Ok(false),
             }
 
+            order.sell_quantity = checked_sub(order.sell_quantity, oppo_sell_order_mut.buy_quantity)?;
+            order.buy_quantity = checked_sub(order.buy_quantity, oppo_sell_order_mut.sell_quantity)?;
             result.push(Result::Ok(OrderSuccess::Filled {
                 _who: order.owner,
                 oppo_user: oppo_sell_order_mut.owner,
@@ -339,32 +485,29 @@ impl<'a> MatchingEngine<'a> {
                 _order_type: order_type,
                 sell_quantity: oppo_sell_order_mut.buy_quantity,
                 buy_quantity: oppo_sell_order_mut.sell_quantity,
+                remaining_buy_quantity: order.buy_quantity,
+                remaining_sell_quantity: order.sell_quantity,
+                oppo_remaining_buy_quantity: 0,
+                oppo_remaining_sell_quantity: 0,
                 filled: false,  // 当前订单未完全成交
                 oppo_filled: true,  // 对方订单已完全成交
+                taker_fee: 0,
+                maker_fee: 0,
+                maker_rebate: 0,
+                referral_fee: 0,
+                maker_discount_bps: oppo_sell_order_mut.maker_discount_bps,
             }));
-            order.sell_quantity -= oppo_sell_order_mut.buy_quantity;
-            order.buy_quantity -= oppo_sell_order_mut.sell_quantity;
 
             let opposite_order_id = oppo_sell_order_mut.id;
-            emit!(FilledOrderEvent::from_order_node(oppo_sell_order_mut, OrderType::Limit));
-            emit!(PartiallyFilledOrderEvent::from_order_node(
-                order.id,
-                order.owner,
-                order.buy_token,
-                order.sell_token,
-                oppo_sell_order_mut.sell_quantity,
-                oppo_sell_order_mut.buy_quantity,
-                order_type,
-            ));
             if let Err(_) = sell_queue.remove_order(opposite_order_id) {
                 result.push(Result::Err(OrderFailure::OrderNotFound{_order_id: opposite_order_id}));
             }
-            return false;
+            return Ok(false);
         } else {
             let oppo_order_mut: &mut OrderNode;
             match sell_queue.get_best_order_mut() {
                 Some(order) => oppo_order_mut = order,
-                None => return false,
+                None => return Ok(false),
             }
             result.push(Result::Ok(OrderSuccess::Filled {
                 _who: order.owner,
@@ -374,16 +517,23 @@ impl<'a> MatchingEngine<'a> {
                 _order_type: order_type,
                 sell_quantity: oppo_order_mut.buy_quantity,
                 buy_quantity: oppo_order_mut.sell_quantity,
+                remaining_buy_quantity: 0,
+                remaining_sell_quantity: 0,
+                oppo_remaining_buy_quantity: 0,
+                oppo_remaining_sell_quantity: 0,
                 filled: true,
                 oppo_filled: true,  // 双方订单完全成交
+                taker_fee: 0,
+                maker_fee: 0,
+                maker_rebate: 0,
+                referral_fee: 0,
+                maker_discount_bps: oppo_order_mut.maker_discount_bps,
             }));
-            emit!(FilledOrderEvent::from_order_node(oppo_order_mut, OrderType::Limit));
-            emit!(FilledOrderEvent::from_order_node(order, order_type));
             let opposite_order_id = oppo_order_mut.id;
             if let Err(_) = sell_queue.remove_order(opposite_order_id) {
                 result.push(Result::Err(OrderFailure::OrderNotFound{_order_id: opposite_order_id}));
             }
-            return true;
+            return Ok(true);
         }
     }
 
@@ -393,7 +543,7 @@ impl<'a> MatchingEngine<'a> {
         result: &mut OrderProcessResult,
         order_type: OrderType,
         is_sell: bool,
-    ) -> bool {
+    ) -> anchor_lang::Result<bool> {
         if is_sell {
             Self::order_match_sell(order, sell_queue, result, order_type)
         } else {
@@ -401,13 +551,47 @@ impl<'a> MatchingEngine<'a> {
         }
     }
 
+    /// Whether the resting `maker` order is priced within `price_band`.
+    fn within_band(price_band: &PriceBand, maker: &OrderNode) -> bool {
+        price_band.contains(price_band.price_of(&maker.buy_token, maker.buy_quantity, maker.sell_quantity))
+    }
+
+    /// Takes the best resting orders off `sell_queue` into `skipped` while they
+    /// are priced beyond `price_band` in the taker's favour, so that a stale
+    /// order left behind by the reference price neither fills nor blocks the
+    /// orders behind it. At most `MAX_EVENTS` orders are skipped per order.
+    fn skip_makers_beyond_band(sell_queue: &mut dyn OrderHeap, price_band: &PriceBand, skipped: &mut Vec<OrderNode>) -> anchor_lang::Result<()> {
+        while skipped.len() < MAX_EVENTS {
+            let Some(maker) = sell_queue.get_best_order() else {
+                return Ok(());
+            };
+            let price = price_band.price_of(&maker.buy_token, maker.buy_quantity, maker.sell_quantity);
+            if !price_band.favours_taker(&maker.buy_token, price) {
+                return Ok(());
+            }
+            let maker_id = maker.id;
+            skipped.push(sell_queue.remove_order(maker_id)?);
+        }
+        Ok(())
+    }
+
+    /// Puts the orders `skip_makers_beyond_band` took off back on the book.
+    fn restore_skipped(sell_queue: &mut dyn OrderHeap, skipped: Vec<OrderNode>) -> anchor_lang::Result<()> {
+        for maker in skipped {
+            sell_queue.add_order(maker)?;
+        }
+        Ok(())
+    }
+
     fn process_market_order(
         buy_queue: &mut dyn OrderHeap,
         sell_queue: &mut dyn OrderHeap,
         mut order: OrderNode,
         result: &mut OrderProcessResult,
-        is_sell: bool
-    ) {
+        is_sell: bool,
+        price_band: &PriceBand,
+        skipped: &mut Vec<OrderNode>,
+    ) -> anchor_lang::Result<()> {
         if result.len() + 2 > MAX_EVENTS  {
                 result.push(Result::Err(OrderFailure::TooManyEvents{
                     who: order.owner,
@@ -416,13 +600,24 @@ impl<'a> MatchingEngine<'a> {
                     sell_quantity: order.sell_quantity,
                     buy_quantity: order.buy_quantity,
                 }));
-                return;
+                return Ok(());
         } 
-        if let Some(_opposite_order) = sell_queue.get_best_order() {
-            let completed = Self::order_match(&mut order, sell_queue, result, OrderType::Market, is_sell);
+        Self::skip_makers_beyond_band(sell_queue, price_band, skipped)?;
+        if let Some(opposite_order) = sell_queue.get_best_order() {
+            if !Self::within_band(price_band, opposite_order) {
+                result.push(Result::Err(OrderFailure::PriceBandBreached {
+                    who: order.owner,
+                    _order_id: order.id,
+                    _order_type: OrderType::Market,
+                    sell_quantity: order.sell_quantity,
+                    buy_quantity: order.buy_quantity,
+                }));
+                return Ok(());
+            }
+            let completed = Self::order_match(&mut order, sell_queue, result, OrderType::Market, is_sell)?;
 
             if !completed {
-                Self::process_market_order(buy_queue, sell_queue, order, result, is_sell);
+                Self::process_market_order(buy_queue, sell_queue, order, result, is_sell, price_band, skipped)?;
             }
         } else {
             result.push(Result::Err(OrderFailure::NoMatch{
@@ -432,8 +627,88 @@ impl<'a> MatchingEngine<'a> {
                 sell_quantity: order.sell_quantity,
                 buy_quantity: order.buy_quantity,
             }));
-            emit!(NoMatchedOrderEvent::from_order_node(&order, OrderType::Market));
         }
+        Ok(())
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::FeeDiscount;
+    use crate::state::OrderHeapImpl;
+
+    const REFERENCE_PRICE: f64 = 100.0;
+
+    fn ask(id: u64, base: &Pubkey, quote: &Pubkey, base_quantity: u64, price: u64) -> OrderNode {
+        OrderNode::new(id, base_quantity * price, base_quantity, *quote, *base, Pubkey::new_unique(), 0, 0)
+    }
+
+    fn market_buy(base: &Pubkey, quote: &Pubkey, base_quantity: u64) -> OrderRequest {
+        OrderRequest::new(9, base_quantity, 1_000_000, *base, *quote, Pubkey::new_unique(), 0, OrderType::Market, FeeDiscount::default())
+    }
+
+    fn filled_maker_ids(result: &OrderProcessResult) -> Vec<u64> {
+        result.iter().filter_map(|res| match res {
+            Ok(OrderSuccess::Filled { oppo_order_id, .. }) => Some(*oppo_order_id),
+            _ => None,
+        }).collect()
+    }
+
+    #[test]
+    fn test_partial_buy_rounds_the_payment_up() {
+        let (base, quote) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut bids = OrderHeapImpl::new();
+        let mut asks = OrderHeapImpl::new();
+        let mut orderbook = Box::new(UserOrderbook::default());
+        // 3 base for 10 quote, 1 base is worth 3.33 quote
+        asks.add_order(OrderNode::new(1, 10, 3, quote, base, Pubkey::new_unique(), 0, 0)).unwrap();
+
+        let mut engine = MatchingEngine::new(base, quote, &mut bids, &mut asks, orderbook.as_mut());
+        let result = engine.process_order(market_buy(&base, &quote, 1), false).unwrap();
+
+        assert!(matches!(result[..], [Ok(OrderSuccess::Filled { buy_quantity: 1, sell_quantity: 4, .. })]));
+        let maker = asks.get_best_order().unwrap();
+        assert_eq!((maker.buy_quantity, maker.sell_quantity), (6, 2));
+    }
+
+    #[test]
+    fn test_market_order_skips_makers_below_the_band() {
+        let (base, quote) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let band = PriceBand { base_mint: base, reference_price: REFERENCE_PRICE, band_bps: 1_000 };
+        let mut bids = OrderHeapImpl::new();
+        let mut asks = OrderHeapImpl::new();
+        let mut orderbook = Box::new(UserOrderbook::default());
+        // A stale ask left below the band by the reference price, then one within it
+        asks.add_order(ask(1, &base, &quote, 1, 80)).unwrap();
+        asks.add_order(ask(2, &base, &quote, 10, 105)).unwrap();
+
+        let mut engine = MatchingEngine::new(base, quote, &mut bids, &mut asks, orderbook.as_mut()).with_price_band(band);
+        let result = engine.process_order(market_buy(&base, &quote, 5), false).unwrap();
+
+        assert_eq!(filled_maker_ids(&result), vec![2]);
+        assert_eq!(result.len(), 1);
+        // The stale ask is back on the book, untouched
+        assert_eq!(asks.len(), 2);
+        assert_eq!(asks.get_best_order().unwrap().id, 1);
+        assert_eq!(asks.get_order_by_id(1).unwrap().sell_quantity, 1);
+    }
+
+    #[test]
+    fn test_market_order_stops_at_makers_above_the_band() {
+        let (base, quote) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let band = PriceBand { base_mint: base, reference_price: REFERENCE_PRICE, band_bps: 1_000 };
+        let mut bids = OrderHeapImpl::new();
+        let mut asks = OrderHeapImpl::new();
+        let mut orderbook = Box::new(UserOrderbook::default());
+        asks.add_order(ask(1, &base, &quote, 1, 120)).unwrap();
+
+        let mut engine = MatchingEngine::new(base, quote, &mut bids, &mut asks, orderbook.as_mut()).with_price_band(band);
+        let result = engine.process_order(market_buy(&base, &quote, 5), false).unwrap();
+
+        assert!(filled_maker_ids(&result).is_empty());
+        assert!(matches!(result[..], [Err(OrderFailure::PriceBandBreached { buy_quantity: 5, .. })]));
+        assert_eq!(asks.len(), 1);
+    }
+}
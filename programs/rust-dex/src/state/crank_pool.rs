@@ -0,0 +1,23 @@
+use anchor_lang::prelude::*;
+
+/// Followed by the market's two mints in ascending order, as `MARKET_EVENTS_SEED`.
+pub const CRANK_POOL_SEED: &[u8] = b"crank_pool";
+
+/// Lamports set aside for keepers settling other users' events on a market.
+#[account]
+pub struct CrankPool {
+    pub base_mint: Pubkey,
+    pub quote_mint: Pubkey,
+    pub reward_per_event: u64, // lamports paid to the keeper for each consumed event
+    pub bump: u8,
+}
+
+impl CrankPool {
+    pub const SIZE: usize = 8 + 32 + 32 + 8 + 1;
+
+    /// Whether this pool belongs to the market of `token_a` and `token_b`, in either order.
+    pub fn is_market(&self, token_a: &Pubkey, token_b: &Pubkey) -> bool {
+        (self.base_mint == *token_a && self.quote_mint == *token_b)
+            || (self.base_mint == *token_b && self.quote_mint == *token_a)
+    }
+}
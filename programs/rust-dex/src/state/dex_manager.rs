@@ -5,6 +5,11 @@ pub const DEX_MANAGER_SEED: &[u8] = b"dex_manager";
 #[account]
 pub struct DexManager {
     pub sequence_number: u64,
+    pub event_sequence: u64,
+    pub admin: Pubkey, // registers markets and vaults, sets their parameters and may close the DEX
+    pub pending_admin: Pubkey, // proposed by the admin, becomes admin once it accepts
+    pub referral_share_bps: u16, // share of referred takers' fees paid to their referrer
+    pub paused: bool, // emergency stop for new orders and swaps on every market
     pub bump: u8,
 }
 
@@ -17,4 +22,11 @@ impl DexManager {
         self.sequence_number = next;
         self.sequence_number
     }
-}
\ No newline at end of file
+
+    /// Numbers order lifecycle events across every market, so indexers can
+    /// order them and detect gaps.
+    pub fn next_event_sequence(&mut self) -> u64 {
+        self.event_sequence = self.event_sequence.wrapping_add(1);
+        self.event_sequence
+    }
+}
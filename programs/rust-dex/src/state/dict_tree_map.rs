@@ -1,4 +1,5 @@
 use anchor_lang::prelude::*;
+use crate::error::ErrorCode;
 
 
 pub trait DictTreeMap {
@@ -13,15 +14,6 @@ pub trait DictTreeMap {
 const MAX_LEAVES: usize = 64;
 const MAX_MIDDLES: usize = 64;
 
-#[error_code]
-pub enum ErrorCode {
-    MaxLeavesReached,
-    MaxMiddlesReached,
-
-    TooLongKey,
-    KeyNotFound,
-}
-
 
 #[zero_copy]
 #[derive(Debug)]
@@ -1,17 +1,14 @@
 use anchor_lang::prelude::*;
-use crate::common::MAX_EVENTS;
+use crate::common::{MAX_EVENTS, MAX_PENDING_EVENTS};
+use crate::error::ErrorCode;
 
 pub const ORDER_EVENTS_SEED: &[u8] = b"order_events";
 
-#[error_code]
-pub enum ErrorCode {
-    EventListFull,
-    EventListAlreadyInUse,
-    EventListClosed,
-}
-
 #[derive(Debug)]
 pub struct Event {
+    pub order_id: u64,
+    pub token_buy: Pubkey,
+    pub token_sell: Pubkey,
     pub oppo_user: Pubkey,
     pub buy_quantity: u64,
     pub sell_quantity: u64,
@@ -19,104 +16,108 @@ pub struct Event {
     pub oppo_order_id: u64,
     pub filled: bool,
     pub oppo_filled: bool,
+    pub fee: u64,
+    pub oppo_fee: u64,
+    pub oppo_rebate: u64,
+    pub referral_fee: u64,
 }
 
+/// FIFO ring buffer of a user's pending settlements. Every entry carries the
+/// order and market it came from, so events of any number of orders can wait
+/// to be consumed side by side. `fee` is charged to the user in `token_buy`
+/// and `oppo_fee` to the counterparty in `token_sell`. `oppo_rebate` is paid
+/// to the counterparty in `token_buy`, out of `fee`, and `referral_fee` to the
+/// user's referrer.
 #[account]
 #[derive(Debug)]
 pub struct EventList {
-    pub oppo_user: [Pubkey; MAX_EVENTS],
-    pub buy_quantity: [u64; MAX_EVENTS],
-    pub sell_quantity: [u64; MAX_EVENTS],
-    pub rollback: [u8; MAX_EVENTS],
-    pub oppo_order_id: [u64; MAX_EVENTS],
-    pub filled: [u8; MAX_EVENTS],
-    pub oppo_filled: [u8; MAX_EVENTS],
+    pub order_id: [u64; MAX_PENDING_EVENTS],
+    pub token_buy: [Pubkey; MAX_PENDING_EVENTS],
+    pub token_sell: [Pubkey; MAX_PENDING_EVENTS],
+    pub oppo_user: [Pubkey; MAX_PENDING_EVENTS],
+    pub buy_quantity: [u64; MAX_PENDING_EVENTS],
+    pub sell_quantity: [u64; MAX_PENDING_EVENTS],
+    pub rollback: [u8; MAX_PENDING_EVENTS],
+    pub oppo_order_id: [u64; MAX_PENDING_EVENTS],
+    pub filled: [u8; MAX_PENDING_EVENTS],
+    pub oppo_filled: [u8; MAX_PENDING_EVENTS],
+    pub fee: [u64; MAX_PENDING_EVENTS],
+    pub oppo_fee: [u64; MAX_PENDING_EVENTS],
+    pub oppo_rebate: [u64; MAX_PENDING_EVENTS],
+    pub referral_fee: [u64; MAX_PENDING_EVENTS],
     pub user: Pubkey,
-    pub token_buy: Pubkey,
-    pub token_sell: Pubkey,
-    pub order_id: u64,
+    pub head: u64,
     pub length: u64,
-    pub in_use: u8,
     pub bump: u8,
 }
 
 impl Default for EventList {
     fn default() -> Self {
         Self {
-            oppo_user: [Pubkey::default(); MAX_EVENTS],
-            buy_quantity: [0; MAX_EVENTS],
-            sell_quantity: [0; MAX_EVENTS],
-            rollback: [0; MAX_EVENTS],
-            oppo_order_id: [0; MAX_EVENTS],
-            filled: [0; MAX_EVENTS],
-            oppo_filled: [0; MAX_EVENTS],
+            order_id: [0; MAX_PENDING_EVENTS],
+            token_buy: [Pubkey::default(); MAX_PENDING_EVENTS],
+            token_sell: [Pubkey::default(); MAX_PENDING_EVENTS],
+            oppo_user: [Pubkey::default(); MAX_PENDING_EVENTS],
+            buy_quantity: [0; MAX_PENDING_EVENTS],
+            sell_quantity: [0; MAX_PENDING_EVENTS],
+            rollback: [0; MAX_PENDING_EVENTS],
+            oppo_order_id: [0; MAX_PENDING_EVENTS],
+            filled: [0; MAX_PENDING_EVENTS],
+            oppo_filled: [0; MAX_PENDING_EVENTS],
+            fee: [0; MAX_PENDING_EVENTS],
+            oppo_fee: [0; MAX_PENDING_EVENTS],
+            oppo_rebate: [0; MAX_PENDING_EVENTS],
+            referral_fee: [0; MAX_PENDING_EVENTS],
             user: Pubkey::default(),
-            token_buy: Pubkey::default(),
-            token_sell: Pubkey::default(),
-            order_id: 0,
+            head: 0,
             length: 0,
-            in_use: 0,
             bump: 0,
         }
     }
 }
 
 impl EventList {
-    pub fn init(&mut self, user: Pubkey, token_buy: Pubkey, token_sell: Pubkey, order_id: u64) {
-        self.user = user;
-        self.token_buy = token_buy;
-        self.token_sell = token_sell;
-        self.order_id = order_id;
-        self.length = 0;
-        self.in_use = 0; // Mark as not in use
-        self.oppo_user = [Pubkey::default(); MAX_EVENTS];
-        self.buy_quantity = [0; MAX_EVENTS];
-        self.sell_quantity = [0; MAX_EVENTS];
-        self.rollback = [0; MAX_EVENTS];
-        self.oppo_order_id = [0; MAX_EVENTS];
-        self.filled = [0; MAX_EVENTS];
-        self.oppo_filled = [0; MAX_EVENTS];
-    }
+    /// Account size `register_user` allocates, the most a single instruction may create.
+    pub const SPACE: usize = 10240;
 
-    pub fn close(&mut self) {
+    pub fn init(&mut self, user: Pubkey) {
+        self.user = user;
+        self.head = 0;
         self.length = 0;
-        self.in_use = 0;
     }
 
-    pub fn is_closed(&self) -> bool {
-        self.in_use == 0
-    }
-
-    pub fn open(&mut self, user: Pubkey, token_buy: Pubkey, token_sell: Pubkey, order_id: u64) -> Result<()> {
-        if self.in_use != 0 {
-            return Err(ErrorCode::EventListAlreadyInUse.into());
+    /// Fails unless the list can take every event a single order may produce.
+    pub fn reserve_order(&self) -> Result<()> {
+        if MAX_PENDING_EVENTS - (self.length as usize) < MAX_EVENTS {
+            return Err(ErrorCode::EventListFull.into());
         }
-        self.user = user;
-        self.token_buy = token_buy;
-        self.token_sell = token_sell;
-        self.order_id = order_id;
-        self.length = 0;
-        self.in_use = 1; // Mark as in use
         Ok(())
     }
 
-    pub fn add_event(&mut self, 
-        oppo_user: Pubkey, 
-        buy_quantity: u64, 
-        sell_quantity: u64, 
-        rollback: u8, 
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_event(&mut self,
+        order_id: u64,
+        token_buy: Pubkey,
+        token_sell: Pubkey,
+        oppo_user: Pubkey,
+        buy_quantity: u64,
+        sell_quantity: u64,
+        rollback: u8,
         oppo_order_id: u64,
         oppo_filled: u8,
         filled: u8,
-
+        fee: u64,
+        oppo_fee: u64,
+        oppo_rebate: u64,
+        referral_fee: u64,
     ) -> Result<()> {
-        if self.in_use == 0 {
-            return Err(ErrorCode::EventListClosed.into());
-        }
-        if self.length as usize >= MAX_EVENTS {
+        if self.is_full() {
             return Err(ErrorCode::EventListFull.into());
         }
-        let idx = self.length as usize;
+        let idx = self.slot(self.length as usize);
+        self.order_id[idx] = order_id;
+        self.token_buy[idx] = token_buy;
+        self.token_sell[idx] = token_sell;
         self.oppo_user[idx] = oppo_user;
         self.buy_quantity[idx] = buy_quantity;
         self.sell_quantity[idx] = sell_quantity;
@@ -124,6 +125,10 @@ impl EventList {
         self.filled[idx] = filled;
         self.oppo_filled[idx] = oppo_filled;
         self.oppo_order_id[idx] = oppo_order_id;
+        self.fee[idx] = fee;
+        self.oppo_fee[idx] = oppo_fee;
+        self.oppo_rebate[idx] = oppo_rebate;
+        self.referral_fee[idx] = referral_fee;
         self.length += 1;
         Ok(())
     }
@@ -131,25 +136,57 @@ impl EventList {
     pub fn length(&self) -> u64 {
         self.length
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
     pub fn is_full(&self) -> bool {
-        self.length as usize >= MAX_EVENTS
+        self.length as usize >= MAX_PENDING_EVENTS
+    }
+
+    fn slot(&self, index: usize) -> usize {
+        (self.head as usize + index) % MAX_PENDING_EVENTS
+    }
+
+    /// Token bought by the order of the oldest pending event.
+    pub fn next_token_buy(&self) -> &Pubkey {
+        &self.token_buy[self.head as usize]
+    }
+
+    /// Token sold by the order of the oldest pending event.
+    pub fn next_token_sell(&self) -> &Pubkey {
+        &self.token_sell[self.head as usize]
+    }
+
+    /// Whether the oldest pending event is a fill against another user, the
+    /// only kind of event that moves a counterparty's funds.
+    pub fn next_is_counterparty_fill(&self) -> bool {
+        let idx = self.head as usize;
+        self.rollback[idx] == 0 && self.oppo_user[idx] != self.user
     }
 
     pub fn at(&self, index: usize) -> Option<(Pubkey, u64, u64)> {
-        if index >= self.length as usize || self.is_closed() {
+        if index >= self.length as usize {
             return None;
         }
-        Some((self.oppo_user[index], self.buy_quantity[index], self.sell_quantity[index]))
+        let idx = self.slot(index);
+        Some((self.oppo_user[idx], self.buy_quantity[idx], self.sell_quantity[idx]))
     }
-    
+
+    /// Removes and returns the oldest pending event.
     pub fn pop(&mut self) -> Option<Event> {
         if self.length == 0 {
             return None;
         }
-        let idx = (self.length - 1) as usize;
+        let idx = self.head as usize;
+        self.head = self.slot(1) as u64;
         self.length -= 1;
-        
+
         Some(Event {
+            order_id: self.order_id[idx],
+            token_buy: self.token_buy[idx],
+            token_sell: self.token_sell[idx],
             oppo_user: self.oppo_user[idx],
             buy_quantity: self.buy_quantity[idx],
             sell_quantity: self.sell_quantity[idx],
@@ -157,6 +194,55 @@ impl EventList {
             oppo_order_id: self.oppo_order_id[idx],
             filled: self.filled[idx] == 1,
             oppo_filled: self.oppo_filled[idx] == 1,
+            fee: self.fee[idx],
+            oppo_fee: self.oppo_fee[idx],
+            oppo_rebate: self.oppo_rebate[idx],
+            referral_fee: self.referral_fee[idx],
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn add(list: &mut EventList, order_id: u64) -> Result<()> {
+        list.add_event(order_id, Pubkey::default(), Pubkey::default(), Pubkey::default(), 1, 1, 0, 0, 0, 0, 0, 0, 0, 0)
+    }
+
+    #[test]
+    fn test_pop_is_fifo_across_wraparound() {
+        let mut list = Box::new(EventList::default());
+        for order_id in 0..MAX_PENDING_EVENTS as u64 {
+            add(&mut list, order_id).unwrap();
+        }
+        assert!(add(&mut list, 99).is_err());
+
+        assert_eq!(list.pop().unwrap().order_id, 0);
+        assert_eq!(list.pop().unwrap().order_id, 1);
+        add(&mut list, 100).unwrap();
+        for order_id in 2..MAX_PENDING_EVENTS as u64 {
+            assert_eq!(list.pop().unwrap().order_id, order_id);
+        }
+        assert_eq!(list.pop().unwrap().order_id, 100);
+        assert!(list.pop().is_none());
+    }
+
+    #[test]
+    fn test_full_list_fits_its_account() {
+        let mut data = Vec::new();
+        EventList::default().serialize(&mut data).unwrap();
+        assert!(8 + data.len() <= EventList::SPACE);
+    }
+
+    #[test]
+    fn test_reserve_order_needs_room_for_a_full_order() {
+        let mut list = Box::new(EventList::default());
+        for order_id in 0..(MAX_PENDING_EVENTS - MAX_EVENTS) as u64 {
+            add(&mut list, order_id).unwrap();
+        }
+        assert!(list.reserve_order().is_ok());
+        add(&mut list, 99).unwrap();
+        assert!(list.reserve_order().is_err());
+    }
+}
@@ -0,0 +1,25 @@
+use anchor_lang::prelude::*;
+use crate::error::ErrorCode;
+
+pub const FEE_LEDGER_SEED: &[u8] = b"fee_ledger";
+
+/// Trading fees charged in one mint. The fees stay in the mint's vault and in
+/// its `total_balance` until the admin collects them.
+#[account]
+pub struct FeeLedgerAccount {
+    pub mint_account: Pubkey,
+    pub accrued_fees: u64,
+    pub collected_fees: u64,
+    pub bump: u8,
+}
+
+impl FeeLedgerAccount {
+    pub const SIZE: usize = 8 + 32 + 8 + 8 + 1;
+
+    pub fn accrue(&mut self, fee: u64) -> Result<()> {
+        self.accrued_fees = self.accrued_fees
+            .checked_add(fee)
+            .ok_or(ErrorCode::SettlementOverflow)?;
+        Ok(())
+    }
+}
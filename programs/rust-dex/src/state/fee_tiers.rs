@@ -0,0 +1,84 @@
+use anchor_lang::prelude::*;
+use crate::common::{FeeDiscount, MAX_FEE_BPS, MAX_FEE_TIERS};
+use crate::error::ErrorCode;
+
+pub const FEE_TIERS_SEED: &[u8] = b"fee_tiers";
+
+/// Discounts off the base fee rates for users whose trailing volume reached
+/// `min_volume`. A maker discount above a market's maker fee is paid as a rebate.
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub struct FeeTier {
+    pub min_volume: u64,
+    pub maker_discount_bps: u16,
+    pub taker_discount_bps: u16,
+}
+
+/// Fee tiers of every market quoted in `quote_mint`, keyed on the trailing
+/// volume of the user's `quote_mint` token ledger. The first `tier_count`
+/// tiers are sorted by strictly increasing `min_volume`.
+#[account]
+pub struct FeeTierTable {
+    pub quote_mint: Pubkey,
+    pub tiers: [FeeTier; MAX_FEE_TIERS],
+    pub tier_count: u8,
+    pub bump: u8,
+}
+
+impl FeeTierTable {
+    pub const SIZE: usize = 8 + 32 + MAX_FEE_TIERS * (8 + 2 + 2) + 1 + 1;
+
+    pub fn set_tiers(&mut self, tiers: &[FeeTier]) -> Result<()> {
+        if tiers.len() > MAX_FEE_TIERS {
+            return Err(ErrorCode::InvalidFeeTiers.into());
+        }
+        let sorted = tiers.windows(2).all(|pair| pair[0].min_volume < pair[1].min_volume);
+        let bounded = tiers.iter().all(|tier| tier.maker_discount_bps <= MAX_FEE_BPS && tier.taker_discount_bps <= MAX_FEE_BPS);
+        if !sorted || !bounded {
+            return Err(ErrorCode::InvalidFeeTiers.into());
+        }
+        self.tiers = [FeeTier::default(); MAX_FEE_TIERS];
+        self.tiers[..tiers.len()].copy_from_slice(tiers);
+        self.tier_count = tiers.len() as u8;
+        Ok(())
+    }
+
+    /// Discount of the highest tier `volume` reaches, none below the first tier.
+    pub fn discount_for(&self, volume: u64) -> FeeDiscount {
+        self.tiers[..self.tier_count as usize]
+            .iter()
+            .rev()
+            .find(|tier| tier.min_volume <= volume)
+            .map(|tier| FeeDiscount { maker_bps: tier.maker_discount_bps, taker_bps: tier.taker_discount_bps })
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tier(min_volume: u64, maker_discount_bps: u16, taker_discount_bps: u16) -> FeeTier {
+        FeeTier { min_volume, maker_discount_bps, taker_discount_bps }
+    }
+
+    #[test]
+    fn test_discount_for_picks_highest_reached_tier() {
+        let mut table = FeeTierTable {
+            quote_mint: Pubkey::default(),
+            tiers: [FeeTier::default(); MAX_FEE_TIERS],
+            tier_count: 0,
+            bump: 0,
+        };
+        assert_eq!(table.discount_for(u64::MAX), FeeDiscount::default());
+
+        table.set_tiers(&[tier(1_000, 2, 5), tier(10_000, 15, 10)]).unwrap();
+        assert_eq!(table.discount_for(999), FeeDiscount::default());
+        assert_eq!(table.discount_for(1_000), FeeDiscount { maker_bps: 2, taker_bps: 5 });
+        assert_eq!(table.discount_for(50_000), FeeDiscount { maker_bps: 15, taker_bps: 10 });
+
+        assert!(table.set_tiers(&[tier(10_000, 0, 0), tier(10_000, 1, 1)]).is_err());
+        assert!(table.set_tiers(&[tier(0, MAX_FEE_BPS + 1, 0)]).is_err());
+        assert!(table.set_tiers(&[tier(0, 0, 0); MAX_FEE_TIERS + 1]).is_err());
+        assert_eq!(table.tier_count, 2);
+    }
+}
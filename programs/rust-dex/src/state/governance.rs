@@ -0,0 +1,188 @@
+use anchor_lang::prelude::*;
+use crate::common::{MAX_GOVERNANCE_SIGNERS, MAX_PROPOSAL_ACCOUNTS, MAX_PROPOSAL_DATA};
+use crate::error::ErrorCode;
+
+pub const GOVERNANCE_SEED: &[u8] = b"governance";
+pub const GOVERNANCE_AUTHORITY_SEED: &[u8] = b"governance_authority";
+pub const GOVERNANCE_PROPOSAL_SEED: &[u8] = b"governance_proposal";
+
+/// The M-of-N multisig the DEX admin authority is handed to. Once it is, the
+/// admin is the data-less `governance_authority` PDA, which only signs, and
+/// pays rent, for proposals that reached `threshold` approvals and waited
+/// out `timelock_secs`.
+#[account]
+pub struct GovernanceConfig {
+    pub signers: [Pubkey; MAX_GOVERNANCE_SIGNERS],
+    pub signer_count: u8,
+    pub threshold: u8,
+    pub timelock_secs: i64,
+    pub proposal_count: u64,
+    pub generation: u32, // bumped on every reconfiguration, voiding older proposals
+    pub authority_bump: u8,
+    pub bump: u8,
+}
+
+impl GovernanceConfig {
+    pub const SIZE: usize = 8 + 32 * MAX_GOVERNANCE_SIGNERS + 1 + 1 + 8 + 8 + 4 + 1 + 1;
+
+    pub fn configure(&mut self, signers: &[Pubkey], threshold: u8, timelock_secs: i64) -> Result<()> {
+        let distinct = signers.iter().enumerate().all(|(i, signer)| !signers[..i].contains(signer));
+        if signers.is_empty()
+            || signers.len() > MAX_GOVERNANCE_SIGNERS
+            || !distinct
+            || threshold == 0
+            || threshold as usize > signers.len()
+            || timelock_secs < 0
+        {
+            return Err(ErrorCode::InvalidGovernanceConfig.into());
+        }
+        self.signers = [Pubkey::default(); MAX_GOVERNANCE_SIGNERS];
+        self.signers[..signers.len()].copy_from_slice(signers);
+        self.signer_count = signers.len() as u8;
+        self.threshold = threshold;
+        self.timelock_secs = timelock_secs;
+        self.generation = self.generation.wrapping_add(1);
+        Ok(())
+    }
+
+    pub fn signer_index(&self, key: &Pubkey) -> Result<usize> {
+        self.signers[..self.signer_count as usize]
+            .iter()
+            .position(|signer| signer == key)
+            .ok_or(ErrorCode::NotGovernanceSigner.into())
+    }
+}
+
+/// One account of the instruction a proposal executes.
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, Clone, Copy, Eq, PartialEq)]
+pub struct ProposalAccountMeta {
+    pub pubkey: Pubkey,
+    pub is_signer: bool,
+    pub is_writable: bool,
+}
+
+/// An instruction of this program waiting for governance approval. Bit `i`
+/// of `approvals` is set once signer `i` approved. `approved_at` is when the
+/// threshold was reached, 0 before, and the timelock runs from it.
+#[account]
+pub struct GovernanceProposal {
+    pub id: u64,
+    pub generation: u32,
+    pub proposer: Pubkey,
+    pub approvals: u16,
+    pub approval_count: u8,
+    pub created_at: i64,
+    pub approved_at: i64,
+    pub executed: bool,
+    pub accounts: Vec<ProposalAccountMeta>,
+    pub data: Vec<u8>,
+    pub bump: u8,
+}
+
+impl GovernanceProposal {
+    pub fn size(accounts: &[ProposalAccountMeta], data: &[u8]) -> usize {
+        8 + 8 + 4 + 32 + 2 + 1 + 8 + 8 + 1 + 4 + accounts.len() * (32 + 1 + 1) + 4 + data.len() + 1
+    }
+
+    pub fn check_instruction(accounts: &[ProposalAccountMeta], data: &[u8]) -> Result<()> {
+        if accounts.len() > MAX_PROPOSAL_ACCOUNTS || data.len() < 8 || data.len() > MAX_PROPOSAL_DATA {
+            return Err(ErrorCode::InvalidProposal.into());
+        }
+        Ok(())
+    }
+
+    /// Records `index`'s approval, starting the timelock once `threshold` is reached.
+    pub fn approve(&mut self, index: usize, threshold: u8, now: i64) -> Result<()> {
+        let bit = 1u16 << index;
+        if self.approvals & bit != 0 {
+            return Err(ErrorCode::ProposalAlreadyApproved.into());
+        }
+        self.approvals |= bit;
+        self.approval_count += 1;
+        if self.approval_count == threshold {
+            self.approved_at = now;
+        }
+        Ok(())
+    }
+
+    pub fn check_executable(&self, config: &GovernanceConfig, now: i64) -> Result<()> {
+        if self.executed {
+            return Err(ErrorCode::ProposalAlreadyExecuted.into());
+        }
+        if self.generation != config.generation {
+            return Err(ErrorCode::StaleProposal.into());
+        }
+        if self.approval_count < config.threshold {
+            return Err(ErrorCode::ProposalNotApproved.into());
+        }
+        let unlocks_at = self.approved_at.saturating_add(config.timelock_secs);
+        if now < unlocks_at {
+            return Err(ErrorCode::TimelockNotElapsed.into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_config(signers: &[Pubkey], threshold: u8, timelock_secs: i64) -> GovernanceConfig {
+        let mut config = GovernanceConfig {
+            signers: [Pubkey::default(); MAX_GOVERNANCE_SIGNERS],
+            signer_count: 0,
+            threshold: 0,
+            timelock_secs: 0,
+            proposal_count: 0,
+            generation: 0,
+            authority_bump: 0,
+            bump: 0,
+        };
+        config.configure(signers, threshold, timelock_secs).unwrap();
+        config
+    }
+
+    #[test]
+    fn test_proposal_needs_threshold_and_timelock() {
+        let signers = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+        let config = new_config(&signers, 2, 100);
+        let mut proposal = GovernanceProposal {
+            id: 0,
+            generation: config.generation,
+            proposer: signers[0],
+            approvals: 0,
+            approval_count: 0,
+            created_at: 1_000,
+            approved_at: 0,
+            executed: false,
+            accounts: vec![],
+            data: vec![0; 8],
+            bump: 0,
+        };
+
+        proposal.approve(0, config.threshold, 1_000).unwrap();
+        assert!(proposal.approve(0, config.threshold, 1_000).is_err());
+        assert!(proposal.check_executable(&config, 5_000).is_err());
+
+        proposal.approve(2, config.threshold, 1_050).unwrap();
+        assert_eq!(proposal.approved_at, 1_050);
+        assert!(proposal.check_executable(&config, 1_149).is_err());
+        assert!(proposal.check_executable(&config, 1_150).is_ok());
+
+        let mut reconfigured = new_config(&signers, 2, 100);
+        reconfigured.configure(&signers[..2], 1, 0).unwrap();
+        assert!(proposal.check_executable(&reconfigured, 1_150).is_err());
+    }
+
+    #[test]
+    fn test_configure_rejects_unreachable_thresholds() {
+        let mut governance = new_config(&[Pubkey::new_unique()], 1, 0);
+        let signer = Pubkey::new_unique();
+        assert!(governance.configure(&[], 1, 0).is_err());
+        assert!(governance.configure(&[signer], 2, 0).is_err());
+        assert!(governance.configure(&[signer], 0, 0).is_err());
+        assert!(governance.configure(&[signer, signer], 1, 0).is_err());
+        assert!(governance.configure(&[signer], 1, -1).is_err());
+        assert!(governance.signer_index(&signer).is_err());
+    }
+}
@@ -1,31 +1,28 @@
 use anchor_lang::prelude::*;
 use crate::common::MAX_TOKEN_MINTS;
 use crate::common::USER_ORDERBOOK_CAP;
+use crate::common::VOLUME_WINDOW_SECS;
+use crate::error::ErrorCode;
 
 pub const INDIVIDUAL_LEDGER_SEED: &[u8] = b"user_ledger";
 pub const USER_ORDERBOOK_SEED: &[u8] = b"user_orderbook";
 pub const INDIVIDUAL_TOKEN_LEDGER_SEED: &[u8] = b"individual_token_ledger";
 
-#[error_code]
-pub enum ErrorCode {
-    MaxTokensReached,
-    OrderNotFound,
-
-    OrderbookCapacityReached,
-}
-
+/// `referrer` is the user who referred this one, `Pubkey::default()` if none.
 #[account]
 pub struct IndividualLedgerAccount {
     pub tokens: [Pubkey; MAX_TOKEN_MINTS],
     pub next_index: u16,
     pub bitmap: [u8; MAX_TOKEN_MINTS],
     pub bump: u8,
+    pub referrer: Pubkey,
 }
 
 impl IndividualLedgerAccount {
     pub fn init(&mut self) {
         self.next_index = 0;
         self.bump = 0; // Set the bump to 0 initially
+        self.referrer = Pubkey::default();
         for i in 0..MAX_TOKEN_MINTS {
             self.tokens[i] = Pubkey::default();
             self.bitmap[i] = 0;
@@ -41,6 +38,25 @@ impl IndividualLedgerAccount {
         self.next_index += 1;
         Ok(())
     }
+
+    /// Forgets `token` once the user's ledger of it is closed.
+    pub fn remove_token(&mut self, token: &Pubkey) {
+        let len = self.next_index as usize;
+        let Some(index) = self.tokens[..len].iter().position(|t| t == token) else {
+            return;
+        };
+        for i in index..len - 1 {
+            self.tokens[i] = self.tokens[i + 1];
+            self.bitmap[i] = self.bitmap[i + 1];
+        }
+        self.tokens[len - 1] = Pubkey::default();
+        self.bitmap[len - 1] = 0;
+        self.next_index -= 1;
+    }
+
+    pub fn registered_tokens(&self) -> &[Pubkey] {
+        &self.tokens[..self.next_index as usize]
+    }
 }
 
 #[account]
@@ -115,11 +131,128 @@ impl UserOrderbook {
     }
 }
 
+/// Besides the balances, a token ledger keeps the amount of its mint the user
+/// traded in the current and the previous `VOLUME_WINDOW_SECS` window, which
+/// together make up the trailing volume fee tiers are picked by. Windows are
+/// aligned to multiples of `VOLUME_WINDOW_SECS` since the Unix epoch. The
+/// user's `referrer` is copied from its `IndividualLedgerAccount`, and
+/// `referral_earnings` totals what referred takers paid it in this mint.
 #[account]
 pub struct IndividualTokenLedgerAccount {
     pub available_balance: u64,
     pub locked_balance: u64,
     pub mint_account: Pubkey,
     pub user_token_account: Pubkey,
+    pub owner: Pubkey,
     pub bump: u8,
-}
\ No newline at end of file
+    pub volume_window_start: i64,
+    pub window_volume: u64,
+    pub prior_window_volume: u64,
+    pub referrer: Pubkey,
+    pub referral_earnings: u64,
+}
+
+impl IndividualTokenLedgerAccount {
+    pub const SIZE: usize = 8 + 8 + 8 + 32 + 32 + 32 + 1 + 8 + 8 + 8 + 32 + 8;
+
+    /// Volume traded over the current and the previous window as of `now`.
+    pub fn trailing_volume(&self, now: i64) -> u64 {
+        let elapsed = now.saturating_sub(self.volume_window_start);
+        if elapsed < VOLUME_WINDOW_SECS {
+            self.window_volume.saturating_add(self.prior_window_volume)
+        } else if elapsed < 2 * VOLUME_WINDOW_SECS {
+            self.window_volume
+        } else {
+            0
+        }
+    }
+
+    /// Pays a referral fee out of a referred taker's fee into this ledger.
+    pub fn credit_referral(&mut self, referral_fee: u64) -> Result<()> {
+        self.available_balance = self.available_balance
+            .checked_add(referral_fee)
+            .ok_or(ErrorCode::SettlementOverflow)?;
+        self.referral_earnings = self.referral_earnings
+            .checked_add(referral_fee)
+            .ok_or(ErrorCode::SettlementOverflow)?;
+        Ok(())
+    }
+
+    pub fn record_volume(&mut self, amount: u64, now: i64) {
+        let elapsed = now.saturating_sub(self.volume_window_start);
+        if elapsed >= VOLUME_WINDOW_SECS {
+            self.prior_window_volume = if elapsed < 2 * VOLUME_WINDOW_SECS { self.window_volume } else { 0 };
+            self.window_volume = 0;
+            self.volume_window_start = now - elapsed % VOLUME_WINDOW_SECS;
+        }
+        self.window_volume = self.window_volume.saturating_add(amount);
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ledger() -> IndividualTokenLedgerAccount {
+        IndividualTokenLedgerAccount {
+            available_balance: 0,
+            locked_balance: 0,
+            mint_account: Pubkey::default(),
+            user_token_account: Pubkey::default(),
+            owner: Pubkey::default(),
+            bump: 0,
+            volume_window_start: 0,
+            window_volume: 0,
+            prior_window_volume: 0,
+            referrer: Pubkey::default(),
+            referral_earnings: 0,
+        }
+    }
+
+    #[test]
+    fn test_trailing_volume_rolls_over_windows() {
+        let start = 600 * VOLUME_WINDOW_SECS;
+        let mut ledger = ledger();
+        ledger.record_volume(100, start);
+        ledger.record_volume(50, start + 10);
+        assert_eq!(ledger.volume_window_start, start);
+        assert_eq!(ledger.trailing_volume(start + 10), 150);
+
+        ledger.record_volume(30, start + VOLUME_WINDOW_SECS + 5);
+        assert_eq!(ledger.trailing_volume(start + VOLUME_WINDOW_SECS + 5), 180);
+        assert_eq!(ledger.trailing_volume(start + 2 * VOLUME_WINDOW_SECS + 5), 30);
+        assert_eq!(ledger.trailing_volume(start + 3 * VOLUME_WINDOW_SECS + 5), 0);
+
+        ledger.record_volume(7, start + 4 * VOLUME_WINDOW_SECS);
+        assert_eq!(ledger.trailing_volume(start + 4 * VOLUME_WINDOW_SECS), 7);
+        assert_eq!(ledger.volume_window_start, start + 4 * VOLUME_WINDOW_SECS);
+    }
+
+    #[test]
+    fn test_token_ledger_fills_its_account() {
+        let mut data = Vec::new();
+        ledger().serialize(&mut data).unwrap();
+        assert_eq!(8 + data.len(), IndividualTokenLedgerAccount::SIZE);
+    }
+
+    #[test]
+    fn test_remove_token_keeps_the_others() {
+        let mut ledger = IndividualLedgerAccount {
+            tokens: [Pubkey::default(); MAX_TOKEN_MINTS],
+            next_index: 0,
+            bitmap: [0; MAX_TOKEN_MINTS],
+            bump: 0,
+            referrer: Pubkey::default(),
+        };
+        let tokens = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+        for token in tokens {
+            ledger.add_token(token).unwrap();
+        }
+        ledger.remove_token(&tokens[0]);
+        ledger.remove_token(&Pubkey::new_unique());
+        assert_eq!(ledger.registered_tokens(), &tokens[1..]);
+        assert_eq!(ledger.bitmap[2], 0);
+        ledger.remove_token(&tokens[2]);
+        ledger.remove_token(&tokens[1]);
+        assert!(ledger.registered_tokens().is_empty());
+    }
+}
@@ -0,0 +1,151 @@
+use anchor_lang::prelude::*;
+use crate::common::{FeeRates, MarketStatus, PriceBand};
+
+/// Followed by the market's two mints in ascending order, as `MARKET_EVENTS_SEED`.
+pub const MARKET_SEED: &[u8] = b"market";
+
+/// Configuration and trading state of a registered pair: fee rates, status,
+/// price band and circuit breaker, and the fills still waiting to settle.
+#[account]
+pub struct Market {
+    pub base_mint: Pubkey,
+    pub quote_mint: Pubkey,
+    pub maker_fee_bps: u16,
+    pub taker_fee_bps: u16,
+    pub status: u8, // a `MarketStatus`
+    pub band_bps: u16, // 0 when the market has no price band
+    pub reference_price: f64, // quote per base of the last fill, unless the admin set one since
+    pub halt_cooldown_secs: i64,
+    pub halted_until: i64, // the circuit breaker keeps new orders out until then
+    pub pending_fills: u64, // fills waiting in users' event lists, the market cannot close before they settle
+    pub bump: u8,
+}
+
+impl Market {
+    pub const SIZE: usize = 8 + 32 + 32 + 2 + 2 + 1 + 2 + 8 + 8 + 8 + 8 + 1;
+
+    pub fn init(&mut self, base_mint: Pubkey, quote_mint: Pubkey, bump: u8) {
+        self.base_mint = base_mint;
+        self.quote_mint = quote_mint;
+        self.maker_fee_bps = 0;
+        self.taker_fee_bps = 0;
+        self.status = MarketStatus::Active as u8;
+        self.band_bps = 0;
+        self.reference_price = 0.0;
+        self.halt_cooldown_secs = 0;
+        self.halted_until = 0;
+        self.pending_fills = 0;
+        self.bump = bump;
+    }
+
+    pub fn status(&self) -> MarketStatus {
+        MarketStatus::from_u8(self.status)
+    }
+
+    pub fn set_status(&mut self, status: MarketStatus) {
+        self.status = status as u8;
+    }
+
+    pub fn fee_rates(&self) -> FeeRates {
+        FeeRates {
+            maker_fee_bps: self.maker_fee_bps,
+            taker_fee_bps: self.taker_fee_bps,
+        }
+    }
+
+    pub fn set_fee_rates(&mut self, rates: FeeRates) {
+        self.maker_fee_bps = rates.maker_fee_bps;
+        self.taker_fee_bps = rates.taker_fee_bps;
+    }
+
+    pub fn price_band(&self) -> PriceBand {
+        PriceBand {
+            base_mint: self.base_mint,
+            reference_price: self.reference_price,
+            band_bps: self.band_bps,
+        }
+    }
+
+    pub fn set_price_band(&mut self, band_bps: u16, halt_cooldown_secs: i64) {
+        self.band_bps = band_bps;
+        self.halt_cooldown_secs = halt_cooldown_secs;
+    }
+
+    /// Keeps new orders out for the cooldown, after a fill priced outside the band.
+    pub fn trip_circuit_breaker(&mut self, now: i64) {
+        self.halted_until = now.saturating_add(self.halt_cooldown_secs);
+    }
+
+    pub fn circuit_breaker_tripped(&self, now: i64) -> bool {
+        now < self.halted_until
+    }
+
+    /// Counts `fills` queued in a taker's event list for `consume_events`.
+    pub fn queue_fills(&mut self, fills: u64) {
+        self.pending_fills = self.pending_fills.saturating_add(fills);
+    }
+
+    pub fn settle_fill(&mut self) {
+        self.pending_fills = self.pending_fills.saturating_sub(1);
+    }
+
+    /// Whether this is the market of `token_a` and `token_b`, in either order.
+    pub fn is_market(&self, token_a: &Pubkey, token_b: &Pubkey) -> bool {
+        (self.base_mint == *token_a && self.quote_mint == *token_b)
+            || (self.base_mint == *token_b && self.quote_mint == *token_a)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_market() -> Market {
+        let mut market = Market {
+            base_mint: Pubkey::default(),
+            quote_mint: Pubkey::default(),
+            maker_fee_bps: 0,
+            taker_fee_bps: 0,
+            status: 0,
+            band_bps: 0,
+            reference_price: 0.0,
+            halt_cooldown_secs: 0,
+            halted_until: 0,
+            pending_fills: 0,
+            bump: 0,
+        };
+        market.init(Pubkey::new_unique(), Pubkey::new_unique(), 0);
+        market
+    }
+
+    #[test]
+    fn test_status_round_trips() {
+        let mut market = new_market();
+        assert_eq!(market.status(), MarketStatus::Active);
+        for status in [MarketStatus::CancelOnly, MarketStatus::PostOnly, MarketStatus::Halted, MarketStatus::Delisted, MarketStatus::Active] {
+            market.set_status(status);
+            assert_eq!(market.status(), status);
+        }
+        // Unknown values fail closed
+        market.status = 9;
+        assert_eq!(market.status(), MarketStatus::Halted);
+    }
+
+    #[test]
+    fn test_pending_fills_never_underflow() {
+        let mut market = new_market();
+        market.queue_fills(2);
+        market.settle_fill();
+        assert_eq!(market.pending_fills, 1);
+        market.settle_fill();
+        market.settle_fill();
+        assert_eq!(market.pending_fills, 0);
+    }
+
+    #[test]
+    fn test_is_market_either_way_round() {
+        let market = new_market();
+        assert!(market.is_market(&market.quote_mint, &market.base_mint));
+        assert!(!market.is_market(&market.base_mint, &Pubkey::new_unique()));
+    }
+}
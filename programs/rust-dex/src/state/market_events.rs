@@ -0,0 +1,217 @@
+use anchor_lang::prelude::*;
+use crate::common::MAX_MARKET_EVENTS;
+
+/// Followed by the market's two mints in ascending order, so that orders in
+/// either orientation of the pair find the same log.
+pub const MARKET_EVENTS_SEED: &[u8] = b"market_events";
+
+pub const MARKET_EVENT_FILL: u8 = 0;
+pub const MARKET_EVENT_OUT: u8 = 1;
+
+pub const TAKER_SIDE_BUY: u8 = 0;
+pub const TAKER_SIDE_SELL: u8 = 1;
+
+/// One entry of a market's event log.
+///
+/// A fill records both counterparties, with quantities in base/quote terms so
+/// makers and takers read them the same way. An out event records an order
+/// leaving the market without a fill: `maker` is the order owner and the
+/// quantities are what it still had open.
+#[zero_copy]
+#[derive(Debug, Default)]
+pub struct MarketEvent {
+    pub seq_num: u64,
+    pub maker_order_id: u64,
+    pub taker_order_id: u64,
+    pub base_quantity: u64,
+    pub quote_quantity: u64,
+    pub price: f64,
+    pub timestamp: i64,
+    pub maker: Pubkey,
+    pub taker: Pubkey,
+    pub event_type: u8,
+    pub taker_side: u8,
+    pub maker_out: u8, // 1 when the fill completed the maker order
+    pub pad: [u8; 5],
+}
+
+/// Ring buffer of the latest `MAX_MARKET_EVENTS` fills and outs of a market,
+/// kept for off-chain readers such as market data feeds. Settlement does not
+/// read it, fills settle through the takers' `EventList`s. When full, the
+/// oldest event is overwritten, readers detect gaps through `seq_num`.
+#[account(zero_copy)]
+pub struct MarketEventLog {
+    pub base_mint: Pubkey,
+    pub quote_mint: Pubkey,
+    pub events: [MarketEvent; MAX_MARKET_EVENTS],
+    pub head: u64,
+    pub count: u64,
+    pub seq_num: u64,
+    pub bump: u8,
+    pub pad: [u8; 7],
+}
+
+impl MarketEventLog {
+    pub fn init(&mut self, base_mint: Pubkey, quote_mint: Pubkey, bump: u8) {
+        self.base_mint = base_mint;
+        self.quote_mint = quote_mint;
+        self.head = 0;
+        self.count = 0;
+        self.seq_num = 0;
+        self.bump = bump;
+    }
+
+    /// Whether this log belongs to the market of `token_a` and `token_b`, in either order.
+    pub fn is_market(&self, token_a: &Pubkey, token_b: &Pubkey) -> bool {
+        (self.base_mint == *token_a && self.quote_mint == *token_b)
+            || (self.base_mint == *token_b && self.quote_mint == *token_a)
+    }
+
+    pub fn len(&self) -> usize {
+        self.count as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// The `index`-th oldest event still in the log.
+    pub fn at(&self, index: usize) -> Option<&MarketEvent> {
+        if index >= self.len() {
+            return None;
+        }
+        Some(&self.events[(self.head as usize + index) % MAX_MARKET_EVENTS])
+    }
+
+    pub fn push(&mut self, mut event: MarketEvent) -> u64 {
+        event.seq_num = self.seq_num;
+        self.seq_num = self.seq_num.wrapping_add(1);
+        let tail = (self.head as usize + self.len()) % MAX_MARKET_EVENTS;
+        self.events[tail] = event;
+        if self.len() == MAX_MARKET_EVENTS {
+            self.head = ((self.head as usize + 1) % MAX_MARKET_EVENTS) as u64;
+        } else {
+            self.count += 1;
+        }
+        event.seq_num
+    }
+
+    /// Records a fill and returns it. `taker_buy_quantity`/`taker_sell_quantity`
+    /// are in the taker's terms, as the matching engine reports them.
+    #[allow(clippy::too_many_arguments)]
+    pub fn push_fill(
+        &mut self,
+        maker: Pubkey,
+        maker_order_id: u64,
+        taker: Pubkey,
+        taker_order_id: u64,
+        taker_buy_token: &Pubkey,
+        taker_buy_quantity: u64,
+        taker_sell_quantity: u64,
+        maker_out: bool,
+        timestamp: i64,
+    ) -> MarketEvent {
+        let taker_buys_base = *taker_buy_token == self.base_mint;
+        let (base_quantity, quote_quantity) = if taker_buys_base {
+            (taker_buy_quantity, taker_sell_quantity)
+        } else {
+            (taker_sell_quantity, taker_buy_quantity)
+        };
+        let mut event = MarketEvent {
+            maker_order_id,
+            taker_order_id,
+            base_quantity,
+            quote_quantity,
+            price: price_of(base_quantity, quote_quantity),
+            timestamp,
+            maker,
+            taker,
+            event_type: MARKET_EVENT_FILL,
+            taker_side: if taker_buys_base { TAKER_SIDE_BUY } else { TAKER_SIDE_SELL },
+            maker_out: maker_out as u8,
+            ..Default::default()
+        };
+        event.seq_num = self.push(event);
+        event
+    }
+
+    /// Records an order leaving the market without being filled, e.g. a cancel
+    /// or the part of a taker order that could not be matched nor rested.
+    pub fn push_out(
+        &mut self,
+        owner: Pubkey,
+        order_id: u64,
+        buy_token: &Pubkey,
+        buy_quantity: u64,
+        sell_quantity: u64,
+        timestamp: i64,
+    ) -> u64 {
+        let (base_quantity, quote_quantity) = if *buy_token == self.base_mint {
+            (buy_quantity, sell_quantity)
+        } else {
+            (sell_quantity, buy_quantity)
+        };
+        self.push(MarketEvent {
+            maker_order_id: order_id,
+            base_quantity,
+            quote_quantity,
+            price: price_of(base_quantity, quote_quantity),
+            timestamp,
+            maker: owner,
+            event_type: MARKET_EVENT_OUT,
+            ..Default::default()
+        })
+    }
+}
+
+/// Quote paid per base token, 0 when no base changed hands.
+pub fn price_of(base_quantity: u64, quote_quantity: u64) -> f64 {
+    if base_quantity == 0 {
+        return 0.0;
+    }
+    quote_quantity as f64 / base_quantity as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_queue() -> Box<MarketEventLog> {
+        let mut queue: Box<MarketEventLog> = Box::new(bytemuck::Zeroable::zeroed());
+        queue.init(Pubkey::new_unique(), Pubkey::new_unique(), 0);
+        queue
+    }
+
+    #[test]
+    fn test_push_fill_uses_base_quote_terms() {
+        let mut queue = new_queue();
+        let (base, quote) = (queue.base_mint, queue.quote_mint);
+        let (maker, taker) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+        // Taker sells 2 base for 200 quote, then buys 1 base for 110 quote
+        queue.push_fill(maker, 1, taker, 2, &quote, 200, 2, true, 0);
+        queue.push_fill(maker, 3, taker, 4, &base, 1, 110, false, 0);
+
+        let sell = queue.at(0).unwrap();
+        assert_eq!((sell.base_quantity, sell.quote_quantity, sell.price), (2, 200, 100.0));
+        assert_eq!((sell.taker_side, sell.maker_out), (TAKER_SIDE_SELL, 1));
+        let buy = queue.at(1).unwrap();
+        assert_eq!((buy.base_quantity, buy.quote_quantity, buy.price), (1, 110, 110.0));
+        assert_eq!((buy.taker_side, buy.seq_num), (TAKER_SIDE_BUY, 1));
+    }
+
+    #[test]
+    fn test_full_queue_overwrites_oldest() {
+        let mut queue = new_queue();
+        let owner = Pubkey::new_unique();
+        let base = queue.base_mint;
+        for id in 0..(MAX_MARKET_EVENTS as u64 + 3) {
+            queue.push_out(owner, id, &base, 1, 1, 0);
+        }
+
+        assert_eq!(queue.len(), MAX_MARKET_EVENTS);
+        assert_eq!(queue.at(0).unwrap().seq_num, 3);
+        assert_eq!(queue.at(MAX_MARKET_EVENTS - 1).unwrap().maker_order_id, MAX_MARKET_EVENTS as u64 + 2);
+        assert!(queue.at(MAX_MARKET_EVENTS).is_none());
+    }
+}
@@ -6,6 +6,12 @@ mod individual_ledger;
 mod token_pair;
 mod dex_manager;
 mod dict_tree_map;
+mod market;
+mod market_events;
+mod crank_pool;
+mod fee_ledger;
+mod fee_tiers;
+mod governance;
 
 pub use orderheap::*;
 pub use order_node::*;
@@ -14,4 +20,10 @@ pub use vault_token_ledger::*;
 pub use individual_ledger::*;
 pub use token_pair::*;
 pub use dex_manager::*;
-pub use dict_tree_map::*;
\ No newline at end of file
+pub use dict_tree_map::*;
+pub use market::*;
+pub use market_events::*;
+pub use crank_pool::*;
+pub use fee_ledger::*;
+pub use fee_tiers::*;
+pub use governance::*;
\ No newline at end of file
@@ -4,6 +4,8 @@ use std::f32::INFINITY;
 use anchor_lang::prelude::*;
 
 
+/// `maker_discount_bps` is the fee tier discount of the owner when the order
+/// was placed, applied to its maker fee whenever it is filled.
 #[zero_copy]
 #[derive(Debug, Default)]
 pub struct OrderNode {
@@ -14,9 +16,12 @@ pub struct OrderNode {
     pub sell_token: Pubkey,
     pub owner: Pubkey,
     pub timestamp: i64,
+    pub maker_discount_bps: u16,
+    pub pad: [u8; 6],
 }
 
 impl OrderNode {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         id: u64,
         buy_quantity: u64,
@@ -25,6 +30,7 @@ impl OrderNode {
         sell_token: Pubkey,
         owner: Pubkey,
         timestamp: i64,
+        maker_discount_bps: u16,
     ) -> Self {
         Self {
             id,
@@ -34,6 +40,8 @@ impl OrderNode {
             sell_token,
             owner,
             timestamp,
+            maker_discount_bps,
+            pad: [0; 6],
         }
     }
     pub fn buy_price(&self) -> f64 {
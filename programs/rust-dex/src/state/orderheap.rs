@@ -5,12 +5,7 @@ use crate::common::ORDER_HEAP_CAPACITY;
 use crate::state::OrderNode;
 use crate::state::DictTreeMapImpl;
 use crate::DictTreeMap;
-
-#[error_code]
-pub enum ErrorCode {
-    OrderHeapFull,
-    OrderNotFound,
-}
+use crate::error::ErrorCode;
 
 pub trait OrderHeap {
     fn add_order(&mut self, order: OrderNode) -> Result<()>;
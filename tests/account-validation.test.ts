@@ -0,0 +1,141 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { RustDex } from "../target/types/rust_dex";
+import { getAssociatedTokenAddressSync } from "@solana/spl-token";
+import { PublicKey, Keypair } from "@solana/web3.js";
+import { expect } from "chai";
+import {
+  createFundedUser,
+  createTokenMint,
+  createUserTokenAccount,
+  registerVaultTokenLedger,
+  registerUser,
+  registerUserTokenLedger,
+  placeLimitOrder,
+  cancelOrder,
+  dexAdmin,
+  expectError,
+  fundUser,
+  ledgerPda,
+  orderAccounts,
+  pairPda,
+  registerTokenPair,
+  resetDexManager,
+  userPda,
+} from "./test-utils";
+
+describe("rust-dex: account-validation", () => {
+  anchor.setProvider(anchor.AnchorProvider.env());
+  const program = anchor.workspace.rustDex as Program<RustDex>;
+  const provider = anchor.getProvider() as anchor.AnchorProvider;
+
+  let mintAuthority: Keypair;
+  let maker: Keypair;
+  let taker: Keypair;
+  let admin: Keypair;
+
+  let baseMint: PublicKey;
+  let quoteMint: PublicKey;
+
+  const INITIAL_AMOUNT = 1000000;
+  const DEPOSIT_AMOUNT = 100000;
+
+  async function balances(mint: PublicKey, user: Keypair) {
+    const ledger = await program.account.individualTokenLedgerAccount.fetch(ledgerPda(program, mint, user));
+    return { available: ledger.availableBalance.toNumber(), locked: ledger.lockedBalance.toNumber() };
+  }
+
+  before(async () => {
+    mintAuthority = await createFundedUser(provider);
+    maker = await createFundedUser(provider);
+    taker = await createFundedUser(provider);
+    admin = dexAdmin(provider);
+
+    baseMint = await createTokenMint(provider.connection, mintAuthority, 9);
+    quoteMint = await createTokenMint(provider.connection, mintAuthority, 6);
+
+    await resetDexManager(program, admin);
+
+    await registerTokenPair(program, admin, baseMint, quoteMint);
+
+    await registerUser(program, maker);
+    await registerUser(program, taker);
+    for (const mint of [baseMint, quoteMint]) {
+      const { vaultTokenAccount, vaultTokenLedgerPda } = await registerVaultTokenLedger(program, admin, mint);
+      for (const user of [maker, taker]) {
+        await fundUser(program, mintAuthority, user, mint, { vaultTokenAccount, vaultTokenLedgerPda }, INITIAL_AMOUNT, DEPOSIT_AMOUNT);
+      }
+    }
+
+    // Maker bids 10 base at 100 quote and asks 10 base at 110 quote
+    for (const [side, price] of [["buy", 100], ["sell", 110]] as const) {
+      await placeLimitOrder(program, maker, baseMint, quoteMint, side, price, 10, ...orderAccounts(program, maker, baseMint, quoteMint));
+    }
+  });
+
+  async function restingBid() {
+    const bidQueue = await program.account.tokenPairAccount.fetch(pairPda(program, baseMint, quoteMint));
+    return bidQueue.orderHeap.orders[0];
+  }
+
+  it("rejects cancelling someone else's order", async () => {
+    const bid = await restingBid();
+    await expectError(
+      cancelOrder(program, taker, bid.id.toNumber(), pairPda(program, baseMint, quoteMint), userPda(program, "user_orderbook", taker), ledgerPda(program, quoteMint, taker), baseMint, quoteMint),
+      "NotOrderOwner"
+    );
+  });
+
+  it("rejects cancelling into someone else's ledger", async () => {
+    const bid = await restingBid();
+    await expectError(
+      cancelOrder(program, maker, bid.id.toNumber(), pairPda(program, baseMint, quoteMint), userPda(program, "user_orderbook", maker), ledgerPda(program, quoteMint, taker), baseMint, quoteMint),
+      "ConstraintSeeds"
+    );
+  });
+
+  it("rejects cancelling into a ledger of the wrong token", async () => {
+    const bid = await restingBid();
+    await expectError(
+      cancelOrder(program, maker, bid.id.toNumber(), pairPda(program, baseMint, quoteMint), userPda(program, "user_orderbook", maker), ledgerPda(program, baseMint, maker), baseMint, quoteMint),
+      "LedgerMintMismatch"
+    );
+  });
+
+  it("rejects placing an order against someone else's ledgers", async () => {
+    const accounts = orderAccounts(program, taker, baseMint, quoteMint);
+    await expectError(
+      placeLimitOrder(program, taker, baseMint, quoteMint, "buy", 90, 1,
+        accounts[0], accounts[1], accounts[2], accounts[3], ledgerPda(program, baseMint, maker), ledgerPda(program, quoteMint, maker), accounts[6]),
+      "ConstraintSeeds"
+    );
+  });
+
+  it("rejects placing an order into someone else's orderbook", async () => {
+    const accounts = orderAccounts(program, taker, baseMint, quoteMint);
+    await expectError(
+      placeLimitOrder(program, taker, baseMint, quoteMint, "buy", 90, 1,
+        accounts[0], accounts[1], accounts[2], accounts[3], accounts[4], accounts[5], userPda(program, "user_orderbook", maker)),
+      "ConstraintSeeds"
+    );
+  });
+
+  it("rejects registering a ledger with someone else's token account", async () => {
+    const otherMint = await createTokenMint(provider.connection, mintAuthority, 6);
+    const makerTokenAccount = await createUserTokenAccount(provider.connection, maker, otherMint);
+    await expectError(registerUserTokenLedger(program, taker, otherMint, makerTokenAccount), "ConstraintTokenOwner");
+  });
+
+  it("rejects registering a ledger with a token account of another mint", async () => {
+    const otherMint = await createTokenMint(provider.connection, mintAuthority, 6);
+    const takerTokenAccount = getAssociatedTokenAddressSync(quoteMint, taker.publicKey);
+    await expectError(registerUserTokenLedger(program, taker, otherMint, takerTokenAccount), "ConstraintTokenMint");
+  });
+
+  it("still lets the owner cancel the order", async () => {
+    const bid = await restingBid();
+    const before = await balances(quoteMint, maker);
+    await cancelOrder(program, maker, bid.id.toNumber(), pairPda(program, baseMint, quoteMint), userPda(program, "user_orderbook", maker), ledgerPda(program, quoteMint, maker), baseMint, quoteMint);
+    expect((await balances(quoteMint, maker)).available).to.equal(before.available + 1000);
+  });
+});
@@ -0,0 +1,174 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { RustDex } from "../target/types/rust_dex";
+import { TOKEN_PROGRAM_ID } from "@solana/spl-token";
+import { PublicKey, Keypair, SystemProgram } from "@solana/web3.js";
+import { expect } from "chai";
+import {
+  createFundedUser,
+  createTokenMint,
+  registerVaultTokenLedger,
+  dexAdmin,
+  marketEventsPda,
+  crankPoolPda,
+  marketPda,
+  expectError,
+  pairPda,
+  resetDexManager,
+} from "./test-utils";
+
+describe("rust-dex: admin", () => {
+  anchor.setProvider(anchor.AnchorProvider.env());
+  const program = anchor.workspace.rustDex as Program<RustDex>;
+  const provider = anchor.getProvider() as anchor.AnchorProvider;
+
+  let admin: Keypair;
+  let outsider: Keypair;
+  let newAdmin: Keypair;
+
+  let baseMint: PublicKey;
+  let quoteMint: PublicKey;
+  let dexManagerPda: PublicKey;
+
+  function registerTokenPair(signer: Keypair) {
+    return program.methods
+      .registerTokenPair(baseMint, quoteMint)
+      .accountsPartial({
+        market: marketPda(program, baseMint, quoteMint),
+        marketEvents: marketEventsPda(program, baseMint, quoteMint),
+        admin: signer.publicKey,
+        systemProgram: SystemProgram.programId,
+        tokenPair: pairPda(program, baseMint, quoteMint),
+        oppositePair: pairPda(program, quoteMint, baseMint),
+      })
+      .signers([signer])
+      .rpc();
+  }
+
+  function proposeAdmin(signer: Keypair, candidate: PublicKey) {
+    return program.methods
+      .proposeAdmin(candidate)
+      .accountsPartial({ dexManager: dexManagerPda, admin: signer.publicKey })
+      .signers([signer])
+      .rpc();
+  }
+
+  function acceptAdmin(signer: Keypair) {
+    return program.methods
+      .acceptAdmin()
+      .accountsPartial({ dexManager: dexManagerPda, pendingAdmin: signer.publicKey })
+      .signers([signer])
+      .rpc();
+  }
+
+  function setMarketFees(signer: Keypair) {
+    return program.methods
+      .setMarketFees(baseMint, quoteMint, 0, 0)
+      .accountsPartial({ market: marketPda(program, baseMint, quoteMint), dexManager: dexManagerPda, admin: signer.publicKey })
+      .signers([signer])
+      .rpc();
+  }
+
+  before(async () => {
+    admin = dexAdmin(provider);
+    outsider = await createFundedUser(provider);
+    newAdmin = await createFundedUser(provider);
+
+    const mintAuthority = await createFundedUser(provider);
+    baseMint = await createTokenMint(provider.connection, mintAuthority, 9);
+    quoteMint = await createTokenMint(provider.connection, mintAuthority, 6);
+
+    dexManagerPda = await resetDexManager(program, admin);
+  });
+
+  it("makes whoever initializes the DEX its admin", async () => {
+    const dexManager = await program.account.dexManager.fetch(dexManagerPda);
+    expect(dexManager.admin.toBase58()).to.equal(admin.publicKey.toBase58());
+    expect(dexManager.pendingAdmin.toBase58()).to.equal(PublicKey.default.toBase58());
+  });
+
+  it("only lets the admin register token pairs", async () => {
+    await expectError(registerTokenPair(outsider), "Unauthorized");
+    await registerTokenPair(admin);
+  });
+
+  it("only lets the admin register vaults", async () => {
+    const vaultTokenAccount = Keypair.generate();
+    await expectError(
+      program.methods
+        .registerVaultTokenLedger()
+        .accountsPartial({
+          mintAccount: baseMint,
+          vaultTokenAccount: vaultTokenAccount.publicKey,
+          admin: outsider.publicKey,
+          systemProgram: SystemProgram.programId,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([outsider, vaultTokenAccount])
+        .rpc(),
+      "Unauthorized"
+    );
+    await registerVaultTokenLedger(program, admin, baseMint);
+  });
+
+  it("only lets the admin create crank pools", async () => {
+    await expectError(
+      program.methods
+        .initCrankPool(baseMint, quoteMint, new anchor.BN(1000))
+        .accountsPartial({
+          crankPool: crankPoolPda(program, baseMint, quoteMint),
+          market: marketPda(program, baseMint, quoteMint),
+          admin: outsider.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([outsider])
+        .rpc(),
+      "Unauthorized"
+    );
+  });
+
+  it("only lets the admin close the DEX", async () => {
+    await expectError(
+      program.methods.closeDexManager()
+        .accountsPartial({
+          dexManager: dexManagerPda,
+          admin: outsider.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([outsider])
+        .rpc(),
+      "Unauthorized"
+    );
+  });
+
+  it("only lets the admin propose a new admin", async () => {
+    await expectError(proposeAdmin(outsider, outsider.publicKey), "Unauthorized");
+  });
+
+  it("only lets the proposed admin accept", async () => {
+    await proposeAdmin(admin, newAdmin.publicKey);
+    const dexManager = await program.account.dexManager.fetch(dexManagerPda);
+    expect(dexManager.admin.toBase58()).to.equal(admin.publicKey.toBase58());
+    expect(dexManager.pendingAdmin.toBase58()).to.equal(newAdmin.publicKey.toBase58());
+
+    await expectError(acceptAdmin(outsider), "NotPendingAdmin");
+  });
+
+  it("hands the authority over once the proposed admin accepts", async () => {
+    await acceptAdmin(newAdmin);
+    const dexManager = await program.account.dexManager.fetch(dexManagerPda);
+    expect(dexManager.admin.toBase58()).to.equal(newAdmin.publicKey.toBase58());
+    expect(dexManager.pendingAdmin.toBase58()).to.equal(PublicKey.default.toBase58());
+
+    await expectError(setMarketFees(admin), "Unauthorized");
+    await setMarketFees(newAdmin);
+  });
+
+  it("hands the authority back to the provider wallet", async () => {
+    // The other suites expect the provider wallet to be the admin
+    await proposeAdmin(newAdmin, admin.publicKey);
+    await acceptAdmin(admin);
+    const dexManager = await program.account.dexManager.fetch(dexManagerPda);
+    expect(dexManager.admin.toBase58()).to.equal(admin.publicKey.toBase58());
+  });
+});
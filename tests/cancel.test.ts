@@ -14,7 +14,10 @@ import {
   depositTokens,
   placeLimitOrder,
   placeMarketOrder,
-  cancelOrder
+  cancelOrder,
+  dexAdmin,
+  marketEventsPda,
+  marketPda,
 } from "./test-utils";
 
 describe("rust-dex: cancel-order", () => {
@@ -26,7 +29,7 @@ describe("rust-dex: cancel-order", () => {
   let mintAuthority: Keypair;
   let user1: Keypair;
   let user2: Keypair;
-  let vault: Keypair;
+  let admin: Keypair;
 
   // Token related
   let baseMint: PublicKey;
@@ -63,7 +66,7 @@ describe("rust-dex: cancel-order", () => {
     mintAuthority = await createFundedUser(provider);
     user1 = await createFundedUser(provider);
     user2 = await createFundedUser(provider);
-    vault = await createFundedUser(provider);
+    admin = dexAdmin(provider);
 
     // Create tokens
     baseMint = await createTokenMint(provider.connection, mintAuthority, 9);
@@ -146,10 +149,10 @@ describe("rust-dex: cancel-order", () => {
       await program.methods.closeDexManager()
         .accountsPartial({
           dexManager: dexManagerPda,
-          user: user1.publicKey,
+          admin: admin.publicKey,
           systemProgram: SystemProgram.programId,
         })
-        .signers([user1])
+        .signers([admin])
         .rpc();
     } catch (error) {
       console.log("No existing dex_manager to close:", error.message);
@@ -158,29 +161,31 @@ describe("rust-dex: cancel-order", () => {
     await program.methods.initialize()
       .accountsPartial({
         dexManager: dexManagerPda,
-        user: user1.publicKey,
+        user: admin.publicKey,
         systemProgram: SystemProgram.programId,
       })
-      .signers([user1])
+      .signers([admin])
       .rpc();
 
     // Register vault token ledgers
-    const baseVaultResult = await registerVaultTokenLedger(program, vault, baseMint);
+    const baseVaultResult = await registerVaultTokenLedger(program, admin, baseMint);
     vaultBaseTokenAccount = baseVaultResult.vaultTokenAccount;
     
-    const quoteVaultResult = await registerVaultTokenLedger(program, vault, quoteMint);
+    const quoteVaultResult = await registerVaultTokenLedger(program, admin, quoteMint);
     vaultQuoteTokenAccount = quoteVaultResult.vaultTokenAccount;
 
     // Register token pair
     await program.methods
       .registerTokenPair(baseMint, quoteMint)
       .accountsPartial({
-        user: user1.publicKey,
+        market: marketPda(program, baseMint, quoteMint),
+        marketEvents: marketEventsPda(program, baseMint, quoteMint),
+        admin: admin.publicKey,
         systemProgram: SystemProgram.programId,
         tokenPair: buyBaseQueuePda,
         oppositePair: sellBaseQueuePda,
       })
-      .signers([user1])
+      .signers([admin])
       .rpc();
 
     // Register users
@@ -0,0 +1,158 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { RustDex } from "../target/types/rust_dex";
+import { mintTo, TOKEN_PROGRAM_ID } from "@solana/spl-token";
+import { PublicKey, Keypair, SystemProgram } from "@solana/web3.js";
+import { expect } from "chai";
+import {
+  createFundedUser,
+  createTokenMint,
+  createUserTokenAccount,
+  registerVaultTokenLedger,
+  registerUser,
+  registerUserTokenLedger,
+  depositTokens,
+  placeLimitOrder,
+  cancelOrder,
+  dexAdmin,
+  expectError,
+  ledgerPda,
+  pairPda,
+  registerTokenPair,
+  resetDexManager,
+  userPda,
+  orderAccounts,
+} from "./test-utils";
+
+describe("rust-dex: closing user accounts", () => {
+  anchor.setProvider(anchor.AnchorProvider.env());
+  const program = anchor.workspace.rustDex as Program<RustDex>;
+  const provider = anchor.getProvider() as anchor.AnchorProvider;
+
+  let mintAuthority: Keypair;
+  let admin: Keypair;
+  let user: Keypair;
+
+  let baseMint: PublicKey;
+  let quoteMint: PublicKey;
+  const vaults = new Map<string, { vaultTokenAccount: PublicKey; vaultTokenLedgerPda: PublicKey }>();
+  const userTokenAccounts = new Map<string, PublicKey>();
+
+  const INITIAL_AMOUNT = 1000000;
+  const DEPOSIT_AMOUNT = 100000;
+
+  const writable = (ledgers: PublicKey[]) => ledgers.map((pubkey) => ({ pubkey, isSigner: false, isWritable: true }));
+
+  function closeUserTokenLedger(mint: PublicKey, otherLedgers: PublicKey[]) {
+    return program.methods
+      .closeUserTokenLedger(mint)
+      .accountsPartial({ user: user.publicKey })
+      .remainingAccounts(writable(otherLedgers))
+      .signers([user])
+      .rpc();
+  }
+
+  function deregisterUser(ledgers: PublicKey[]) {
+    return program.methods
+      .deregisterUser()
+      .accountsPartial({ user: user.publicKey })
+      .remainingAccounts(writable(ledgers))
+      .signers([user])
+      .rpc();
+  }
+
+  async function withdrawAll(mint: PublicKey) {
+    const { vaultTokenAccount, vaultTokenLedgerPda } = vaults.get(mint.toBase58());
+    const [vaultTokenAuthorityPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("vault_token_account"), mint.toBuffer()],
+      program.programId
+    );
+    const ledger = await program.account.individualTokenLedgerAccount.fetch(ledgerPda(program, mint, user));
+    await program.methods.withdraw(mint, ledger.availableBalance)
+      .accountsPartial({
+        vaultTokenLedger: vaultTokenLedgerPda,
+        vaultTokenAuthority: vaultTokenAuthorityPda,
+        userTokenLedger: ledgerPda(program, mint, user),
+        userTokenAccount: userTokenAccounts.get(mint.toBase58()),
+        vaultTokenAccount,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        user: user.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([user])
+      .rpc();
+  }
+
+  before(async () => {
+    mintAuthority = await createFundedUser(provider);
+    admin = dexAdmin(provider);
+    user = await createFundedUser(provider);
+
+    baseMint = await createTokenMint(provider.connection, mintAuthority, 9);
+    quoteMint = await createTokenMint(provider.connection, mintAuthority, 6);
+
+    await resetDexManager(program, admin);
+
+    await registerTokenPair(program, admin, baseMint, quoteMint);
+
+    await registerUser(program, user);
+    for (const mint of [baseMint, quoteMint]) {
+      const vaultAccounts = await registerVaultTokenLedger(program, admin, mint);
+      vaults.set(mint.toBase58(), vaultAccounts);
+      const userTokenAccount = await createUserTokenAccount(provider.connection, user, mint);
+      userTokenAccounts.set(mint.toBase58(), userTokenAccount);
+      await mintTo(provider.connection, mintAuthority, mint, userTokenAccount, mintAuthority, INITIAL_AMOUNT);
+      await registerUserTokenLedger(program, user, mint, userTokenAccount);
+    }
+    const { vaultTokenAccount, vaultTokenLedgerPda } = vaults.get(quoteMint.toBase58());
+    await depositTokens(program, user, quoteMint, DEPOSIT_AMOUNT, userTokenAccounts.get(quoteMint.toBase58()), vaultTokenAccount, vaultTokenLedgerPda, ledgerPda(program, quoteMint, user));
+  });
+
+  it("keeps ledgers open while an order locks tokens", async () => {
+    await placeLimitOrder(program, user, baseMint, quoteMint, "buy", 1, 1000, ...orderAccounts(program, user, baseMint, quoteMint));
+
+    // The base ledger is empty, but the bid settles into it once filled
+    await expectError(closeUserTokenLedger(baseMint, []), "MissingUserTokenLedger");
+    await expectError(closeUserTokenLedger(baseMint, [ledgerPda(program, quoteMint, user)]), "TokensLocked");
+    await expectError(closeUserTokenLedger(quoteMint, [ledgerPda(program, baseMint, user)]), "LedgerNotEmpty");
+    await expectError(deregisterUser([ledgerPda(program, baseMint, user), ledgerPda(program, quoteMint, user)]), "LedgerNotEmpty");
+  });
+
+  it("closes an empty ledger once nothing is locked", async () => {
+    const bids = await program.account.tokenPairAccount.fetch(pairPda(program, baseMint, quoteMint));
+    const order = bids.orderHeap.orders[0];
+    await cancelOrder(
+      program, user, order.id.toNumber(), pairPda(program, baseMint, quoteMint), userPda(program, "user_orderbook", user),
+      ledgerPda(program, quoteMint, user), baseMint, quoteMint
+    );
+
+    const before = await provider.connection.getBalance(user.publicKey);
+    const rent = await provider.connection.getBalance(ledgerPda(program, baseMint, user));
+    await closeUserTokenLedger(baseMint, [ledgerPda(program, quoteMint, user)]);
+
+    expect(await provider.connection.getAccountInfo(ledgerPda(program, baseMint, user))).to.equal(null);
+    expect(await provider.connection.getBalance(user.publicKey)).to.be.greaterThan(before + rent - 10_000);
+    const individualLedger = await program.account.individualLedgerAccount.fetch(userPda(program, "user_ledger", user));
+    expect(individualLedger.nextIndex).to.equal(1);
+    expect(individualLedger.tokens[0].toBase58()).to.equal(quoteMint.toBase58());
+  });
+
+  it("deregisters a user whose ledgers are empty", async () => {
+    await withdrawAll(quoteMint);
+    await expectError(deregisterUser([]), "MissingUserTokenLedger");
+
+    await deregisterUser([ledgerPda(program, quoteMint, user)]);
+
+    for (const account of [
+      ledgerPda(program, quoteMint, user), userPda(program, "user_ledger", user), userPda(program, "user_orderbook", user), userPda(program, "order_events", user),
+    ]) {
+      expect(await provider.connection.getAccountInfo(account)).to.equal(null);
+    }
+  });
+
+  it("lets a retired wallet register again", async () => {
+    await registerUser(program, user);
+    const eventList = await program.account.eventList.fetch(userPda(program, "order_events", user));
+    expect(eventList.length.toNumber()).to.equal(0);
+  });
+});
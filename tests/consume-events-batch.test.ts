@@ -0,0 +1,131 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { RustDex } from "../target/types/rust_dex";
+import { PublicKey, Keypair } from "@solana/web3.js";
+import { expect } from "chai";
+import {
+  createFundedUser,
+  createTokenMint,
+  registerVaultTokenLedger,
+  registerUser,
+  placeLimitOrder,
+  dexAdmin,
+  marketPda,
+  fundUser,
+  ledgerPda,
+  registerTokenPair,
+  resetDexManager,
+  userPda,
+  orderAccounts,
+} from "./test-utils";
+
+describe("rust-dex: consume-events-batch", () => {
+  anchor.setProvider(anchor.AnchorProvider.env());
+  const program = anchor.workspace.rustDex as Program<RustDex>;
+  const provider = anchor.getProvider() as anchor.AnchorProvider;
+
+  let mintAuthority: Keypair;
+  let maker: Keypair;
+  let otherMaker: Keypair;
+  let taker: Keypair;
+  let admin: Keypair;
+
+  let baseMint: PublicKey;
+  let quoteMint: PublicKey;
+
+  const INITIAL_AMOUNT = 1000000;
+  const DEPOSIT_AMOUNT = 100000;
+
+  async function balances(mint: PublicKey, user: Keypair) {
+    const ledger = await program.account.individualTokenLedgerAccount.fetch(ledgerPda(program, mint, user));
+    return { available: ledger.availableBalance.toNumber(), locked: ledger.lockedBalance.toNumber() };
+  }
+
+  before(async () => {
+    mintAuthority = await createFundedUser(provider);
+    maker = await createFundedUser(provider);
+    otherMaker = await createFundedUser(provider);
+    taker = await createFundedUser(provider);
+    admin = dexAdmin(provider);
+
+    baseMint = await createTokenMint(provider.connection, mintAuthority, 9);
+    quoteMint = await createTokenMint(provider.connection, mintAuthority, 6);
+
+    await resetDexManager(program, admin);
+
+    await registerTokenPair(program, admin, baseMint, quoteMint);
+
+    await registerUser(program, maker);
+    await registerUser(program, otherMaker);
+    await registerUser(program, taker);
+    for (const mint of [baseMint, quoteMint]) {
+      const { vaultTokenAccount, vaultTokenLedgerPda } = await registerVaultTokenLedger(program, admin, mint);
+      for (const user of [maker, otherMaker, taker]) {
+        await fundUser(program, mintAuthority, user, mint, { vaultTokenAccount, vaultTokenLedgerPda }, INITIAL_AMOUNT, DEPOSIT_AMOUNT);
+      }
+    }
+
+    // Two makers bid at different prices and one also asks, the taker sells
+    // through both bids and then lifts the ask before settling anything
+    await placeLimitOrder(program, maker, baseMint, quoteMint, "buy", 100, 5, ...orderAccounts(program, maker, baseMint, quoteMint));
+    await placeLimitOrder(program, otherMaker, baseMint, quoteMint, "buy", 99, 5, ...orderAccounts(program, otherMaker, baseMint, quoteMint));
+    await placeLimitOrder(program, maker, baseMint, quoteMint, "sell", 101, 5, ...orderAccounts(program, maker, baseMint, quoteMint));
+    await placeLimitOrder(program, taker, baseMint, quoteMint, "sell", 99, 10, ...orderAccounts(program, taker, baseMint, quoteMint));
+    await placeLimitOrder(program, taker, baseMint, quoteMint, "buy", 101, 5, ...orderAccounts(program, taker, baseMint, quoteMint));
+  });
+
+  const ledgers = (users: Keypair[]) => users.flatMap((user) =>
+    [baseMint, quoteMint].map((mint) => ({ pubkey: ledgerPda(program, mint, user), isSigner: false, isWritable: true }))
+  );
+
+  async function consumeEventsBatch(maxEvents: number, makers: Keypair[]) {
+    await program.methods
+      .consumeEventsBatch(maxEvents)
+      .accountsPartial({
+        eventList: userPda(program, "order_events", taker),
+        user: taker.publicKey,
+      })
+      .remainingAccounts([
+        ...ledgers([taker, ...makers]),
+        { pubkey: marketPda(program, baseMint, quoteMint), isSigner: false, isWritable: true },
+      ])
+      .signers([taker])
+      .rpc();
+  }
+
+  it("keeps the events of back-to-back orders pending together", async () => {
+    const eventList = await program.account.eventList.fetch(userPda(program, "order_events", taker));
+    expect(eventList.length.toNumber()).to.equal(3);
+    const head = eventList.head.toNumber();
+    const orderIds = [0, 1, 2].map((i) => eventList.orderId[(head + i) % eventList.orderId.length].toNumber());
+    expect(orderIds[0]).to.equal(orderIds[1]);
+    expect(orderIds[2]).to.be.greaterThan(orderIds[1]);
+  });
+
+  it("fails when a counterparty ledger is missing", async () => {
+    try {
+      await consumeEventsBatch(8, [maker]);
+      expect.fail("batch should have failed");
+    } catch (error) {
+      expect(error.message).to.include("MissingCounterpartyLedger");
+    }
+  });
+
+  it("settles fills against several makers in one instruction", async () => {
+    const takerBase = await balances(baseMint, taker);
+    const takerQuote = await balances(quoteMint, taker);
+    const makerBase = await balances(baseMint, maker);
+    const otherMakerBase = await balances(baseMint, otherMaker);
+
+    await consumeEventsBatch(8, [maker, otherMaker]);
+
+    expect((await balances(baseMint, taker)).locked).to.equal(takerBase.locked - 10);
+    expect((await balances(baseMint, taker)).available).to.equal(takerBase.available + 5);
+    expect((await balances(quoteMint, taker)).locked).to.equal(takerQuote.locked - 505);
+    expect((await balances(quoteMint, taker)).available).to.equal(takerQuote.available + 500 + 495);
+    expect((await balances(baseMint, maker)).available).to.equal(makerBase.available + 5);
+    expect((await balances(baseMint, otherMaker)).available).to.equal(otherMakerBase.available + 5);
+    const eventList = await program.account.eventList.fetch(userPda(program, "order_events", taker));
+    expect(eventList.length.toNumber()).to.equal(0);
+  });
+});
@@ -0,0 +1,129 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { RustDex } from "../target/types/rust_dex";
+import { PublicKey, Keypair, SystemProgram } from "@solana/web3.js";
+import { expect } from "chai";
+import {
+  createFundedUser,
+  createTokenMint,
+  registerVaultTokenLedger,
+  registerUser,
+  feeLedgerPda,
+  placeLimitOrder,
+  dexAdmin,
+  marketPda,
+  fundUser,
+  ledgerPda,
+  registerTokenPair,
+  resetDexManager,
+  userPda,
+  orderAccounts,
+} from "./test-utils";
+
+describe("rust-dex: consume-events-validation", () => {
+  anchor.setProvider(anchor.AnchorProvider.env());
+  const program = anchor.workspace.rustDex as Program<RustDex>;
+  const provider = anchor.getProvider() as anchor.AnchorProvider;
+
+  let mintAuthority: Keypair;
+  let maker: Keypair;
+  let taker: Keypair;
+  let admin: Keypair;
+
+  let baseMint: PublicKey;
+  let quoteMint: PublicKey;
+
+  const INITIAL_AMOUNT = 1000000;
+  const DEPOSIT_AMOUNT = 100000;
+
+  async function balances(mint: PublicKey, user: Keypair) {
+    const ledger = await program.account.individualTokenLedgerAccount.fetch(ledgerPda(program, mint, user));
+    return { available: ledger.availableBalance.toNumber(), locked: ledger.lockedBalance.toNumber() };
+  }
+
+  before(async () => {
+    mintAuthority = await createFundedUser(provider);
+    maker = await createFundedUser(provider);
+    taker = await createFundedUser(provider);
+    admin = dexAdmin(provider);
+
+    baseMint = await createTokenMint(provider.connection, mintAuthority, 9);
+    quoteMint = await createTokenMint(provider.connection, mintAuthority, 6);
+
+    await resetDexManager(program, admin);
+
+    await registerTokenPair(program, admin, baseMint, quoteMint);
+
+    await registerUser(program, maker);
+    await registerUser(program, taker);
+    for (const mint of [baseMint, quoteMint]) {
+      const { vaultTokenAccount, vaultTokenLedgerPda } = await registerVaultTokenLedger(program, admin, mint);
+      for (const user of [maker, taker]) {
+        await fundUser(program, mintAuthority, user, mint, { vaultTokenAccount, vaultTokenLedgerPda }, INITIAL_AMOUNT, DEPOSIT_AMOUNT);
+      }
+    }
+
+    // Maker bids 10 base at 100 quote and asks 10 base at 110 quote
+    for (const [side, price] of [["buy", 100], ["sell", 110]] as const) {
+      await placeLimitOrder(program, maker, baseMint, quoteMint, side, price, 10, ...orderAccounts(program, maker, baseMint, quoteMint));
+    }
+
+    // The taker sells into the maker's bid and walks away without consuming its events
+    await placeLimitOrder(program, taker, baseMint, quoteMint, "sell", 100, 4, ...orderAccounts(program, taker, baseMint, quoteMint));
+  });
+
+  // The taker sold base for quote, so its income is quote and its outcome base
+  async function consumeEvents(ledgers: { userIncome: PublicKey; userOutcome: PublicKey; oppIncome: PublicKey; oppOutcome: PublicKey; incomeFee: PublicKey; outcomeFee: PublicKey }) {
+    await program.methods
+      .consumeEvents(maker.publicKey)
+      .accountsPartial({
+        eventList: userPda(program, "order_events", taker),
+        userTokenIncomeLedger: ledgers.userIncome,
+        userTokenOutcomeLedger: ledgers.userOutcome,
+        oppositeUserTokenIncomeLedger: ledgers.oppIncome,
+        oppositeUserTokenOutcomeLedger: ledgers.oppOutcome,
+        incomeFeeLedger: ledgers.incomeFee,
+        outcomeFeeLedger: ledgers.outcomeFee,
+        market: marketPda(program, baseMint, quoteMint),
+        user: taker.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([taker])
+      .rpc();
+  }
+
+  const correctLedgers = () => ({
+    userIncome: ledgerPda(program, quoteMint, taker),
+    userOutcome: ledgerPda(program, baseMint, taker),
+    oppIncome: ledgerPda(program, baseMint, maker),
+    oppOutcome: ledgerPda(program, quoteMint, maker),
+    incomeFee: feeLedgerPda(program, quoteMint),
+    outcomeFee: feeLedgerPda(program, baseMint),
+  });
+
+  it("rejects user ledgers in the wrong token", async () => {
+    try {
+      await consumeEvents({ ...correctLedgers(), userIncome: ledgerPda(program, baseMint, taker), userOutcome: ledgerPda(program, quoteMint, taker),
+        incomeFee: feeLedgerPda(program, baseMint), outcomeFee: feeLedgerPda(program, quoteMint),
+      });
+      expect.fail("consume should have failed");
+    } catch (error) {
+      expect(error.message).to.include("IncomeLedgerMintMismatch");
+    }
+  });
+
+  it("rejects counterparty ledgers in the wrong token", async () => {
+    try {
+      await consumeEvents({ ...correctLedgers(), oppIncome: ledgerPda(program, quoteMint, maker), oppOutcome: ledgerPda(program, baseMint, maker) });
+      expect.fail("consume should have failed");
+    } catch (error) {
+      expect(error.message).to.include("IncomeLedgerMintMismatch");
+    }
+  });
+
+  it("settles once every ledger matches the event", async () => {
+    const takerQuote = await balances(quoteMint, taker);
+    await consumeEvents(correctLedgers());
+    expect((await balances(quoteMint, taker)).available).to.equal(takerQuote.available + 400);
+  });
+});
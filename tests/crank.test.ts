@@ -0,0 +1,201 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { RustDex } from "../target/types/rust_dex";
+import { PublicKey, Keypair, SystemProgram } from "@solana/web3.js";
+import { expect } from "chai";
+import {
+  createFundedUser,
+  createTokenMint,
+  registerVaultTokenLedger,
+  registerUser,
+  feeLedgerPda,
+  placeLimitOrder,
+  placeMarketOrder,
+  dexAdmin,
+  crankPoolPda,
+  marketPda,
+  fundUser,
+  ledgerPda,
+  registerTokenPair,
+  resetDexManager,
+  userPda,
+  orderAccounts,
+} from "./test-utils";
+
+describe("rust-dex: crank", () => {
+  anchor.setProvider(anchor.AnchorProvider.env());
+  const program = anchor.workspace.rustDex as Program<RustDex>;
+  const provider = anchor.getProvider() as anchor.AnchorProvider;
+
+  let mintAuthority: Keypair;
+  let maker: Keypair;
+  let taker: Keypair;
+  let keeper: Keypair;
+  let admin: Keypair;
+
+  let baseMint: PublicKey;
+  let quoteMint: PublicKey;
+
+  const INITIAL_AMOUNT = 1000000;
+  const DEPOSIT_AMOUNT = 100000;
+  const REWARD = 5000;
+
+  let crankPool: PublicKey;
+
+  async function balances(mint: PublicKey, user: Keypair) {
+    const ledger = await program.account.individualTokenLedgerAccount.fetch(ledgerPda(program, mint, user));
+    return { available: ledger.availableBalance.toNumber(), locked: ledger.lockedBalance.toNumber() };
+  }
+
+  before(async () => {
+    mintAuthority = await createFundedUser(provider);
+    maker = await createFundedUser(provider);
+    taker = await createFundedUser(provider);
+    keeper = await createFundedUser(provider);
+    admin = dexAdmin(provider);
+
+    baseMint = await createTokenMint(provider.connection, mintAuthority, 9);
+    quoteMint = await createTokenMint(provider.connection, mintAuthority, 6);
+
+    await resetDexManager(program, admin);
+
+    await registerTokenPair(program, admin, baseMint, quoteMint);
+
+    await registerUser(program, maker);
+    await registerUser(program, taker);
+    for (const mint of [baseMint, quoteMint]) {
+      const { vaultTokenAccount, vaultTokenLedgerPda } = await registerVaultTokenLedger(program, admin, mint);
+      for (const user of [maker, taker]) {
+        await fundUser(program, mintAuthority, user, mint, { vaultTokenAccount, vaultTokenLedgerPda }, INITIAL_AMOUNT, DEPOSIT_AMOUNT);
+      }
+    }
+
+    // Maker bids 10 base at 100 quote and asks 10 base at 110 quote
+    for (const [side, price] of [["buy", 100], ["sell", 110]] as const) {
+      await placeLimitOrder(program, maker, baseMint, quoteMint, side, price, 10, ...orderAccounts(program, maker, baseMint, quoteMint));
+    }
+
+    crankPool = crankPoolPda(program, baseMint, quoteMint);
+    await program.methods
+      .initCrankPool(baseMint, quoteMint, new anchor.BN(REWARD))
+      .accountsPartial({
+        market: marketPda(program, baseMint, quoteMint),
+        crankPool,
+        admin: admin.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([admin])
+      .rpc();
+    await program.methods
+      .fundCrankPool(new anchor.BN(1000000))
+      .accountsPartial({ crankPool, user: maker.publicKey, systemProgram: SystemProgram.programId })
+      .signers([maker])
+      .rpc();
+
+    // The taker sells into the maker's bid and walks away without consuming its events
+    await placeLimitOrder(program, taker, baseMint, quoteMint, "sell", 100, 4, ...orderAccounts(program, taker, baseMint, quoteMint));
+  });
+
+  async function crank(opposite: Keypair, swapOwnerLedgers = false, counterpartyLedgers = true) {
+    const eventList = await program.account.eventList.fetch(userPda(program, "order_events", taker));
+    // The crank settles the oldest pending event
+    const head = eventList.head.toNumber();
+    const [income, outcome] = swapOwnerLedgers
+      ? [eventList.tokenSell[head], eventList.tokenBuy[head]]
+      : [eventList.tokenBuy[head], eventList.tokenSell[head]];
+    await program.methods
+      .crankConsumeEvents(taker.publicKey, opposite.publicKey)
+      .accountsPartial({
+        eventList: userPda(program, "order_events", taker),
+        userTokenIncomeLedger: ledgerPda(program, income, taker),
+        userTokenOutcomeLedger: ledgerPda(program, outcome, taker),
+        oppositeUserTokenIncomeLedger: counterpartyLedgers ? ledgerPda(program, outcome, opposite) : null,
+        oppositeUserTokenOutcomeLedger: counterpartyLedgers ? ledgerPda(program, income, opposite) : null,
+        incomeFeeLedger: feeLedgerPda(program, income),
+        outcomeFeeLedger: feeLedgerPda(program, outcome),
+        market: marketPda(program, baseMint, quoteMint),
+        crankPool,
+        keeper: keeper.publicKey,
+      })
+      .signers([keeper])
+      .rpc();
+  }
+
+  it("rejects ledgers whose mints do not match the event list", async () => {
+    try {
+      await crank(maker, true);
+      expect.fail("crank should have failed");
+    } catch (error) {
+      expect(error.message).to.include("ConstraintSeeds");
+    }
+  });
+
+  it("lets any keeper settle another user's fill and pays the reward", async () => {
+    const takerBase = await balances(baseMint, taker);
+    const takerQuote = await balances(quoteMint, taker);
+    const makerBase = await balances(baseMint, maker);
+    const makerQuote = await balances(quoteMint, maker);
+    const keeperLamports = await provider.connection.getBalance(keeper.publicKey);
+
+    await crank(maker);
+
+    expect((await balances(baseMint, taker)).locked).to.equal(takerBase.locked - 4);
+    expect((await balances(quoteMint, taker)).available).to.equal(takerQuote.available + 400);
+    expect((await balances(baseMint, maker)).available).to.equal(makerBase.available + 4);
+    expect((await balances(quoteMint, maker)).locked).to.equal(makerQuote.locked - 400);
+    expect(await provider.connection.getBalance(keeper.publicKey)).to.equal(keeperLamports + REWARD);
+  });
+
+  it("refuses to crank a drained event list", async () => {
+    const keeperLamports = await provider.connection.getBalance(keeper.publicKey);
+    try {
+      await crank(maker);
+      expect.fail("crank should have failed");
+    } catch (error) {
+      expect(error.message).to.include("NoPendingEvents");
+    }
+    expect(await provider.connection.getBalance(keeper.publicKey)).to.equal(keeperLamports);
+  });
+
+  it("refunds a rollback without letting the owner stand in for the counterparty", async () => {
+    // The remaining 6 base of the bid fill, the other 2 base are rolled back
+    await placeMarketOrder(program, taker, baseMint, quoteMint, "sell", 8, ...orderAccounts(program, taker, baseMint, quoteMint));
+    await crank(maker);
+    const takerBase = await balances(baseMint, taker);
+
+    try {
+      await crank(taker);
+      expect.fail("crank should have failed");
+    } catch (error) {
+      expect(error.message).to.include("InconsistentUserKey");
+    }
+
+    const keeperLamports = await provider.connection.getBalance(keeper.publicKey);
+    await crank(maker, false, false);
+    expect(await balances(baseMint, taker)).to.deep.equal({
+      available: takerBase.available + 2,
+      locked: takerBase.locked - 2,
+    });
+    // Rollbacks move no counterparty funds and earn no reward
+    expect(await provider.connection.getBalance(keeper.publicKey)).to.equal(keeperLamports);
+  });
+
+  it("only lets the admin change the keeper reward", async () => {
+    const setReward = (signer: Keypair, reward: number) =>
+      program.methods
+        .setCrankReward(baseMint, quoteMint, new anchor.BN(reward))
+        .accountsPartial({ crankPool, admin: signer.publicKey })
+        .signers([signer])
+        .rpc();
+    try {
+      await setReward(keeper, REWARD * 100);
+      expect.fail("only the admin may change the reward");
+    } catch (error) {
+      expect(error.message).to.include("Unauthorized");
+    }
+
+    await setReward(admin, REWARD * 2);
+    const pool = await program.account.crankPool.fetch(crankPool);
+    expect(pool.rewardPerEvent.toNumber()).to.equal(REWARD * 2);
+  });
+});
@@ -0,0 +1,208 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { RustDex } from "../target/types/rust_dex";
+import { PublicKey, Keypair, SystemProgram } from "@solana/web3.js";
+import { expect } from "chai";
+import {
+  createFundedUser,
+  createTokenMint,
+  registerVaultTokenLedger,
+  registerUser,
+  feeLedgerPda,
+  placeLimitOrder,
+  dexAdmin,
+  marketEventsPda,
+  crankPoolPda,
+  marketPda,
+  expectError,
+  fundUser,
+  ledgerPda,
+  pairPda,
+  registerTokenPair,
+  resetDexManager,
+  userPda,
+  orderAccounts,
+} from "./test-utils";
+
+describe("rust-dex: delisting", () => {
+  anchor.setProvider(anchor.AnchorProvider.env());
+  const program = anchor.workspace.rustDex as Program<RustDex>;
+  const provider = anchor.getProvider() as anchor.AnchorProvider;
+
+  let mintAuthority: Keypair;
+  let admin: Keypair;
+  let maker: Keypair;
+  let taker: Keypair;
+  let keeper: Keypair;
+
+  let baseMint: PublicKey;
+  let quoteMint: PublicKey;
+  let dexManagerPda: PublicKey;
+
+  const INITIAL_AMOUNT = 1000000;
+  const DEPOSIT_AMOUNT = 100000;
+
+  type Status = "active" | "cancelOnly" | "postOnly" | "halted" | "delisted";
+
+  function setMarketStatus(signer: Keypair, status: Status) {
+    return program.methods
+      .setMarketStatus(baseMint, quoteMint, { [status]: {} } as any)
+      .accountsPartial({ market: marketPda(program, baseMint, quoteMint), dexManager: dexManagerPda, admin: signer.publicKey })
+      .signers([signer])
+      .rpc();
+  }
+
+  function closeMarket(signer: Keypair) {
+    return program.methods
+      .closeMarket(baseMint, quoteMint)
+      .accountsPartial({
+        market: marketPda(program, baseMint, quoteMint), marketEvents: marketEventsPda(program, baseMint, quoteMint), crankPool: crankPoolPda(program, baseMint, quoteMint),
+        dexManager: dexManagerPda, admin: signer.publicKey,
+      })
+      .signers([signer])
+      .rpc();
+  }
+
+  // Bids lock quote and asks lock base, so these are the ledgers the crank unlocks,
+  // each passed with its owner's orderbook
+  function cancelDelistedOrders(owners: [PublicKey, Keypair][]) {
+    const accounts = owners.flatMap(([mint, owner]) => [ledgerPda(program, mint, owner), userPda(program, "user_orderbook", owner)]);
+    return program.methods
+      .cancelDelistedOrders(baseMint, quoteMint)
+      .accountsPartial({ market: marketPda(program, baseMint, quoteMint), marketEvents: marketEventsPda(program, baseMint, quoteMint), dexManager: dexManagerPda, keeper: keeper.publicKey })
+      .remainingAccounts(accounts.map((pubkey) => ({ pubkey, isSigner: false, isWritable: true })))
+      .signers([keeper])
+      .rpc();
+  }
+
+  function market() {
+    return program.account.market.fetch(marketPda(program, baseMint, quoteMint));
+  }
+
+  async function bookSize() {
+    const bids = await program.account.tokenPairAccount.fetch(pairPda(program, baseMint, quoteMint));
+    const asks = await program.account.tokenPairAccount.fetch(pairPda(program, quoteMint, baseMint));
+    return bids.orderHeap.size.toNumber() + asks.orderHeap.size.toNumber();
+  }
+
+  function lockedBalance(mint: PublicKey, user: Keypair) {
+    return program.account.individualTokenLedgerAccount
+      .fetch(ledgerPda(program, mint, user))
+      .then((ledger) => ledger.lockedBalance.toNumber());
+  }
+
+  // The taker sold base for quote, so its income is quote and its outcome base
+  function consumeTakerEvent() {
+    return program.methods
+      .consumeEvents(maker.publicKey)
+      .accountsPartial({
+        eventList: userPda(program, "order_events", taker),
+        userTokenIncomeLedger: ledgerPda(program, quoteMint, taker),
+        userTokenOutcomeLedger: ledgerPda(program, baseMint, taker),
+        oppositeUserTokenIncomeLedger: ledgerPda(program, baseMint, maker),
+        oppositeUserTokenOutcomeLedger: ledgerPda(program, quoteMint, maker),
+        incomeFeeLedger: feeLedgerPda(program, quoteMint),
+        outcomeFeeLedger: feeLedgerPda(program, baseMint),
+        market: marketPda(program, baseMint, quoteMint),
+        user: taker.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([taker])
+      .rpc();
+  }
+
+  before(async () => {
+    mintAuthority = await createFundedUser(provider);
+    admin = dexAdmin(provider);
+    maker = await createFundedUser(provider);
+    taker = await createFundedUser(provider);
+    keeper = await createFundedUser(provider);
+
+    baseMint = await createTokenMint(provider.connection, mintAuthority, 9);
+    quoteMint = await createTokenMint(provider.connection, mintAuthority, 6);
+
+    dexManagerPda = await resetDexManager(program, admin);
+
+    await registerTokenPair(program, admin, baseMint, quoteMint);
+
+    await registerUser(program, maker);
+    await registerUser(program, taker);
+    for (const mint of [baseMint, quoteMint]) {
+      const vaultAccounts = await registerVaultTokenLedger(program, admin, mint);
+      for (const user of [maker, taker]) {
+        await fundUser(program, mintAuthority, user, mint, vaultAccounts, INITIAL_AMOUNT, DEPOSIT_AMOUNT);
+      }
+    }
+  });
+
+  it("counts fills queued for settlement", async () => {
+    await placeLimitOrder(program, maker, baseMint, quoteMint, "buy", 1, 1000, ...orderAccounts(program, maker, baseMint, quoteMint));
+    await placeLimitOrder(program, taker, baseMint, quoteMint, "sell", 2, 1000, ...orderAccounts(program, taker, baseMint, quoteMint));
+    // Fills half of the maker's bid, the fill waits in the taker's event list
+    await placeLimitOrder(program, taker, baseMint, quoteMint, "sell", 1, 500, ...orderAccounts(program, taker, baseMint, quoteMint));
+
+    expect((await market()).pendingFills.toNumber()).to.equal(1);
+  });
+
+  it("makes delisting final", async () => {
+    await expectError(cancelDelistedOrders([[quoteMint, maker]]), "MarketNotDelisted");
+    await expectError(setMarketStatus(maker, "delisted"), "Unauthorized");
+    await setMarketStatus(admin, "delisted");
+
+    await expectError(
+      placeLimitOrder(program, maker, baseMint, quoteMint, "buy", 1, 1000, ...orderAccounts(program, maker, baseMint, quoteMint)),
+      "MarketNotAcceptingOrders"
+    );
+    await expectError(setMarketStatus(admin, "active"), "MarketDelisted");
+  });
+
+  it("keeps the market open until its fills settle and its book is empty", async () => {
+    await expectError(closeMarket(maker), "Unauthorized");
+    await expectError(closeMarket(admin), "MarketHasPendingFills");
+
+    await consumeTakerEvent();
+    expect((await market()).pendingFills.toNumber()).to.equal(0);
+    await expectError(closeMarket(admin), "MarketNotEmpty");
+  });
+
+  it("lets anyone cancel the orders left on the book", async () => {
+    expect(await bookSize()).to.equal(2);
+    expect(await lockedBalance(quoteMint, maker)).to.equal(500);
+    expect(await lockedBalance(baseMint, taker)).to.equal(1000);
+
+    // Only orders whose owner's ledger is passed are cancelled
+    await cancelDelistedOrders([[quoteMint, maker]]);
+    expect(await bookSize()).to.equal(1);
+    await cancelDelistedOrders([[baseMint, taker]]);
+    expect(await bookSize()).to.equal(0);
+
+    for (const owner of [maker, taker]) {
+      const orderbook = await program.account.userOrderbook.fetch(userPda(program, "user_orderbook", owner));
+      expect(orderbook.nextIndex).to.equal(0);
+    }
+
+    expect(await lockedBalance(quoteMint, maker)).to.equal(0);
+    expect(await lockedBalance(baseMint, taker)).to.equal(0);
+    const makerQuote = await program.account.individualTokenLedgerAccount.fetch(ledgerPda(program, quoteMint, maker));
+    expect(makerQuote.availableBalance.toNumber()).to.equal(DEPOSIT_AMOUNT - 500);
+  });
+
+  it("closes the market and refunds its rent", async () => {
+    const accounts = [
+      pairPda(program, baseMint, quoteMint), pairPda(program, quoteMint, baseMint), marketPda(program, baseMint, quoteMint), marketEventsPda(program, baseMint, quoteMint),
+    ];
+    let rent = 0;
+    for (const account of accounts) {
+      rent += await provider.connection.getBalance(account);
+    }
+    const before = await provider.connection.getBalance(admin.publicKey);
+
+    await closeMarket(admin);
+
+    for (const account of accounts) {
+      expect(await provider.connection.getAccountInfo(account)).to.equal(null);
+    }
+    // The admin paid the transaction fee out of the refund
+    expect(await provider.connection.getBalance(admin.publicKey)).to.be.greaterThan(before + rent - 10_000);
+  });
+});
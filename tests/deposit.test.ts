@@ -11,7 +11,9 @@ import {
   registerVaultTokenLedger,
   registerUser,
   registerUserTokenLedger,
-  depositTokens
+  depositTokens,
+  dexAdmin,
+  ensureDexManager
 } from "./test-utils";
 
 describe("rust-dex: deposit", () => {
@@ -21,7 +23,7 @@ describe("rust-dex: deposit", () => {
 
   let mint: PublicKey;
   let user: Keypair;
-  let vault: Keypair;
+  let admin: Keypair;
   let mintAuthority: Keypair;
   let userTokenAccount: PublicKey;
   let vaultTokenAccount: PublicKey;
@@ -32,12 +34,13 @@ describe("rust-dex: deposit", () => {
   before(async () => {
     mintAuthority = await createFundedUser(provider);
     user = await createFundedUser(provider);
-    vault = await createFundedUser(provider);
+    admin = dexAdmin(provider);
+    await ensureDexManager(program, admin);
 
     mint = await createTokenMint(provider.connection, mintAuthority);
     userTokenAccount = await createUserTokenAccount(provider.connection, user, mint);
     
-    const vaultResult = await registerVaultTokenLedger(program, vault, mint);
+    const vaultResult = await registerVaultTokenLedger(program, admin, mint);
     vaultTokenAccount = vaultResult.vaultTokenAccount;
     vaultTokenLedgerPda = vaultResult.vaultTokenLedgerPda;
     
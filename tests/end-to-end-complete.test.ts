@@ -9,6 +9,7 @@ import {
 } from "@solana/spl-token";
 import { PublicKey, Keypair, SystemProgram } from "@solana/web3.js";
 import { expect } from "chai";
+import { dexAdmin, marketEventsPda, marketPda } from "./test-utils";
 
 describe("rust-dex: 完整端到端测试", () => {
   anchor.setProvider(anchor.AnchorProvider.env());
@@ -19,7 +20,7 @@ describe("rust-dex: 完整端到端测试", () => {
   let mintAuthority: Keypair;
   let user1: Keypair;
   let user2: Keypair;
-  let vault: Keypair;
+  let admin: Keypair;
 
   // 代币
   let token1Mint: PublicKey; // 基础代币 (9 decimals)
@@ -40,6 +41,12 @@ describe("rust-dex: 完整端到端测试", () => {
   let token1Token2QueuePda: PublicKey; // token1/token2 交易对
   let token2Token1QueuePda: PublicKey; // token2/token1 交易对
 
+  // 代币的手续费账本
+  const feeLedgerPda = (mint: PublicKey) => PublicKey.findProgramAddressSync(
+    [Buffer.from("fee_ledger"), mint.toBuffer()],
+    program.programId
+  )[0];
+
   it("完整交易流程测试", async () => {
     console.log("🚀 开始完整的DEX交易流程测试");
 
@@ -50,10 +57,10 @@ describe("rust-dex: 完整端到端测试", () => {
     mintAuthority = Keypair.generate();
     user1 = Keypair.generate();
     user2 = Keypair.generate();
-    vault = Keypair.generate();
+    admin = dexAdmin(provider);
 
     // 为账户充值SOL
-    for (const user of [mintAuthority, user1, user2, vault]) {
+    for (const user of [mintAuthority, user1, user2]) {
       const signature = await provider.connection.requestAirdrop(
         user.publicKey,
         20 * anchor.web3.LAMPORTS_PER_SOL
@@ -173,10 +180,10 @@ describe("rust-dex: 完整端到端测试", () => {
       await program.methods.closeDexManager()
         .accountsPartial({
           dexManager: dexManagerPda,
-          user: user1.publicKey,
+          admin: admin.publicKey,
           systemProgram: SystemProgram.programId,
         })
-        .signers([user1])
+        .signers([admin])
         .rpc();
     } catch (error) {
       console.log("没有已存在的dex_manager需要关闭");
@@ -186,10 +193,10 @@ describe("rust-dex: 完整端到端测试", () => {
     await program.methods.initialize()
       .accountsPartial({
         dexManager: dexManagerPda,
-        user: user1.publicKey,
+        user: admin.publicKey,
         systemProgram: SystemProgram.programId,
       })
-      .signers([user1])
+      .signers([admin])
       .rpc();
 
     console.log("✅ DEX管理器初始化完成");
@@ -218,11 +225,11 @@ describe("rust-dex: 完整端到端测试", () => {
         vaultTokenAuthority: vaultToken1AuthorityPda,
         mintAccount: token1Mint,
         vaultTokenAccount: vaultToken1Account,
-        user: vault.publicKey,
+        admin: admin.publicKey,
         systemProgram: SystemProgram.programId,
         tokenProgram: TOKEN_PROGRAM_ID,
       })
-      .signers([vault, vaultToken1AccountKeypair])
+      .signers([admin, vaultToken1AccountKeypair])
       .rpc();
 
     // 注册token2金库
@@ -246,11 +253,11 @@ describe("rust-dex: 完整端到端测试", () => {
         vaultTokenAuthority: vaultToken2AuthorityPda,
         mintAccount: token2Mint,
         vaultTokenAccount: vaultToken2Account,
-        user: vault.publicKey,
+        admin: admin.publicKey,
         systemProgram: SystemProgram.programId,
         tokenProgram: TOKEN_PROGRAM_ID,
       })
-      .signers([vault, vaultToken2AccountKeypair])
+      .signers([admin, vaultToken2AccountKeypair])
       .rpc();
 
     console.log("✅ 代币金库注册完成");
@@ -271,12 +278,14 @@ describe("rust-dex: 完整端到端测试", () => {
     await program.methods
       .registerTokenPair(token1Mint, token2Mint)
       .accountsPartial({
-        user: user1.publicKey,
+        market: marketPda(program, token1Mint, token2Mint),
+        marketEvents: marketEventsPda(program, token1Mint, token2Mint),
+        admin: admin.publicKey,
         systemProgram: SystemProgram.programId,
         tokenPair: token1Token2QueuePda,
         oppositePair: token2Token1QueuePda,
       })
-      .signers([user1])
+      .signers([admin])
       .rpc();
 
     console.log("✅ 交易对注册完成");
@@ -301,7 +310,7 @@ describe("rust-dex: 完整端到端测试", () => {
     );
 
     await program.methods
-      .registerUser()
+      .registerUser(null)
       .accountsPartial({
         individualLedger: user1LedgerPda,
         userOrderBook: user1OrderbookPda,
@@ -329,7 +338,7 @@ describe("rust-dex: 完整端到端测试", () => {
     );
 
     await program.methods
-      .registerUser()
+      .registerUser(null)
       .accountsPartial({
         individualLedger: user2LedgerPda,
         userOrderBook: user2OrderbookPda,
@@ -500,8 +509,10 @@ describe("rust-dex: 完整端到端测试", () => {
     const sellPrice = 1; // 1 token2 per token1
 
     await program.methods
-      .placeLimitOrder(token1Mint, token2Mint, "sell", sellPrice, new anchor.BN(sellAmount))
+      .placeLimitOrder(token1Mint, token2Mint, { sell: {} }, sellPrice, new anchor.BN(sellAmount))
       .accountsPartial({
+        market: marketPda(program, token1Mint, token2Mint),
+        marketEvents: marketEventsPda(program, token1Mint, token2Mint),
         baseQuoteQueue: token1Token2QueuePda,
         quoteBaseQueue: token2Token1QueuePda,
         dexManager: dexManagerPda,
@@ -523,8 +534,10 @@ describe("rust-dex: 完整端到端测试", () => {
     const buyAmount = 10 * 10 ** 9; // 10 token1
 
     await program.methods
-      .placeMarketOrder(token1Mint, token2Mint, "buy", new anchor.BN(buyAmount))
+      .placeMarketOrder(token1Mint, token2Mint, { buy: {} }, new anchor.BN(buyAmount))
       .accountsPartial({
+        market: marketPda(program, token1Mint, token2Mint),
+        marketEvents: marketEventsPda(program, token1Mint, token2Mint),
         baseQuoteQueue: token1Token2QueuePda,
         quoteBaseQueue: token2Token1QueuePda,
         dexManager: dexManagerPda,
@@ -552,6 +565,9 @@ describe("rust-dex: 完整端到端测试", () => {
         userTokenOutcomeLedger: user1Token1LedgerPda,   // 用户1支出token1
         oppositeUserTokenIncomeLedger: user2Token1LedgerPda, // 用户2收入token1
         oppositeUserTokenOutcomeLedger: user2Token2LedgerPda, // 用户2支出token2
+        incomeFeeLedger: feeLedgerPda(token2Mint),
+        outcomeFeeLedger: feeLedgerPda(token1Mint),
+        market: marketPda(program, token1Mint, token2Mint),
         user: user1.publicKey,
         systemProgram: SystemProgram.programId,
       })
@@ -567,6 +583,9 @@ describe("rust-dex: 完整端到端测试", () => {
         userTokenOutcomeLedger: user2Token2LedgerPda,   // 用户2支出token2
         oppositeUserTokenIncomeLedger: user1Token2LedgerPda, // 用户1收入token2
         oppositeUserTokenOutcomeLedger: user1Token1LedgerPda, // 用户1支出token1
+        incomeFeeLedger: feeLedgerPda(token1Mint),
+        outcomeFeeLedger: feeLedgerPda(token2Mint),
+        market: marketPda(program, token1Mint, token2Mint),
         user: user2.publicKey,
         systemProgram: SystemProgram.programId,
       })
@@ -0,0 +1,183 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { RustDex } from "../target/types/rust_dex";
+import { PublicKey, Keypair, SystemProgram } from "@solana/web3.js";
+import { expect } from "chai";
+import {
+  createFundedUser,
+  createTokenMint,
+  registerVaultTokenLedger,
+  registerUser,
+  feeLedgerPda,
+  feeTiersPda,
+  placeLimitOrder,
+  dexAdmin,
+  marketPda,
+  fundUser,
+  ledgerPda,
+  registerTokenPair,
+  resetDexManager,
+  userPda,
+  orderAccounts,
+} from "./test-utils";
+
+describe("rust-dex: fee-tiers", () => {
+  anchor.setProvider(anchor.AnchorProvider.env());
+  const program = anchor.workspace.rustDex as Program<RustDex>;
+  const provider = anchor.getProvider() as anchor.AnchorProvider;
+
+  let mintAuthority: Keypair;
+  let admin: Keypair;
+  let maker: Keypair;
+  let taker: Keypair;
+
+  let baseMint: PublicKey;
+  let quoteMint: PublicKey;
+  let dexManagerPda: PublicKey;
+
+  const INITIAL_AMOUNT = 1000000;
+  const DEPOSIT_AMOUNT = 100000;
+  const MAKER_FEE_BPS = 10;
+  const TAKER_FEE_BPS = 25;
+  // Past 1000 quote traded, makers earn a 10 bps rebate and takers pay 20 bps
+  const TIERS = [
+    { minVolume: new anchor.BN(1000), makerDiscountBps: 20, takerDiscountBps: 5 },
+  ];
+
+  async function ledger(mint: PublicKey, user: Keypair) {
+    return await program.account.individualTokenLedgerAccount.fetch(ledgerPda(program, mint, user));
+  }
+
+  async function initFeeTiers(signer: Keypair, tiers: typeof TIERS) {
+    await program.methods
+      .initFeeTiers(quoteMint, tiers)
+      .accountsPartial({
+        feeTiers: feeTiersPda(program, quoteMint),
+        dexManager: dexManagerPda,
+        admin: signer.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([signer])
+      .rpc();
+  }
+
+  // The taker sells base for quote against the maker's resting buy
+  async function fill() {
+    await placeLimitOrder(program, maker, baseMint, quoteMint, "buy", 1, 10000, ...orderAccounts(program, maker, baseMint, quoteMint));
+    const signature = await placeLimitOrder(program, taker, baseMint, quoteMint, "sell", 1, 10000, ...orderAccounts(program, taker, baseMint, quoteMint));
+    await program.methods
+      .consumeEvents(maker.publicKey)
+      .accountsPartial({
+        eventList: userPda(program, "order_events", taker),
+        userTokenIncomeLedger: ledgerPda(program, quoteMint, taker),
+        userTokenOutcomeLedger: ledgerPda(program, baseMint, taker),
+        oppositeUserTokenIncomeLedger: ledgerPda(program, baseMint, maker),
+        oppositeUserTokenOutcomeLedger: ledgerPda(program, quoteMint, maker),
+        incomeFeeLedger: feeLedgerPda(program, quoteMint),
+        outcomeFeeLedger: feeLedgerPda(program, baseMint),
+        market: marketPda(program, baseMint, quoteMint),
+        user: taker.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([taker])
+      .rpc();
+    return signature;
+  }
+
+  before(async () => {
+    mintAuthority = await createFundedUser(provider);
+    admin = dexAdmin(provider);
+    maker = await createFundedUser(provider);
+    taker = await createFundedUser(provider);
+
+    baseMint = await createTokenMint(provider.connection, mintAuthority, 9);
+    quoteMint = await createTokenMint(provider.connection, mintAuthority, 6);
+
+    dexManagerPda = await resetDexManager(program, admin);
+
+    await registerTokenPair(program, admin, baseMint, quoteMint);
+    await program.methods
+      .setMarketFees(baseMint, quoteMint, MAKER_FEE_BPS, TAKER_FEE_BPS)
+      .accountsPartial({
+        market: marketPda(program, baseMint, quoteMint),
+        dexManager: dexManagerPda,
+        admin: admin.publicKey,
+      })
+      .signers([admin])
+      .rpc();
+
+    await registerUser(program, maker);
+    await registerUser(program, taker);
+    for (const mint of [baseMint, quoteMint]) {
+      const { vaultTokenAccount, vaultTokenLedgerPda } = await registerVaultTokenLedger(program, admin, mint);
+      for (const user of [maker, taker]) {
+        await fundUser(program, mintAuthority, user, mint, { vaultTokenAccount, vaultTokenLedgerPda }, INITIAL_AMOUNT, DEPOSIT_AMOUNT);
+      }
+    }
+  });
+
+  it("only lets the admin set fee tiers", async () => {
+    try {
+      await initFeeTiers(maker, TIERS);
+      expect.fail("init_fee_tiers should have failed");
+    } catch (error) {
+      expect(error.message).to.include("Unauthorized");
+    }
+  });
+
+  it("rejects tiers that are not sorted by volume", async () => {
+    try {
+      await initFeeTiers(admin, [TIERS[0], TIERS[0]]);
+      expect.fail("init_fee_tiers should have failed");
+    } catch (error) {
+      expect(error.message).to.include("InvalidFeeTiers");
+    }
+  });
+
+  it("records the trailing volume of both sides as fills settle", async () => {
+    await initFeeTiers(admin, TIERS);
+    const signature = await fill();
+
+    await provider.connection.confirmTransaction(signature, "confirmed");
+    const tx = await provider.connection.getTransaction(signature, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    const parser = new anchor.EventParser(program.programId, program.coder);
+    const fills: any[] = [...parser.parseLogs(tx.meta.logMessages)].filter((event) => event.name === "orderFilledEvent");
+    // Neither side had any volume when the orders were placed
+    expect(fills[0].data.fee.toNumber()).to.equal(25);
+    expect(fills[1].data.fee.toNumber()).to.equal(10);
+    for (const user of [maker, taker]) {
+      expect((await ledger(quoteMint, user)).windowVolume.toNumber()).to.equal(10000);
+      expect((await ledger(baseMint, user)).windowVolume.toNumber()).to.equal(10000);
+    }
+  });
+
+  it("discounts the taker and pays the maker a rebate out of the taker fee", async () => {
+    const makerQuote = (await ledger(quoteMint, maker)).availableBalance.toNumber();
+    const makerBase = (await ledger(baseMint, maker)).availableBalance.toNumber();
+    const takerQuote = (await ledger(quoteMint, taker)).availableBalance.toNumber();
+    const feesBefore = await program.account.feeLedgerAccount.fetch(feeLedgerPda(program, quoteMint));
+
+    const signature = await fill();
+
+    await provider.connection.confirmTransaction(signature, "confirmed");
+    const tx = await provider.connection.getTransaction(signature, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    const parser = new anchor.EventParser(program.programId, program.coder);
+    const [takerFill, makerFill]: any[] = [...parser.parseLogs(tx.meta.logMessages)].filter((event) => event.name === "orderFilledEvent");
+    expect(takerFill.data.fee.toNumber()).to.equal(20);
+    expect(makerFill.data.fee.toNumber()).to.equal(0);
+    expect(makerFill.data.rebate.toNumber()).to.equal(10);
+
+    expect((await ledger(quoteMint, taker)).availableBalance.toNumber()).to.equal(takerQuote + 10000 - 20);
+    expect((await ledger(baseMint, maker)).availableBalance.toNumber()).to.equal(makerBase + 10000);
+    // The maker's quote was locked by the order, the rebate comes back on top
+    expect((await ledger(quoteMint, maker)).availableBalance.toNumber()).to.equal(makerQuote - 10000 + 10);
+    const feesAfter = await program.account.feeLedgerAccount.fetch(feeLedgerPda(program, quoteMint));
+    expect(feesAfter.accruedFees.toNumber() - feesBefore.accruedFees.toNumber()).to.equal(10);
+  });
+});
@@ -0,0 +1,199 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { RustDex } from "../target/types/rust_dex";
+import { getAccount, TOKEN_PROGRAM_ID } from "@solana/spl-token";
+import { PublicKey, Keypair, SystemProgram } from "@solana/web3.js";
+import { expect } from "chai";
+import {
+  createFundedUser,
+  createTokenMint,
+  createUserTokenAccount,
+  registerVaultTokenLedger,
+  registerUser,
+  feeLedgerPda,
+  placeLimitOrder,
+  dexAdmin,
+  marketPda,
+  fundUser,
+  ledgerPda,
+  registerTokenPair,
+  resetDexManager,
+  userPda,
+  orderAccounts,
+} from "./test-utils";
+
+describe("rust-dex: fees", () => {
+  anchor.setProvider(anchor.AnchorProvider.env());
+  const program = anchor.workspace.rustDex as Program<RustDex>;
+  const provider = anchor.getProvider() as anchor.AnchorProvider;
+
+  let mintAuthority: Keypair;
+  let admin: Keypair;
+  let maker: Keypair;
+  let taker: Keypair;
+
+  let baseMint: PublicKey;
+  let quoteMint: PublicKey;
+  let dexManagerPda: PublicKey;
+  let adminQuoteAccount: PublicKey;
+  const vaults = new Map<string, { vaultTokenAccount: PublicKey; vaultTokenLedgerPda: PublicKey }>();
+
+  const INITIAL_AMOUNT = 1000000;
+  const DEPOSIT_AMOUNT = 100000;
+  const MAKER_FEE_BPS = 10;
+  const TAKER_FEE_BPS = 25;
+
+  async function available(mint: PublicKey, user: Keypair) {
+    const ledger = await program.account.individualTokenLedgerAccount.fetch(ledgerPda(program, mint, user));
+    return ledger.availableBalance.toNumber();
+  }
+
+  async function setMarketFees(signer: Keypair, makerFeeBps: number, takerFeeBps: number) {
+    await program.methods
+      .setMarketFees(baseMint, quoteMint, makerFeeBps, takerFeeBps)
+      .accountsPartial({
+        market: marketPda(program, baseMint, quoteMint),
+        dexManager: dexManagerPda,
+        admin: signer.publicKey,
+      })
+      .signers([signer])
+      .rpc();
+  }
+
+  async function collectFees(signer: Keypair, mint: PublicKey, destination: PublicKey) {
+    const { vaultTokenAccount, vaultTokenLedgerPda } = vaults.get(mint.toBase58());
+    await program.methods
+      .collectFees(mint)
+      .accountsPartial({
+        feeLedger: feeLedgerPda(program, mint),
+        vaultTokenLedger: vaultTokenLedgerPda,
+        vaultTokenAccount,
+        destination,
+        dexManager: dexManagerPda,
+        admin: signer.publicKey,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([signer])
+      .rpc();
+  }
+
+  before(async () => {
+    mintAuthority = await createFundedUser(provider);
+    admin = dexAdmin(provider);
+    maker = await createFundedUser(provider);
+    taker = await createFundedUser(provider);
+
+    baseMint = await createTokenMint(provider.connection, mintAuthority, 9);
+    quoteMint = await createTokenMint(provider.connection, mintAuthority, 6);
+    adminQuoteAccount = await createUserTokenAccount(provider.connection, admin, quoteMint);
+
+    dexManagerPda = await resetDexManager(program, admin);
+
+    await registerTokenPair(program, admin, baseMint, quoteMint);
+
+    await registerUser(program, maker);
+    await registerUser(program, taker);
+    for (const mint of [baseMint, quoteMint]) {
+      const vaultAccounts = await registerVaultTokenLedger(program, admin, mint);
+      vaults.set(mint.toBase58(), vaultAccounts);
+      for (const user of [maker, taker]) {
+        await fundUser(program, mintAuthority, user, mint, vaultAccounts, INITIAL_AMOUNT, DEPOSIT_AMOUNT);
+      }
+    }
+  });
+
+  it("only lets the admin set market fees", async () => {
+    try {
+      await setMarketFees(maker, MAKER_FEE_BPS, TAKER_FEE_BPS);
+      expect.fail("set_market_fees should have failed");
+    } catch (error) {
+      expect(error.message).to.include("Unauthorized");
+    }
+  });
+
+  it("rejects fee rates above the cap", async () => {
+    try {
+      await setMarketFees(admin, MAKER_FEE_BPS, 1001);
+      expect.fail("set_market_fees should have failed");
+    } catch (error) {
+      expect(error.message).to.include("InvalidFeeRate");
+    }
+  });
+
+  it("charges both sides of a fill and reports the fees", async () => {
+    await setMarketFees(admin, MAKER_FEE_BPS, TAKER_FEE_BPS);
+    const makerBase = await available(baseMint, maker);
+    const takerQuote = await available(quoteMint, taker);
+
+    await placeLimitOrder(program, maker, baseMint, quoteMint, "buy", 1, 10000, ...orderAccounts(program, maker, baseMint, quoteMint));
+    const signature = await placeLimitOrder(program, taker, baseMint, quoteMint, "sell", 1, 10000, ...orderAccounts(program, taker, baseMint, quoteMint));
+
+    await provider.connection.confirmTransaction(signature, "confirmed");
+    const tx = await provider.connection.getTransaction(signature, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    const parser = new anchor.EventParser(program.programId, program.coder);
+    const fills: any[] = [...parser.parseLogs(tx.meta.logMessages)].filter((event) => event.name === "orderFilledEvent");
+    const [takerFill, makerFill] = fills;
+    expect(takerFill.data.fee.toNumber()).to.equal(25);
+    expect(takerFill.data.feeMint.toBase58()).to.equal(quoteMint.toBase58());
+    expect(makerFill.data.fee.toNumber()).to.equal(10);
+    expect(makerFill.data.feeMint.toBase58()).to.equal(baseMint.toBase58());
+
+    // The taker sold base for quote, so its income is quote and the maker's base
+    await program.methods
+      .consumeEvents(maker.publicKey)
+      .accountsPartial({
+        eventList: userPda(program, "order_events", taker),
+        userTokenIncomeLedger: ledgerPda(program, quoteMint, taker),
+        userTokenOutcomeLedger: ledgerPda(program, baseMint, taker),
+        oppositeUserTokenIncomeLedger: ledgerPda(program, baseMint, maker),
+        oppositeUserTokenOutcomeLedger: ledgerPda(program, quoteMint, maker),
+        incomeFeeLedger: feeLedgerPda(program, quoteMint),
+        outcomeFeeLedger: feeLedgerPda(program, baseMint),
+        market: marketPda(program, baseMint, quoteMint),
+        user: taker.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([taker])
+      .rpc();
+
+    expect(await available(quoteMint, taker)).to.equal(takerQuote + 10000 - 25);
+    expect(await available(baseMint, maker)).to.equal(makerBase + 10000 - 10);
+    const quoteFees = await program.account.feeLedgerAccount.fetch(feeLedgerPda(program, quoteMint));
+    const baseFees = await program.account.feeLedgerAccount.fetch(feeLedgerPda(program, baseMint));
+    expect(quoteFees.accruedFees.toNumber()).to.equal(25);
+    expect(baseFees.accruedFees.toNumber()).to.equal(10);
+  });
+
+  it("only lets the admin collect fees", async () => {
+    try {
+      await collectFees(maker, quoteMint, adminQuoteAccount);
+      expect.fail("collect_fees should have failed");
+    } catch (error) {
+      expect(error.message).to.include("Unauthorized");
+    }
+  });
+
+  it("sends the accrued fees out of the vault", async () => {
+    const { vaultTokenLedgerPda } = vaults.get(quoteMint.toBase58());
+    const vaultBefore = await program.account.vaultTokenLedgerAccount.fetch(vaultTokenLedgerPda);
+
+    await collectFees(admin, quoteMint, adminQuoteAccount);
+
+    expect(Number((await getAccount(provider.connection, adminQuoteAccount)).amount)).to.equal(25);
+    const quoteFees = await program.account.feeLedgerAccount.fetch(feeLedgerPda(program, quoteMint));
+    expect(quoteFees.accruedFees.toNumber()).to.equal(0);
+    expect(quoteFees.collectedFees.toNumber()).to.equal(25);
+    const vaultAfter = await program.account.vaultTokenLedgerAccount.fetch(vaultTokenLedgerPda);
+    expect(vaultAfter.totalBalance.toNumber()).to.equal(vaultBefore.totalBalance.toNumber() - 25);
+
+    try {
+      await collectFees(admin, quoteMint, adminQuoteAccount);
+      expect.fail("collect_fees should have failed");
+    } catch (error) {
+      expect(error.message).to.include("NoFeesToCollect");
+    }
+  });
+});
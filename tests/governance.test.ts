@@ -0,0 +1,216 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { RustDex } from "../target/types/rust_dex";
+import {
+  PublicKey,
+  Keypair,
+  SystemProgram,
+  Transaction,
+  TransactionInstruction,
+  LAMPORTS_PER_SOL,
+} from "@solana/web3.js";
+import { expect } from "chai";
+import { createFundedUser, createTokenMint, dexAdmin, marketEventsPda, marketPda, expectError, pairPda, registerTokenPair, resetDexManager } from "./test-utils";
+
+describe("rust-dex: governance", () => {
+  anchor.setProvider(anchor.AnchorProvider.env());
+  const program = anchor.workspace.rustDex as Program<RustDex>;
+  const provider = anchor.getProvider() as anchor.AnchorProvider;
+
+  const TIMELOCK_SECS = 2;
+
+  let admin: Keypair;
+  let signers: Keypair[];
+  let outsider: Keypair;
+
+  let baseMint: PublicKey;
+  let quoteMint: PublicKey;
+
+  const [dexManagerPda] = PublicKey.findProgramAddressSync([Buffer.from("dex_manager")], program.programId);
+  const [governancePda] = PublicKey.findProgramAddressSync([Buffer.from("governance")], program.programId);
+  const [authorityPda] = PublicKey.findProgramAddressSync([Buffer.from("governance_authority")], program.programId);
+
+  const proposalPda = (id: anchor.BN) =>
+    PublicKey.findProgramAddressSync(
+      [Buffer.from("governance_proposal"), id.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    )[0];
+
+  const sleep = (ms: number) => new Promise((resolve) => setTimeout(resolve, ms));
+
+  async function propose(proposer: Keypair, ix: TransactionInstruction): Promise<anchor.BN> {
+    const { proposalCount } = await program.account.governanceConfig.fetch(governancePda);
+    await program.methods
+      .createProposal(
+        ix.keys.map((key) => ({ pubkey: key.pubkey, isSigner: key.isSigner, isWritable: key.isWritable })),
+        ix.data
+      )
+      .accountsPartial({ governance: governancePda, proposal: proposalPda(proposalCount), proposer: proposer.publicKey })
+      .signers([proposer])
+      .rpc();
+    return proposalCount;
+  }
+
+  function approve(signer: Keypair, id: anchor.BN) {
+    return program.methods
+      .approveProposal()
+      .accountsPartial({ governance: governancePda, proposal: proposalPda(id), signer: signer.publicKey })
+      .signers([signer])
+      .rpc();
+  }
+
+  function execute(id: anchor.BN, ix: TransactionInstruction) {
+    return program.methods
+      .executeProposal()
+      .accountsPartial({ governance: governancePda, proposal: proposalPda(id), governanceAuthority: authorityPda })
+      .remainingAccounts(ix.keys.map((key) => ({ pubkey: key.pubkey, isSigner: false, isWritable: key.isWritable })))
+      .rpc();
+  }
+
+  /** Approves `ix` by a second signer, waits out the timelock and executes it. */
+  async function pass(ix: TransactionInstruction) {
+    const id = await propose(signers[0], ix);
+    await approve(signers[1], id);
+    await sleep((TIMELOCK_SECS + 1) * 1000);
+    await execute(id, ix);
+    return id;
+  }
+
+  before(async () => {
+    admin = dexAdmin(provider);
+    signers = [await createFundedUser(provider), await createFundedUser(provider), await createFundedUser(provider)];
+    outsider = await createFundedUser(provider);
+
+    const mintAuthority = await createFundedUser(provider);
+    baseMint = await createTokenMint(provider.connection, mintAuthority, 9);
+    quoteMint = await createTokenMint(provider.connection, mintAuthority, 6);
+
+    await resetDexManager(program, admin);
+    await registerTokenPair(program, admin, baseMint, quoteMint);
+
+    // The governance authority pays the rent of what proposals create
+    await provider.sendAndConfirm(
+      new Transaction().add(
+        SystemProgram.transfer({ fromPubkey: admin.publicKey, toPubkey: authorityPda, lamports: LAMPORTS_PER_SOL })
+      )
+    );
+  });
+
+  it("rejects configurations whose threshold cannot be reached", async () => {
+    await expectError(
+      program.methods
+        .initGovernance(signers.map((signer) => signer.publicKey), 4, new anchor.BN(TIMELOCK_SECS))
+        .accountsPartial({ dexManager: dexManagerPda, admin: admin.publicKey })
+        .signers([admin])
+        .rpc(),
+      "InvalidGovernanceConfig"
+    );
+  });
+
+  it("hands the admin authority to a 2 of 3 governance", async () => {
+    await program.methods
+      .initGovernance(signers.map((signer) => signer.publicKey), 2, new anchor.BN(TIMELOCK_SECS))
+      .accountsPartial({ dexManager: dexManagerPda, admin: admin.publicKey })
+      .signers([admin])
+      .rpc();
+
+    const dexManager = await program.account.dexManager.fetch(dexManagerPda);
+    expect(dexManager.admin.toBase58()).to.equal(authorityPda.toBase58());
+
+    await expectError(
+      program.methods
+        .setMarketFees(baseMint, quoteMint, 5, 10)
+        .accountsPartial({ market: marketPda(program, baseMint, quoteMint), dexManager: dexManagerPda, admin: admin.publicKey })
+        .signers([admin])
+        .rpc(),
+      "Unauthorized"
+    );
+  });
+
+  it("only takes proposals from governance signers", async () => {
+    const ix = await program.methods
+      .setMarketFees(baseMint, quoteMint, 5, 10)
+      .accountsPartial({ market: marketPda(program, baseMint, quoteMint), dexManager: dexManagerPda, admin: authorityPda })
+      .instruction();
+    await expectError(propose(outsider, ix), "NotGovernanceSigner");
+  });
+
+  it("changes market fees once approved and timelocked", async () => {
+    const ix = await program.methods
+      .setMarketFees(baseMint, quoteMint, 5, 10)
+      .accountsPartial({ market: marketPda(program, baseMint, quoteMint), dexManager: dexManagerPda, admin: authorityPda })
+      .instruction();
+    const id = await propose(signers[0], ix);
+
+    await expectError(execute(id, ix), "ProposalNotApproved");
+    await expectError(approve(signers[0], id), "ProposalAlreadyApproved");
+    await expectError(approve(outsider, id), "NotGovernanceSigner");
+
+    await approve(signers[2], id);
+    await expectError(execute(id, ix), "TimelockNotElapsed");
+
+    await sleep((TIMELOCK_SECS + 1) * 1000);
+    await execute(id, ix);
+    const market = await program.account.market.fetch(marketPda(program, baseMint, quoteMint));
+    expect(market.makerFeeBps).to.equal(5);
+    expect(market.takerFeeBps).to.equal(10);
+
+    await expectError(execute(id, ix), "ProposalAlreadyExecuted");
+  });
+
+  it("lists a new market through a proposal", async () => {
+    const mintAuthority = await createFundedUser(provider);
+    const newBase = await createTokenMint(provider.connection, mintAuthority, 9);
+    const ix = await program.methods
+      .registerTokenPair(newBase, quoteMint)
+      .accountsPartial({
+        market: marketPda(program, newBase, quoteMint),
+        marketEvents: marketEventsPda(program, newBase, quoteMint),
+        admin: authorityPda,
+        systemProgram: SystemProgram.programId,
+        tokenPair: pairPda(program, newBase, quoteMint),
+        oppositePair: pairPda(program, quoteMint, newBase),
+      })
+      .instruction();
+    await pass(ix);
+
+    const pair = await program.account.tokenPairAccount.fetch(pairPda(program, newBase, quoteMint));
+    expect(pair.buyToken.toBase58()).to.equal(newBase.toBase58());
+    expect(pair.sellToken.toBase58()).to.equal(quoteMint.toBase58());
+  });
+
+  it("voids pending proposals when governance is reconfigured", async () => {
+    const feeIx = await program.methods
+      .setMarketFees(baseMint, quoteMint, 0, 0)
+      .accountsPartial({ market: marketPda(program, baseMint, quoteMint), dexManager: dexManagerPda, admin: authorityPda })
+      .instruction();
+    const pending = await propose(signers[0], feeIx);
+
+    const reconfigureIx = await program.methods
+      .setGovernance(signers.slice(0, 2).map((signer) => signer.publicKey), 2, new anchor.BN(TIMELOCK_SECS))
+      .accountsPartial({ governance: governancePda, governanceAuthority: authorityPda })
+      .instruction();
+    await pass(reconfigureIx);
+
+    const governance = await program.account.governanceConfig.fetch(governancePda);
+    expect(governance.signerCount).to.equal(2);
+    await expectError(approve(signers[1], pending), "StaleProposal");
+  });
+
+  it("hands the admin authority back to the provider wallet", async () => {
+    // The other suites expect the provider wallet to be the admin
+    const ix = await program.methods
+      .proposeAdmin(admin.publicKey)
+      .accountsPartial({ dexManager: dexManagerPda, admin: authorityPda })
+      .instruction();
+    await pass(ix);
+    await program.methods
+      .acceptAdmin()
+      .accountsPartial({ dexManager: dexManagerPda, pendingAdmin: admin.publicKey })
+      .signers([admin])
+      .rpc();
+
+    const dexManager = await program.account.dexManager.fetch(dexManagerPda);
+    expect(dexManager.admin.toBase58()).to.equal(admin.publicKey.toBase58());
+  });
+});
@@ -0,0 +1,127 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { RustDex } from "../target/types/rust_dex";
+import { PublicKey, Keypair } from "@solana/web3.js";
+import { expect } from "chai";
+import {
+  createFundedUser,
+  createTokenMint,
+  registerVaultTokenLedger,
+  registerUser,
+  placeLimitOrder,
+  placeMarketOrder,
+  dexAdmin,
+  fundUser,
+  ledgerPda,
+  registerTokenPair,
+  resetDexManager,
+  orderAccounts,
+} from "./test-utils";
+
+describe("rust-dex: immediate-settlement", () => {
+  anchor.setProvider(anchor.AnchorProvider.env());
+  const program = anchor.workspace.rustDex as Program<RustDex>;
+  const provider = anchor.getProvider() as anchor.AnchorProvider;
+
+  let mintAuthority: Keypair;
+  let maker: Keypair;
+  let taker: Keypair;
+  let admin: Keypair;
+
+  let baseMint: PublicKey;
+  let quoteMint: PublicKey;
+
+  const INITIAL_AMOUNT = 1000000;
+  const DEPOSIT_AMOUNT = 100000;
+
+  async function balances(mint: PublicKey, user: Keypair) {
+    const ledger = await program.account.individualTokenLedgerAccount.fetch(ledgerPda(program, mint, user));
+    return { available: ledger.availableBalance.toNumber(), locked: ledger.lockedBalance.toNumber() };
+  }
+
+  const makerLedgers = () => [baseMint, quoteMint].map((mint) => ({
+    pubkey: ledgerPda(program, mint, maker),
+    isSigner: false,
+    isWritable: true,
+  }));
+
+  before(async () => {
+    mintAuthority = await createFundedUser(provider);
+    maker = await createFundedUser(provider);
+    taker = await createFundedUser(provider);
+    admin = dexAdmin(provider);
+
+    baseMint = await createTokenMint(provider.connection, mintAuthority, 9);
+    quoteMint = await createTokenMint(provider.connection, mintAuthority, 6);
+
+    await resetDexManager(program, admin);
+
+    await registerTokenPair(program, admin, baseMint, quoteMint);
+
+    await registerUser(program, maker);
+    await registerUser(program, taker);
+    for (const mint of [baseMint, quoteMint]) {
+      const { vaultTokenAccount, vaultTokenLedgerPda } = await registerVaultTokenLedger(program, admin, mint);
+      for (const user of [maker, taker]) {
+        await fundUser(program, mintAuthority, user, mint, { vaultTokenAccount, vaultTokenLedgerPda }, INITIAL_AMOUNT, DEPOSIT_AMOUNT);
+      }
+    }
+
+    // Maker bids 10 base at 100 quote and asks 10 base at 110 quote
+    for (const [side, price] of [["buy", 100], ["sell", 110]] as const) {
+      await placeLimitOrder(program, maker, baseMint, quoteMint, side, price, 10, ...orderAccounts(program, maker, baseMint, quoteMint));
+    }
+  });
+
+  it("settles a crossing limit order in the same instruction", async () => {
+    const takerBase = await balances(baseMint, taker);
+    const takerQuote = await balances(quoteMint, taker);
+    const makerBase = await balances(baseMint, maker);
+    const makerQuote = await balances(quoteMint, maker);
+
+    await placeLimitOrder(program, taker, baseMint, quoteMint, "sell", 100, 4, ...orderAccounts(program, taker, baseMint, quoteMint), makerLedgers());
+
+    expect(await balances(baseMint, taker)).to.deep.equal({
+      available: takerBase.available - 4,
+      locked: takerBase.locked,
+    });
+    expect((await balances(quoteMint, taker)).available).to.equal(takerQuote.available + 400);
+    expect((await balances(baseMint, maker)).available).to.equal(makerBase.available + 4);
+    expect((await balances(quoteMint, maker)).locked).to.equal(makerQuote.locked - 400);
+  });
+
+  it("settles a market sell without consume_events", async () => {
+    const takerBase = await balances(baseMint, taker);
+    const takerQuote = await balances(quoteMint, taker);
+
+    await placeMarketOrder(program, taker, baseMint, quoteMint, "sell", 3, ...orderAccounts(program, taker, baseMint, quoteMint), makerLedgers());
+
+    expect(await balances(baseMint, taker)).to.deep.equal({
+      available: takerBase.available - 3,
+      locked: takerBase.locked,
+    });
+    expect((await balances(quoteMint, taker)).available).to.equal(takerQuote.available + 300);
+  });
+
+  it("unlocks the unused balance of a market buy", async () => {
+    const takerBase = await balances(baseMint, taker);
+    const takerQuote = await balances(quoteMint, taker);
+
+    await placeMarketOrder(program, taker, baseMint, quoteMint, "buy", 2, ...orderAccounts(program, taker, baseMint, quoteMint), makerLedgers());
+
+    expect((await balances(baseMint, taker)).available).to.equal(takerBase.available + 2);
+    expect(await balances(quoteMint, taker)).to.deep.equal({
+      available: takerQuote.available - 220,
+      locked: takerQuote.locked,
+    });
+  });
+
+  it("fails when a maker ledger is missing", async () => {
+    try {
+      await placeMarketOrder(program, taker, baseMint, quoteMint, "sell", 1, ...orderAccounts(program, taker, baseMint, quoteMint), makerLedgers().slice(1));
+      expect.fail("order should have failed");
+    } catch (error) {
+      expect(error.message).to.include("MissingCounterpartyLedger");
+    }
+  });
+});
@@ -0,0 +1,138 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { RustDex } from "../target/types/rust_dex";
+import { PublicKey, Keypair } from "@solana/web3.js";
+import { expect } from "chai";
+import {
+  createFundedUser,
+  createTokenMint,
+  registerVaultTokenLedger,
+  registerUser,
+  depositTokens,
+  placeLimitOrder,
+  placeMarketOrder,
+  dexAdmin,
+  expectError,
+  fundUser,
+  ledgerPda,
+  registerTokenPair,
+  resetDexManager,
+  userPda,
+  orderAccounts,
+} from "./test-utils";
+
+describe("rust-dex: input-validation", () => {
+  anchor.setProvider(anchor.AnchorProvider.env());
+  const program = anchor.workspace.rustDex as Program<RustDex>;
+  const provider = anchor.getProvider() as anchor.AnchorProvider;
+
+  let mintAuthority: Keypair;
+  let maker: Keypair;
+  let taker: Keypair;
+  let budgetTaker: Keypair;
+  let admin: Keypair;
+
+  let baseMint: PublicKey;
+  let quoteMint: PublicKey;
+
+  const INITIAL_AMOUNT = 1000000;
+  const DEPOSIT_AMOUNT = 100000;
+  // Enough quote for 5 of the maker's base at 110, with 50 to spare
+  const BUDGET = 600;
+
+  let takerQuoteAccount: PublicKey;
+  let quoteVault: { vaultTokenAccount: PublicKey; vaultTokenLedgerPda: PublicKey };
+
+  async function balances(mint: PublicKey, user: Keypair) {
+    const ledger = await program.account.individualTokenLedgerAccount.fetch(ledgerPda(program, mint, user));
+    return { available: ledger.availableBalance.toNumber(), locked: ledger.lockedBalance.toNumber() };
+  }
+
+  before(async () => {
+    mintAuthority = await createFundedUser(provider);
+    maker = await createFundedUser(provider);
+    taker = await createFundedUser(provider);
+    budgetTaker = await createFundedUser(provider);
+    admin = dexAdmin(provider);
+
+    baseMint = await createTokenMint(provider.connection, mintAuthority, 9);
+    quoteMint = await createTokenMint(provider.connection, mintAuthority, 6);
+
+    await resetDexManager(program, admin);
+
+    await registerTokenPair(program, admin, baseMint, quoteMint);
+
+    await registerUser(program, maker);
+    await registerUser(program, taker);
+    await registerUser(program, budgetTaker);
+    for (const mint of [baseMint, quoteMint]) {
+      const { vaultTokenAccount, vaultTokenLedgerPda } = await registerVaultTokenLedger(program, admin, mint);
+      if (mint === quoteMint) {
+        quoteVault = { vaultTokenAccount, vaultTokenLedgerPda };
+      }
+      for (const user of [maker, taker, budgetTaker]) {
+        const amount = user === budgetTaker && mint === quoteMint ? BUDGET : DEPOSIT_AMOUNT;
+        const userTokenAccount = await fundUser(
+          program, mintAuthority, user, mint, { vaultTokenAccount, vaultTokenLedgerPda }, INITIAL_AMOUNT, amount
+        );
+        if (user === taker && mint === quoteMint) {
+          takerQuoteAccount = userTokenAccount;
+        }
+      }
+    }
+
+    // Maker bids 10 base at 100 quote and asks 10 base at 110 quote
+    for (const [side, price] of [["buy", 100], ["sell", 110]] as const) {
+      await placeLimitOrder(program, maker, baseMint, quoteMint, side, price, 10, ...orderAccounts(program, maker, baseMint, quoteMint));
+    }
+  });
+
+  for (const price of [0, -1, Infinity]) {
+    it(`rejects a limit order at price ${price}`, async () => {
+      await expectError(
+        placeLimitOrder(program, taker, baseMint, quoteMint, "buy", price, 1, ...orderAccounts(program, taker, baseMint, quoteMint)),
+        "InvalidPrice"
+      );
+    });
+  }
+
+  it("rejects a limit order of zero amount", async () => {
+    await expectError(
+      placeLimitOrder(program, taker, baseMint, quoteMint, "buy", 100, 0, ...orderAccounts(program, taker, baseMint, quoteMint)),
+      "InvalidAmount"
+    );
+  });
+
+  it("rejects a limit order whose quote amount rounds to zero", async () => {
+    await expectError(
+      placeLimitOrder(program, taker, baseMint, quoteMint, "sell", 0.5, 1, ...orderAccounts(program, taker, baseMint, quoteMint)),
+      "InvalidAmount"
+    );
+  });
+
+  it("rejects a market order of zero amount", async () => {
+    await expectError(
+      placeMarketOrder(program, taker, baseMint, quoteMint, "buy", 0, ...orderAccounts(program, taker, baseMint, quoteMint)),
+      "InvalidAmount"
+    );
+  });
+
+  it("rejects a deposit of zero", async () => {
+    await expectError(
+      depositTokens(program, taker, quoteMint, 0, takerQuoteAccount, quoteVault.vaultTokenAccount, quoteVault.vaultTokenLedgerPda, ledgerPda(program, quoteMint, taker)),
+      "InvalidAmount"
+    );
+  });
+
+  it("caps a market buy at its balance and unlocks what it does not spend", async () => {
+    await placeMarketOrder(program, budgetTaker, baseMint, quoteMint, "buy", 10, ...orderAccounts(program, budgetTaker, baseMint, quoteMint));
+
+    // 5 base at 110 costs 550 and waits in the event list, the other 50 is free again
+    expect(await balances(quoteMint, budgetTaker)).to.deep.equal({ available: 50, locked: 550 });
+    const eventList = await program.account.eventList.fetch(userPda(program, "order_events", budgetTaker));
+    expect(eventList.length.toNumber()).to.equal(1);
+    const head = eventList.head.toNumber();
+    expect(eventList.buyQuantity[head].toNumber()).to.equal(5);
+    expect(eventList.sellQuantity[head].toNumber()).to.equal(550);
+  });
+});
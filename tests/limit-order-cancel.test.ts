@@ -9,6 +9,7 @@ import {
 } from "@solana/spl-token";
 import { PublicKey, Keypair, SystemProgram } from "@solana/web3.js";
 import { expect } from "chai";
+import { dexAdmin, marketEventsPda, marketPda } from "./test-utils";
 
 describe("rust-dex: 限价交易与取消订单流程", () => {
   anchor.setProvider(anchor.AnchorProvider.env());
@@ -19,7 +20,7 @@ describe("rust-dex: 限价交易与取消订单流程", () => {
   let mintAuthority: Keypair;
   let user1: Keypair;
   let user2: Keypair;
-  let vault: Keypair;
+  let admin: Keypair;
 
   // 代币
   let token1Mint: PublicKey; // 基础代币 (9 decimals)
@@ -46,6 +47,12 @@ describe("rust-dex: 限价交易与取消订单流程", () => {
   let user2Token1LedgerPda: PublicKey;
   let user2Token2LedgerPda: PublicKey;
 
+  // 代币的手续费账本
+  const feeLedgerPda = (mint: PublicKey) => PublicKey.findProgramAddressSync(
+    [Buffer.from("fee_ledger"), mint.toBuffer()],
+    program.programId
+  )[0];
+
   it("限价交易与取消订单完整流程", async () => {
     console.log("🚀 开始限价交易与取消订单流程测试");
 
@@ -55,10 +62,10 @@ describe("rust-dex: 限价交易与取消订单流程", () => {
     mintAuthority = Keypair.generate();
     user1 = Keypair.generate();
     user2 = Keypair.generate();
-    vault = Keypair.generate();
+    admin = dexAdmin(provider);
 
     // 为账户充值SOL
-    for (const user of [mintAuthority, user1, user2, vault]) {
+    for (const user of [mintAuthority, user1, user2]) {
       const signature = await provider.connection.requestAirdrop(
         user.publicKey,
         20 * anchor.web3.LAMPORTS_PER_SOL
@@ -138,10 +145,10 @@ describe("rust-dex: 限价交易与取消订单流程", () => {
       await program.methods.closeDexManager()
         .accountsPartial({
           dexManager: dexManagerPda,
-          user: user1.publicKey,
+          admin: admin.publicKey,
           systemProgram: SystemProgram.programId,
         })
-        .signers([user1])
+        .signers([admin])
         .rpc();
     } catch (error) {
       console.log("没有已存在的dex_manager需要关闭");
@@ -150,10 +157,10 @@ describe("rust-dex: 限价交易与取消订单流程", () => {
     await program.methods.initialize()
       .accountsPartial({
         dexManager: dexManagerPda,
-        user: user1.publicKey,
+        user: admin.publicKey,
         systemProgram: SystemProgram.programId,
       })
-      .signers([user1])
+      .signers([admin])
       .rpc();
 
     // ========== 5. 注册代币金库 ==========
@@ -180,11 +187,11 @@ describe("rust-dex: 限价交易与取消订单流程", () => {
         vaultTokenAuthority: vaultToken1AuthorityPda,
         mintAccount: token1Mint,
         vaultTokenAccount: vaultToken1Account,
-        user: vault.publicKey,
+        admin: admin.publicKey,
         systemProgram: SystemProgram.programId,
         tokenProgram: TOKEN_PROGRAM_ID,
       })
-      .signers([vault, vaultToken1AccountKeypair])
+      .signers([admin, vaultToken1AccountKeypair])
       .rpc();
 
     // Token2金库
@@ -208,11 +215,11 @@ describe("rust-dex: 限价交易与取消订单流程", () => {
         vaultTokenAuthority: vaultToken2AuthorityPda,
         mintAccount: token2Mint,
         vaultTokenAccount: vaultToken2Account,
-        user: vault.publicKey,
+        admin: admin.publicKey,
         systemProgram: SystemProgram.programId,
         tokenProgram: TOKEN_PROGRAM_ID,
       })
-      .signers([vault, vaultToken2AccountKeypair])
+      .signers([admin, vaultToken2AccountKeypair])
       .rpc();
 
     // ========== 6. 注册代币交易对 ==========
@@ -231,12 +238,14 @@ describe("rust-dex: 限价交易与取消订单流程", () => {
     await program.methods
       .registerTokenPair(token1Mint, token2Mint)
       .accountsPartial({
-        user: user1.publicKey,
+        market: marketPda(program, token1Mint, token2Mint),
+        marketEvents: marketEventsPda(program, token1Mint, token2Mint),
+        admin: admin.publicKey,
         systemProgram: SystemProgram.programId,
         tokenPair: token1Token2QueuePda,
         oppositePair: token2Token1QueuePda,
       })
-      .signers([user1])
+      .signers([admin])
       .rpc();
 
     // ========== 7. 注册用户 ==========
@@ -259,7 +268,7 @@ describe("rust-dex: 限价交易与取消订单流程", () => {
     );
 
     await program.methods
-      .registerUser()
+      .registerUser(null)
       .accountsPartial({
         individualLedger: user1LedgerPda,
         userOrderBook: user1OrderbookPda,
@@ -287,7 +296,7 @@ describe("rust-dex: 限价交易与取消订单流程", () => {
     );
 
     await program.methods
-      .registerUser()
+      .registerUser(null)
       .accountsPartial({
         individualLedger: user2LedgerPda,
         userOrderBook: user2OrderbookPda,
@@ -440,8 +449,10 @@ describe("rust-dex: 限价交易与取消订单流程", () => {
     const user1SellPrice = 1; // 1 token2 per token1
 
     await program.methods
-      .placeLimitOrder(token1Mint, token2Mint, "sell", user1SellPrice, new anchor.BN(user1SellAmount))
+      .placeLimitOrder(token1Mint, token2Mint, { sell: {} }, user1SellPrice, new anchor.BN(user1SellAmount))
       .accountsPartial({
+        market: marketPda(program, token1Mint, token2Mint),
+        marketEvents: marketEventsPda(program, token1Mint, token2Mint),
         baseQuoteQueue: token1Token2QueuePda,
         quoteBaseQueue: token2Token1QueuePda,
         dexManager: dexManagerPda,
@@ -464,8 +475,10 @@ describe("rust-dex: 限价交易与取消订单流程", () => {
     const user2BuyPrice = 2; // 2 token2 per token1 (高于用户1的卖价，会匹配用户1的全部订单，剩余10个token1的买单)
 
     await program.methods
-      .placeLimitOrder(token1Mint, token2Mint, "buy", user2BuyPrice, new anchor.BN(user2BuyAmount))
+      .placeLimitOrder(token1Mint, token2Mint, { buy: {} }, user2BuyPrice, new anchor.BN(user2BuyAmount))
       .accountsPartial({
+        market: marketPda(program, token1Mint, token2Mint),
+        marketEvents: marketEventsPda(program, token1Mint, token2Mint),
         baseQuoteQueue: token1Token2QueuePda,
         quoteBaseQueue: token2Token1QueuePda,
         dexManager: dexManagerPda,
@@ -553,6 +566,9 @@ describe("rust-dex: 限价交易与取消订单流程", () => {
           userTokenOutcomeLedger: user2Token2LedgerPda,   // 用户2支出token2
           oppositeUserTokenIncomeLedger: user1Token2LedgerPda, // 用户1收入token2
           oppositeUserTokenOutcomeLedger: user1Token1LedgerPda, // 用户1支出token1
+          incomeFeeLedger: feeLedgerPda(token1Mint),
+          outcomeFeeLedger: feeLedgerPda(token2Mint),
+          market: marketPda(program, token1Mint, token2Mint),
           user: user2.publicKey,
           systemProgram: SystemProgram.programId,
         })
@@ -569,6 +585,8 @@ describe("rust-dex: 限价交易与取消订单流程", () => {
       .cancelOrder(orderIdToCancel)
       .accountsPartial({
         baseQuoteQueue: token1Token2QueuePda, // 用户2的订单在token1Token2队列中
+        market: marketPda(program, token1Mint, token2Mint),
+        marketEvents: marketEventsPda(program, token1Mint, token2Mint),
         userOrderBook: user2OrderbookPda,
         userSellTokenLedger: user2Token2LedgerPda,
         user: user2.publicKey,
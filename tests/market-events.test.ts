@@ -0,0 +1,136 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { RustDex } from "../target/types/rust_dex";
+import { PublicKey, Keypair } from "@solana/web3.js";
+import { expect } from "chai";
+import {
+  createFundedUser,
+  createTokenMint,
+  registerVaultTokenLedger,
+  registerUser,
+  placeLimitOrder,
+  cancelOrder,
+  dexAdmin,
+  marketEventsPda,
+  fundUser,
+  ledgerPda,
+  pairPda,
+  registerTokenPair,
+  resetDexManager,
+  userPda,
+  orderAccounts,
+  expectError,
+} from "./test-utils";
+
+describe("rust-dex: market-events", () => {
+  anchor.setProvider(anchor.AnchorProvider.env());
+  const program = anchor.workspace.rustDex as Program<RustDex>;
+  const provider = anchor.getProvider() as anchor.AnchorProvider;
+
+  let mintAuthority: Keypair;
+  let maker: Keypair;
+  let taker: Keypair;
+  let admin: Keypair;
+
+  let baseMint: PublicKey;
+  let quoteMint: PublicKey;
+
+  const INITIAL_AMOUNT = 1000000;
+  const DEPOSIT_AMOUNT = 100000;
+
+  async function balances(mint: PublicKey, user: Keypair) {
+    const ledger = await program.account.individualTokenLedgerAccount.fetch(ledgerPda(program, mint, user));
+    return { available: ledger.availableBalance.toNumber(), locked: ledger.lockedBalance.toNumber() };
+  }
+
+  async function newEvents(fromSeqNum: number) {
+    const log = await program.account.marketEventLog.fetch(marketEventsPda(program, baseMint, quoteMint));
+    const events = [];
+    for (let i = 0; i < log.count.toNumber(); i++) {
+      const event = log.events[(log.head.toNumber() + i) % log.events.length];
+      if (event.seqNum.toNumber() >= fromSeqNum) {
+        events.push(event);
+      }
+    }
+    return { events, nextSeqNum: log.seqNum.toNumber() };
+  }
+
+  before(async () => {
+    mintAuthority = await createFundedUser(provider);
+    maker = await createFundedUser(provider);
+    taker = await createFundedUser(provider);
+    admin = dexAdmin(provider);
+
+    baseMint = await createTokenMint(provider.connection, mintAuthority, 9);
+    quoteMint = await createTokenMint(provider.connection, mintAuthority, 6);
+
+    await resetDexManager(program, admin);
+
+    await registerTokenPair(program, admin, baseMint, quoteMint);
+
+    await registerUser(program, maker);
+    await registerUser(program, taker);
+    for (const mint of [baseMint, quoteMint]) {
+      const { vaultTokenAccount, vaultTokenLedgerPda } = await registerVaultTokenLedger(program, admin, mint);
+      for (const user of [maker, taker]) {
+        await fundUser(program, mintAuthority, user, mint, { vaultTokenAccount, vaultTokenLedgerPda }, INITIAL_AMOUNT, DEPOSIT_AMOUNT);
+      }
+    }
+
+    // Maker bids 10 base at 100 quote and asks 10 base at 110 quote
+    for (const [side, price] of [["buy", 100], ["sell", 110]] as const) {
+      await placeLimitOrder(program, maker, baseMint, quoteMint, side, price, 10, ...orderAccounts(program, maker, baseMint, quoteMint));
+    }
+  });
+
+  it("records a fill with both counterparties", async () => {
+    const { nextSeqNum } = await newEvents(0);
+
+    await placeLimitOrder(program, taker, baseMint, quoteMint, "sell", 100, 4, ...orderAccounts(program, taker, baseMint, quoteMint));
+
+    const { events } = await newEvents(nextSeqNum);
+    expect(events).to.have.length(1);
+    const fill = events[0];
+    expect(fill.eventType).to.equal(0);
+    expect(fill.maker.toBase58()).to.equal(maker.publicKey.toBase58());
+    expect(fill.taker.toBase58()).to.equal(taker.publicKey.toBase58());
+    expect(fill.baseQuantity.toNumber()).to.equal(4);
+    expect(fill.quoteQuantity.toNumber()).to.equal(400);
+    expect(fill.price).to.equal(100);
+    expect(fill.takerSide).to.equal(1);
+    expect(fill.makerOut).to.equal(0);
+  });
+
+  it("records an out event when a resting order is cancelled", async () => {
+    const { nextSeqNum } = await newEvents(0);
+    const askQueue = await program.account.tokenPairAccount.fetch(pairPda(program, quoteMint, baseMint));
+    const askId = askQueue.orderHeap.orders[0].id.toNumber();
+
+    await cancelOrder(
+      program,
+      maker,
+      askId,
+      pairPda(program, quoteMint, baseMint),
+      userPda(program, "user_orderbook", maker),
+      ledgerPda(program, baseMint, maker),
+      baseMint, quoteMint
+    );
+
+    const { events } = await newEvents(nextSeqNum);
+    expect(events).to.have.length(1);
+    const out = events[0];
+    expect(out.eventType).to.equal(1);
+    expect(out.maker.toBase58()).to.equal(maker.publicKey.toBase58());
+    expect(out.makerOrderId.toNumber()).to.equal(askId);
+    expect(out.baseQuantity.toNumber()).to.equal(10);
+    expect(out.quoteQuantity.toNumber()).to.equal(1100);
+  });
+
+  it("rejects orders naming the market's mints the other way round", async () => {
+    // Quoting in the base mint would pick the base mint's fee tiers and volume
+    await expectError(
+      placeLimitOrder(program, taker, quoteMint, baseMint, "sell", 0.01, 100, ...orderAccounts(program, taker, quoteMint, baseMint)),
+      "MarketMintsMismatch"
+    );
+  });
+});
@@ -0,0 +1,225 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { RustDex } from "../target/types/rust_dex";
+import { TOKEN_PROGRAM_ID } from "@solana/spl-token";
+import { PublicKey, Keypair, SystemProgram } from "@solana/web3.js";
+import { expect } from "chai";
+import {
+  createFundedUser,
+  createTokenMint,
+  registerVaultTokenLedger,
+  registerUser,
+  feeLedgerPda,
+  placeLimitOrder,
+  placeMarketOrder,
+  cancelOrder,
+  dexAdmin,
+  Side,
+  marketPda,
+  expectError,
+  fundUser,
+  ledgerPda,
+  pairPda,
+  registerTokenPair,
+  resetDexManager,
+  userPda,
+  orderAccounts,
+} from "./test-utils";
+
+describe("rust-dex: market status", () => {
+  anchor.setProvider(anchor.AnchorProvider.env());
+  const program = anchor.workspace.rustDex as Program<RustDex>;
+  const provider = anchor.getProvider() as anchor.AnchorProvider;
+
+  let mintAuthority: Keypair;
+  let admin: Keypair;
+  let maker: Keypair;
+  let taker: Keypair;
+
+  let baseMint: PublicKey;
+  let quoteMint: PublicKey;
+  let dexManagerPda: PublicKey;
+  const vaults = new Map<string, { vaultTokenAccount: PublicKey; vaultTokenLedgerPda: PublicKey }>();
+  const userTokenAccounts = new Map<string, PublicKey>();
+
+  const INITIAL_AMOUNT = 1000000;
+  const DEPOSIT_AMOUNT = 100000;
+
+  type Status = "active" | "cancelOnly" | "postOnly" | "halted";
+
+  function setMarketStatus(signer: Keypair, status: Status) {
+    return program.methods
+      .setMarketStatus(baseMint, quoteMint, { [status]: {} } as any)
+      .accountsPartial({ market: marketPda(program, baseMint, quoteMint), dexManager: dexManagerPda, admin: signer.publicKey })
+      .signers([signer])
+      .rpc();
+  }
+
+  function setTradingPaused(signer: Keypair, paused: boolean) {
+    return program.methods
+      .setTradingPaused(paused)
+      .accountsPartial({ dexManager: dexManagerPda, admin: signer.publicKey })
+      .signers([signer])
+      .rpc();
+  }
+
+  // Bids buy base, so they rest in the [base, quote] queue and lock quote, asks the other way round
+  const sideMints = (side: Side) => side === "buy" ? [baseMint, quoteMint] : [quoteMint, baseMint];
+
+  async function restingOrderOf(user: Keypair, side: Side) {
+    const [buyMint, sellMint] = sideMints(side);
+    const queue = await program.account.tokenPairAccount.fetch(pairPda(program, buyMint, sellMint));
+    const orders = queue.orderHeap.orders.slice(0, queue.orderHeap.size.toNumber());
+    return orders.find((order) => order.owner.equals(user.publicKey));
+  }
+
+  async function cancelRestingOrder(user: Keypair, side: Side) {
+    const [buyMint, sellMint] = sideMints(side);
+    const order = await restingOrderOf(user, side);
+    return cancelOrder(
+      program, user, order.id.toNumber(), pairPda(program, buyMint, sellMint), userPda(program, "user_orderbook", user),
+      ledgerPda(program, sellMint, user), baseMint, quoteMint
+    );
+  }
+
+  // The taker sold base for quote, so its income is quote and its outcome base
+  function consumeTakerEvent() {
+    return program.methods
+      .consumeEvents(maker.publicKey)
+      .accountsPartial({
+        eventList: userPda(program, "order_events", taker),
+        userTokenIncomeLedger: ledgerPda(program, quoteMint, taker),
+        userTokenOutcomeLedger: ledgerPda(program, baseMint, taker),
+        oppositeUserTokenIncomeLedger: ledgerPda(program, baseMint, maker),
+        oppositeUserTokenOutcomeLedger: ledgerPda(program, quoteMint, maker),
+        incomeFeeLedger: feeLedgerPda(program, quoteMint),
+        outcomeFeeLedger: feeLedgerPda(program, baseMint),
+        market: marketPda(program, baseMint, quoteMint),
+        user: taker.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([taker])
+      .rpc();
+  }
+
+  before(async () => {
+    mintAuthority = await createFundedUser(provider);
+    admin = dexAdmin(provider);
+    maker = await createFundedUser(provider);
+    taker = await createFundedUser(provider);
+
+    baseMint = await createTokenMint(provider.connection, mintAuthority, 9);
+    quoteMint = await createTokenMint(provider.connection, mintAuthority, 6);
+
+    dexManagerPda = await resetDexManager(program, admin);
+
+    await registerTokenPair(program, admin, baseMint, quoteMint);
+
+    await registerUser(program, maker);
+    await registerUser(program, taker);
+    for (const mint of [baseMint, quoteMint]) {
+      const vaultAccounts = await registerVaultTokenLedger(program, admin, mint);
+      vaults.set(mint.toBase58(), vaultAccounts);
+      for (const user of [maker, taker]) {
+        const userTokenAccount = await fundUser(program, mintAuthority, user, mint, vaultAccounts, INITIAL_AMOUNT, DEPOSIT_AMOUNT);
+        userTokenAccounts.set(`${mint.toBase58()}:${user.publicKey.toBase58()}`, userTokenAccount);
+      }
+    }
+  });
+
+  it("opens new markets as active", async () => {
+    const market = await program.account.market.fetch(marketPda(program, baseMint, quoteMint));
+    expect(market.status).to.equal(0);
+    const dexManager = await program.account.dexManager.fetch(dexManagerPda);
+    expect(dexManager.paused).to.equal(false);
+  });
+
+  it("only lets the admin change the status or pause trading", async () => {
+    await expectError(setMarketStatus(maker, "halted"), "Unauthorized");
+    await expectError(setTradingPaused(maker, true), "Unauthorized");
+  });
+
+  it("only accepts cancels in cancel-only mode", async () => {
+    await placeLimitOrder(program, maker, baseMint, quoteMint, "buy", 1, 1000, ...orderAccounts(program, maker, baseMint, quoteMint));
+    await setMarketStatus(admin, "cancelOnly");
+
+    await expectError(
+      placeLimitOrder(program, maker, baseMint, quoteMint, "buy", 1, 1000, ...orderAccounts(program, maker, baseMint, quoteMint)),
+      "MarketNotAcceptingOrders"
+    );
+    await expectError(
+      placeMarketOrder(program, taker, baseMint, quoteMint, "sell", 1000, ...orderAccounts(program, taker, baseMint, quoteMint)),
+      "MarketNotAcceptingOrders"
+    );
+
+    await cancelRestingOrder(maker, "buy");
+    expect(await restingOrderOf(maker, "buy")).to.equal(undefined);
+  });
+
+  it("only accepts limit orders that rest in post-only mode", async () => {
+    await setMarketStatus(admin, "postOnly");
+    await placeLimitOrder(program, maker, baseMint, quoteMint, "buy", 1, 1000, ...orderAccounts(program, maker, baseMint, quoteMint));
+
+    await expectError(
+      placeLimitOrder(program, taker, baseMint, quoteMint, "sell", 1, 1000, ...orderAccounts(program, taker, baseMint, quoteMint)),
+      "PostOnlyWouldMatch"
+    );
+    await expectError(
+      placeMarketOrder(program, taker, baseMint, quoteMint, "sell", 1000, ...orderAccounts(program, taker, baseMint, quoteMint)),
+      "MarketNotAcceptingOrders"
+    );
+    // An ask above the bid does not match and rests
+    await placeLimitOrder(program, taker, baseMint, quoteMint, "sell", 2, 1000, ...orderAccounts(program, taker, baseMint, quoteMint));
+  });
+
+  it("freezes orders and settlement but not cancels once halted", async () => {
+    // The taker fills the maker's bid, its ask at 2 keeps resting
+    await setMarketStatus(admin, "active");
+    await placeLimitOrder(program, taker, baseMint, quoteMint, "sell", 1, 1000, ...orderAccounts(program, taker, baseMint, quoteMint));
+    await setMarketStatus(admin, "halted");
+
+    await expectError(
+      placeLimitOrder(program, maker, baseMint, quoteMint, "buy", 1, 1000, ...orderAccounts(program, maker, baseMint, quoteMint)),
+      "MarketHalted"
+    );
+    await expectError(consumeTakerEvent(), "MarketHalted");
+    await cancelRestingOrder(taker, "sell");
+    expect(await restingOrderOf(taker, "sell")).to.equal(undefined);
+
+    await setMarketStatus(admin, "active");
+    await consumeTakerEvent();
+  });
+
+  it("pauses trading on every market but keeps withdrawals open", async () => {
+    await setTradingPaused(admin, true);
+    await expectError(
+      placeLimitOrder(program, maker, baseMint, quoteMint, "buy", 1, 1000, ...orderAccounts(program, maker, baseMint, quoteMint)),
+      "TradingPaused"
+    );
+
+    const { vaultTokenAccount, vaultTokenLedgerPda } = vaults.get(quoteMint.toBase58());
+    const [vaultTokenAuthorityPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("vault_token_account"), quoteMint.toBuffer()],
+      program.programId
+    );
+    const before = await program.account.individualTokenLedgerAccount.fetch(ledgerPda(program, quoteMint, maker));
+    await program.methods.withdraw(quoteMint, new anchor.BN(100))
+      .accountsPartial({
+        vaultTokenLedger: vaultTokenLedgerPda,
+        vaultTokenAuthority: vaultTokenAuthorityPda,
+        userTokenLedger: ledgerPda(program, quoteMint, maker),
+        userTokenAccount: userTokenAccounts.get(`${quoteMint.toBase58()}:${maker.publicKey.toBase58()}`),
+        vaultTokenAccount,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        user: maker.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([maker])
+      .rpc();
+    const after = await program.account.individualTokenLedgerAccount.fetch(ledgerPda(program, quoteMint, maker));
+    expect(after.availableBalance.toNumber()).to.equal(before.availableBalance.toNumber() - 100);
+
+    await setTradingPaused(admin, false);
+    await placeLimitOrder(program, maker, baseMint, quoteMint, "buy", 1, 1000, ...orderAccounts(program, maker, baseMint, quoteMint));
+  });
+});
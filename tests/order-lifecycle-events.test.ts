@@ -0,0 +1,186 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { RustDex } from "../target/types/rust_dex";
+import { PublicKey, Keypair } from "@solana/web3.js";
+import { expect } from "chai";
+import {
+  createFundedUser,
+  createTokenMint,
+  registerVaultTokenLedger,
+  registerUser,
+  placeLimitOrder,
+  placeMarketOrder,
+  cancelOrder,
+  dexAdmin,
+  marketPda,
+  fundUser,
+  ledgerPda,
+  pairPda,
+  registerTokenPair,
+  resetDexManager,
+  userPda,
+  orderAccounts,
+} from "./test-utils";
+
+// Little-endian `anchor_lang::event::EVENT_IX_TAG`, prefixed to self-CPI event data
+const EVENT_IX_TAG = Buffer.from("e445a52e51cb9a1d", "hex");
+
+describe("rust-dex: order-lifecycle-events", () => {
+  anchor.setProvider(anchor.AnchorProvider.env());
+  const program = anchor.workspace.rustDex as Program<RustDex>;
+  const provider = anchor.getProvider() as anchor.AnchorProvider;
+
+  let mintAuthority: Keypair;
+  let maker: Keypair;
+  let taker: Keypair;
+  let admin: Keypair;
+
+  let baseMint: PublicKey;
+  let quoteMint: PublicKey;
+
+  const INITIAL_AMOUNT = 1000000;
+  const DEPOSIT_AMOUNT = 100000;
+
+  async function lifecycleEvents(signature: string) {
+    await provider.connection.confirmTransaction(signature, "confirmed");
+    const tx = await provider.connection.getTransaction(signature, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    const parser = new anchor.EventParser(program.programId, program.coder);
+    const events: any[] = [...parser.parseLogs(tx.meta.logMessages)];
+    // Builds with the `event-cpi` feature emit through inner instructions instead
+    const accountKeys = tx.transaction.message.getAccountKeys();
+    for (const { instructions } of tx.meta.innerInstructions ?? []) {
+      for (const ix of instructions) {
+        const data = Buffer.from(anchor.utils.bytes.bs58.decode(ix.data));
+        if (!accountKeys.get(ix.programIdIndex).equals(program.programId) || !data.subarray(0, 8).equals(EVENT_IX_TAG)) {
+          continue;
+        }
+        events.push(program.coder.events.decode(anchor.utils.bytes.base64.encode(data.subarray(8))));
+      }
+    }
+    for (const event of events) {
+      expect(event.data.header.version).to.equal(4);
+      expect(event.data.header.market.toBase58()).to.equal(marketPda(program, baseMint, quoteMint).toBase58());
+    }
+    return events;
+  }
+
+  function expectConsecutive(events: any[]) {
+    const seqNums = events.map((event) => event.data.header.seqNum.toNumber());
+    seqNums.forEach((seqNum, i) => expect(seqNum).to.equal(seqNums[0] + i));
+  }
+
+  before(async () => {
+    mintAuthority = await createFundedUser(provider);
+    maker = await createFundedUser(provider);
+    taker = await createFundedUser(provider);
+    admin = dexAdmin(provider);
+
+    baseMint = await createTokenMint(provider.connection, mintAuthority, 9);
+    quoteMint = await createTokenMint(provider.connection, mintAuthority, 6);
+
+    await resetDexManager(program, admin);
+
+    await registerTokenPair(program, admin, baseMint, quoteMint);
+
+    await registerUser(program, maker);
+    await registerUser(program, taker);
+    for (const mint of [baseMint, quoteMint]) {
+      const { vaultTokenAccount, vaultTokenLedgerPda } = await registerVaultTokenLedger(program, admin, mint);
+      for (const user of [maker, taker]) {
+        await fundUser(program, mintAuthority, user, mint, { vaultTokenAccount, vaultTokenLedgerPda }, INITIAL_AMOUNT, DEPOSIT_AMOUNT);
+      }
+    }
+  });
+
+  it("emits placed and rested for an order that does not cross", async () => {
+    const signature = await placeLimitOrder(program, maker, baseMint, quoteMint, "buy", 100, 10, ...orderAccounts(program, maker, baseMint, quoteMint));
+
+    const events = await lifecycleEvents(signature);
+    expect(events.map((event) => event.name)).to.deep.equal(["orderPlacedEvent", "orderRestedEvent"]);
+    expectConsecutive(events);
+    const [placed, rested] = events;
+    expect(placed.data.header.side).to.deep.equal({ buy: {} });
+    expect(placed.data.orderType).to.deep.equal({ limit: {} });
+    expect(placed.data.price).to.equal(100);
+    expect(placed.data.baseQuantity.toNumber()).to.equal(10);
+    expect(placed.data.quoteQuantity.toNumber()).to.equal(1000);
+    expect(rested.data.header.orderId.toNumber()).to.equal(placed.data.header.orderId.toNumber());
+    expect(rested.data.baseQuantity.toNumber()).to.equal(10);
+  });
+
+  it("emits a filled event for each side with the counterparty", async () => {
+    const signature = await placeLimitOrder(program, taker, baseMint, quoteMint, "sell", 100, 4, ...orderAccounts(program, taker, baseMint, quoteMint));
+
+    const events = await lifecycleEvents(signature);
+    expect(events.map((event) => event.name)).to.deep.equal(["orderPlacedEvent", "orderFilledEvent", "orderFilledEvent"]);
+    expectConsecutive(events);
+    const [placed, takerFill, makerFill] = events;
+    expect(takerFill.data.role).to.deep.equal({ taker: {} });
+    expect(takerFill.data.header.orderId.toNumber()).to.equal(placed.data.header.orderId.toNumber());
+    expect(takerFill.data.header.side).to.deep.equal({ sell: {} });
+    expect(takerFill.data.counterparty.toBase58()).to.equal(maker.publicKey.toBase58());
+    expect(takerFill.data.remainingBase.toNumber()).to.equal(0);
+
+    expect(makerFill.data.role).to.deep.equal({ maker: {} });
+    expect(makerFill.data.header.owner.toBase58()).to.equal(maker.publicKey.toBase58());
+    expect(makerFill.data.header.side).to.deep.equal({ buy: {} });
+    expect(makerFill.data.counterparty.toBase58()).to.equal(taker.publicKey.toBase58());
+    expect(makerFill.data.counterpartyOrderId.toNumber()).to.equal(placed.data.header.orderId.toNumber());
+    expect(makerFill.data.remainingBase.toNumber()).to.equal(6);
+    expect(makerFill.data.remainingQuote.toNumber()).to.equal(600);
+    for (const fill of [takerFill, makerFill]) {
+      expect(fill.data.price).to.equal(100);
+      expect(fill.data.baseQuantity.toNumber()).to.equal(4);
+      expect(fill.data.quoteQuantity.toNumber()).to.equal(400);
+      expect(fill.data.fee.toNumber()).to.equal(0);
+      expect(fill.data.rebate.toNumber()).to.equal(0);
+      expect(fill.data.referralFee.toNumber()).to.equal(0);
+    }
+  });
+
+  it("rests the remainder of a crossing order and reports the cancel", async () => {
+    const signature = await placeLimitOrder(program, taker, baseMint, quoteMint, "sell", 90, 10, ...orderAccounts(program, taker, baseMint, quoteMint));
+
+    const events = await lifecycleEvents(signature);
+    expect(events.map((event) => event.name)).to.deep.equal([
+      "orderPlacedEvent", "orderFilledEvent", "orderFilledEvent", "orderRestedEvent",
+    ]);
+    expectConsecutive(events);
+    const [placed, takerFill, , rested] = events;
+    expect(takerFill.data.remainingBase.toNumber()).to.equal(4);
+    expect(rested.data.header.orderId.toNumber()).to.equal(placed.data.header.orderId.toNumber());
+    expect(rested.data.baseQuantity.toNumber()).to.equal(4);
+    expect(rested.data.price).to.equal(90);
+
+    const cancelSignature = await cancelOrder(
+      program,
+      taker,
+      placed.data.header.orderId.toNumber(),
+      pairPda(program, quoteMint, baseMint),
+      userPda(program, "user_orderbook", taker),
+      ledgerPda(program, baseMint, taker),
+      baseMint, quoteMint
+    );
+    const [cancelled] = await lifecycleEvents(cancelSignature);
+    expect(cancelled.name).to.equal("orderCancelledEvent");
+    expect(cancelled.data.header.seqNum.toNumber()).to.be.greaterThan(rested.data.header.seqNum.toNumber());
+    expect(cancelled.data.header.side).to.deep.equal({ sell: {} });
+    expect(cancelled.data.baseQuantity.toNumber()).to.equal(4);
+    expect(cancelled.data.quoteQuantity.toNumber()).to.equal(360);
+  });
+
+  it("reports the unmatched part of a market order as rolled back", async () => {
+    const signature = await placeMarketOrder(program, taker, baseMint, quoteMint, "buy", 5, ...orderAccounts(program, taker, baseMint, quoteMint));
+
+    const events = await lifecycleEvents(signature);
+    expect(events.map((event) => event.name)).to.deep.equal(["orderPlacedEvent", "orderRolledBackEvent"]);
+    const [placed, rolledBack] = events;
+    expect(placed.data.orderType).to.deep.equal({ market: {} });
+    expect(rolledBack.data.reason).to.deep.equal({ noMatch: {} });
+    expect(rolledBack.data.header.side).to.deep.equal({ buy: {} });
+    expect(rolledBack.data.baseQuantity.toNumber()).to.equal(5);
+  });
+});
@@ -12,8 +12,12 @@ import {
   registerUser,
   registerUserTokenLedger,
   depositTokens,
+  feeLedgerPda,
   placeLimitOrder,
-  placeMarketOrder
+  placeMarketOrder,
+  dexAdmin,
+  marketEventsPda,
+  marketPda,
 } from "./test-utils";
 
 describe("rust-dex: place-order-normal", () => {
@@ -25,7 +29,7 @@ describe("rust-dex: place-order-normal", () => {
   let mintAuthority: Keypair;
   let user1: Keypair;
   let user2: Keypair;
-  let vault: Keypair;
+  let admin: Keypair;
 
   // Token related
   let baseMint: PublicKey;
@@ -62,7 +66,7 @@ describe("rust-dex: place-order-normal", () => {
     mintAuthority = await createFundedUser(provider);
     user1 = await createFundedUser(provider);
     user2 = await createFundedUser(provider);
-    vault = await createFundedUser(provider);
+    admin = dexAdmin(provider);
 
     // Create tokens
     baseMint = await createTokenMint(provider.connection, mintAuthority, 9);
@@ -145,10 +149,10 @@ describe("rust-dex: place-order-normal", () => {
       await program.methods.closeDexManager()
         .accountsPartial({
           dexManager: dexManagerPda,
-          user: user1.publicKey,
+          admin: admin.publicKey,
           systemProgram: SystemProgram.programId,
         })
-        .signers([user1])
+        .signers([admin])
         .rpc();
     } catch (error) {
       console.log("No existing dex_manager to close:", error.message);
@@ -157,29 +161,31 @@ describe("rust-dex: place-order-normal", () => {
     await program.methods.initialize()
       .accountsPartial({
         dexManager: dexManagerPda,
-        user: user1.publicKey,
+        user: admin.publicKey,
         systemProgram: SystemProgram.programId,
       })
-      .signers([user1])
+      .signers([admin])
       .rpc();
 
     // Register vault token ledgers
-    const baseVaultResult = await registerVaultTokenLedger(program, vault, baseMint);
+    const baseVaultResult = await registerVaultTokenLedger(program, admin, baseMint);
     vaultBaseTokenAccount = baseVaultResult.vaultTokenAccount;
     
-    const quoteVaultResult = await registerVaultTokenLedger(program, vault, quoteMint);
+    const quoteVaultResult = await registerVaultTokenLedger(program, admin, quoteMint);
     vaultQuoteTokenAccount = quoteVaultResult.vaultTokenAccount;
 
     // Register token pair
     await program.methods
       .registerTokenPair(baseMint, quoteMint)
       .accountsPartial({
-        user: user1.publicKey,
+        market: marketPda(program, baseMint, quoteMint),
+        marketEvents: marketEventsPda(program, baseMint, quoteMint),
+        admin: admin.publicKey,
         systemProgram: SystemProgram.programId,
         tokenPair: buyBaseQueuePda,
         oppositePair: sellBaseQueuePda,
       })
-      .signers([user1])
+      .signers([admin])
       .rpc();
 
     // Register users
@@ -216,8 +222,9 @@ describe("rust-dex: place-order-normal", () => {
     
     // 根据事件列表中的token类型来确定正确的账户映射
     const events = await program.account.eventList.fetch(eventListPda);
-    const tokenBuy = events.tokenBuy;
-    const tokenSell = events.tokenSell;
+    // consume_events settles the oldest pending event
+    const tokenBuy = events.tokenBuy[events.head.toNumber()];
+    const tokenSell = events.tokenSell[events.head.toNumber()];
     
     // 确定用户的buy和sell ledger（基于实际的token类型）
     let userBuyLedger: PublicKey;
@@ -265,6 +272,9 @@ describe("rust-dex: place-order-normal", () => {
         userTokenOutcomeLedger: userSellLedger,
         oppositeUserTokenIncomeLedger: oppBuyLedger,
         oppositeUserTokenOutcomeLedger: oppSellLedger,
+        incomeFeeLedger: feeLedgerPda(program, tokenBuy),
+        outcomeFeeLedger: feeLedgerPda(program, tokenSell),
+        market: marketPda(program, baseMint, quoteMint),
         user: user.publicKey,
         systemProgram: SystemProgram.programId,
       })
@@ -0,0 +1,199 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { RustDex } from "../target/types/rust_dex";
+import { PublicKey, Keypair } from "@solana/web3.js";
+import { expect } from "chai";
+import {
+  createFundedUser,
+  createTokenMint,
+  registerVaultTokenLedger,
+  registerUser,
+  placeLimitOrder,
+  placeMarketOrder,
+  cancelOrder,
+  dexAdmin,
+  Side,
+  marketPda,
+  expectError,
+  fundUser,
+  ledgerPda,
+  pairPda,
+  registerTokenPair,
+  resetDexManager,
+  userPda,
+  orderAccounts,
+} from "./test-utils";
+
+describe("rust-dex: price bands", () => {
+  anchor.setProvider(anchor.AnchorProvider.env());
+  const program = anchor.workspace.rustDex as Program<RustDex>;
+  const provider = anchor.getProvider() as anchor.AnchorProvider;
+
+  let mintAuthority: Keypair;
+  let admin: Keypair;
+  let maker: Keypair;
+  let taker: Keypair;
+
+  let baseMint: PublicKey;
+  let quoteMint: PublicKey;
+  let dexManagerPda: PublicKey;
+
+  const INITIAL_AMOUNT = 1000000;
+  const DEPOSIT_AMOUNT = 100000;
+  const BAND_BPS = 1000;
+  const COOLDOWN_SECS = 2;
+
+  const sleep = (ms: number) => new Promise((resolve) => setTimeout(resolve, ms));
+
+  function setPriceBand(signer: Keypair, bandBps: number, cooldownSecs: number) {
+    return program.methods
+      .setPriceBand(baseMint, quoteMint, bandBps, new anchor.BN(cooldownSecs))
+      .accountsPartial({ market: marketPda(program, baseMint, quoteMint), dexManager: dexManagerPda, admin: signer.publicKey })
+      .signers([signer])
+      .rpc();
+  }
+
+  function setReferencePrice(signer: Keypair, price: number) {
+    return program.methods
+      .setReferencePrice(baseMint, quoteMint, price)
+      .accountsPartial({ market: marketPda(program, baseMint, quoteMint), dexManager: dexManagerPda, admin: signer.publicKey })
+      .signers([signer])
+      .rpc();
+  }
+
+  function market() {
+    return program.account.market.fetch(marketPda(program, baseMint, quoteMint));
+  }
+
+  // Bids buy base, so they rest in the [base, quote] queue and lock quote, asks the other way round
+  const sideMints = (side: Side) => side === "buy" ? [baseMint, quoteMint] : [quoteMint, baseMint];
+
+  async function restingOrderOf(user: Keypair, side: Side) {
+    const [buyMint, sellMint] = sideMints(side);
+    const queue = await program.account.tokenPairAccount.fetch(pairPda(program, buyMint, sellMint));
+    const orders = queue.orderHeap.orders.slice(0, queue.orderHeap.size.toNumber());
+    return orders.find((order) => order.owner.equals(user.publicKey));
+  }
+
+  async function cancelRestingOrder(user: Keypair, side: Side) {
+    const [buyMint, sellMint] = sideMints(side);
+    const order = await restingOrderOf(user, side);
+    return cancelOrder(
+      program, user, order.id.toNumber(), pairPda(program, buyMint, sellMint), userPda(program, "user_orderbook", user),
+      ledgerPda(program, sellMint, user), baseMint, quoteMint
+    );
+  }
+
+  before(async () => {
+    mintAuthority = await createFundedUser(provider);
+    admin = dexAdmin(provider);
+    maker = await createFundedUser(provider);
+    taker = await createFundedUser(provider);
+
+    baseMint = await createTokenMint(provider.connection, mintAuthority, 9);
+    quoteMint = await createTokenMint(provider.connection, mintAuthority, 6);
+
+    dexManagerPda = await resetDexManager(program, admin);
+
+    await registerTokenPair(program, admin, baseMint, quoteMint);
+
+    await registerUser(program, maker);
+    await registerUser(program, taker);
+    for (const mint of [baseMint, quoteMint]) {
+      const vaultAccounts = await registerVaultTokenLedger(program, admin, mint);
+      for (const user of [maker, taker]) {
+        await fundUser(program, mintAuthority, user, mint, vaultAccounts, INITIAL_AMOUNT, DEPOSIT_AMOUNT);
+      }
+    }
+  });
+
+  it("takes its reference price from trades", async () => {
+    // A stale ask far above the market, resting before any band is set
+    await placeLimitOrder(program, maker, baseMint, quoteMint, "sell", 130, 10, ...orderAccounts(program, maker, baseMint, quoteMint));
+    await placeLimitOrder(program, maker, baseMint, quoteMint, "buy", 100, 10, ...orderAccounts(program, maker, baseMint, quoteMint));
+    await placeLimitOrder(program, taker, baseMint, quoteMint, "sell", 100, 10, ...orderAccounts(program, taker, baseMint, quoteMint));
+
+    expect((await market()).referencePrice).to.equal(100);
+  });
+
+  it("only lets the admin set price bands", async () => {
+    await expectError(setPriceBand(maker, BAND_BPS, COOLDOWN_SECS), "Unauthorized");
+    await expectError(setPriceBand(admin, 5001, COOLDOWN_SECS), "InvalidPriceBand");
+    await expectError(setReferencePrice(maker, 100), "Unauthorized");
+    await setPriceBand(admin, BAND_BPS, COOLDOWN_SECS);
+  });
+
+  it("rejects limit orders priced outside the band", async () => {
+    await expectError(
+      placeLimitOrder(program, taker, baseMint, quoteMint, "buy", 111, 10, ...orderAccounts(program, taker, baseMint, quoteMint)),
+      "PriceOutsideBand"
+    );
+    await expectError(
+      placeLimitOrder(program, taker, baseMint, quoteMint, "sell", 89, 10, ...orderAccounts(program, taker, baseMint, quoteMint)),
+      "PriceOutsideBand"
+    );
+  });
+
+  async function makerAsks() {
+    const queue = await program.account.tokenPairAccount.fetch(pairPda(program, quoteMint, baseMint));
+    return queue.orderHeap.orders
+      .slice(0, queue.orderHeap.size.toNumber())
+      .filter((order) => order.owner.equals(maker.publicKey))
+      .map((order) => ({ price: order.buyQuantity.toNumber() / order.sellQuantity.toNumber(), base: order.sellQuantity.toNumber() }))
+      .sort((a, b) => a.price - b.price);
+  }
+
+  it("stops at a maker priced above the band without halting the market", async () => {
+    await placeMarketOrder(program, taker, baseMint, quoteMint, "buy", 5, ...orderAccounts(program, taker, baseMint, quoteMint));
+
+    const untouched = await market();
+    expect(untouched.referencePrice).to.equal(100);
+    expect(untouched.haltedUntil.toNumber()).to.equal(0);
+    const ask = await restingOrderOf(maker, "sell");
+    expect(ask.buyQuantity.toNumber()).to.equal(1300);
+  });
+
+  it("skips makers left below the band by the reference price", async () => {
+    await placeLimitOrder(program, maker, baseMint, quoteMint, "sell", 95, 10, ...orderAccounts(program, maker, baseMint, quoteMint));
+    await setReferencePrice(admin, 110);
+    await placeLimitOrder(program, maker, baseMint, quoteMint, "sell", 105, 10, ...orderAccounts(program, maker, baseMint, quoteMint));
+
+    await placeMarketOrder(program, taker, baseMint, quoteMint, "buy", 5, ...orderAccounts(program, taker, baseMint, quoteMint));
+
+    const after = await market();
+    expect(after.referencePrice).to.equal(105);
+    expect(after.haltedUntil.toNumber()).to.equal(0);
+    expect(await makerAsks()).to.deep.equal([{ price: 95, base: 10 }, { price: 105, base: 5 }, { price: 130, base: 10 }]);
+  });
+
+  it("trips the circuit breaker on a fill priced outside the band around the last trade", async () => {
+    // 95 is back within the band around 105, and 105 is outside the band around 95
+    await placeMarketOrder(program, taker, baseMint, quoteMint, "buy", 15, ...orderAccounts(program, taker, baseMint, quoteMint));
+
+    const tripped = await market();
+    expect(tripped.referencePrice).to.equal(105);
+    expect(tripped.haltedUntil.toNumber()).to.be.greaterThan(0);
+    expect(await makerAsks()).to.deep.equal([{ price: 130, base: 10 }]);
+
+    await expectError(
+      placeLimitOrder(program, taker, baseMint, quoteMint, "buy", 100, 10, ...orderAccounts(program, taker, baseMint, quoteMint)),
+      "CircuitBreakerTripped"
+    );
+    // Cancels keep working during the cooldown
+    await cancelRestingOrder(maker, "sell");
+  });
+
+  it("reopens the market once the cooldown ends", async () => {
+    await sleep((COOLDOWN_SECS + 1) * 1000);
+    await placeLimitOrder(program, taker, baseMint, quoteMint, "buy", 100, 10, ...orderAccounts(program, taker, baseMint, quoteMint));
+  });
+
+  it("re-centres the band on a new reference price", async () => {
+    await setReferencePrice(admin, 130);
+    await expectError(
+      placeLimitOrder(program, maker, baseMint, quoteMint, "sell", 110, 10, ...orderAccounts(program, maker, baseMint, quoteMint)),
+      "PriceOutsideBand"
+    );
+    await placeLimitOrder(program, maker, baseMint, quoteMint, "sell", 125, 10, ...orderAccounts(program, maker, baseMint, quoteMint));
+  });
+});
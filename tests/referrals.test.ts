@@ -0,0 +1,173 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { RustDex } from "../target/types/rust_dex";
+import { PublicKey, Keypair, SystemProgram } from "@solana/web3.js";
+import { expect } from "chai";
+import {
+  createFundedUser,
+  createTokenMint,
+  createUserTokenAccount,
+  registerVaultTokenLedger,
+  registerUser,
+  registerUserTokenLedger,
+  feeLedgerPda,
+  placeLimitOrder,
+  dexAdmin,
+  marketPda,
+  fundUser,
+  ledgerPda,
+  registerTokenPair,
+  resetDexManager,
+  userPda,
+  orderAccounts,
+} from "./test-utils";
+
+describe("rust-dex: referrals", () => {
+  anchor.setProvider(anchor.AnchorProvider.env());
+  const program = anchor.workspace.rustDex as Program<RustDex>;
+  const provider = anchor.getProvider() as anchor.AnchorProvider;
+
+  let mintAuthority: Keypair;
+  let admin: Keypair;
+  let referrer: Keypair;
+  let maker: Keypair;
+  let taker: Keypair;
+
+  let baseMint: PublicKey;
+  let quoteMint: PublicKey;
+  let dexManagerPda: PublicKey;
+
+  const INITIAL_AMOUNT = 1000000;
+  const DEPOSIT_AMOUNT = 100000;
+  const TAKER_FEE_BPS = 25;
+  const REFERRAL_SHARE_BPS = 2000;
+
+  async function setReferralShare(signer: Keypair, shareBps: number) {
+    await program.methods
+      .setReferralShare(shareBps)
+      .accountsPartial({ dexManager: dexManagerPda, admin: signer.publicKey })
+      .signers([signer])
+      .rpc();
+  }
+
+  // The taker sold base for quote, so its income and its referrer's earnings are in quote
+  async function consumeTakerEvent(referrerLedgers: PublicKey[]) {
+    await program.methods
+      .consumeEvents(maker.publicKey)
+      .accountsPartial({
+        eventList: userPda(program, "order_events", taker),
+        userTokenIncomeLedger: ledgerPda(program, quoteMint, taker),
+        userTokenOutcomeLedger: ledgerPda(program, baseMint, taker),
+        oppositeUserTokenIncomeLedger: ledgerPda(program, baseMint, maker),
+        oppositeUserTokenOutcomeLedger: ledgerPda(program, quoteMint, maker),
+        incomeFeeLedger: feeLedgerPda(program, quoteMint),
+        outcomeFeeLedger: feeLedgerPda(program, baseMint),
+        market: marketPda(program, baseMint, quoteMint),
+        user: taker.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .remainingAccounts(referrerLedgers.map((pubkey) => ({ pubkey, isSigner: false, isWritable: true })))
+      .signers([taker])
+      .rpc();
+  }
+
+  before(async () => {
+    mintAuthority = await createFundedUser(provider);
+    admin = dexAdmin(provider);
+    referrer = await createFundedUser(provider);
+    maker = await createFundedUser(provider);
+    taker = await createFundedUser(provider);
+
+    baseMint = await createTokenMint(provider.connection, mintAuthority, 9);
+    quoteMint = await createTokenMint(provider.connection, mintAuthority, 6);
+
+    dexManagerPda = await resetDexManager(program, admin);
+
+    await registerTokenPair(program, admin, baseMint, quoteMint);
+    await program.methods
+      .setMarketFees(baseMint, quoteMint, 0, TAKER_FEE_BPS)
+      .accountsPartial({ market: marketPda(program, baseMint, quoteMint), dexManager: dexManagerPda, admin: admin.publicKey })
+      .signers([admin])
+      .rpc();
+
+    await registerUser(program, referrer);
+    await registerUser(program, maker);
+    await registerUser(program, taker, referrer.publicKey);
+    const referrerQuoteAccount = await createUserTokenAccount(provider.connection, referrer, quoteMint);
+    await registerUserTokenLedger(program, referrer, quoteMint, referrerQuoteAccount);
+    for (const mint of [baseMint, quoteMint]) {
+      const { vaultTokenAccount, vaultTokenLedgerPda } = await registerVaultTokenLedger(program, admin, mint);
+      for (const user of [maker, taker]) {
+        await fundUser(program, mintAuthority, user, mint, { vaultTokenAccount, vaultTokenLedgerPda }, INITIAL_AMOUNT, DEPOSIT_AMOUNT);
+      }
+    }
+  });
+
+  it("rejects users referring themselves", async () => {
+    const user = await createFundedUser(provider);
+    try {
+      await registerUser(program, user, user.publicKey);
+      expect.fail("register_user should have failed");
+    } catch (error) {
+      expect(error.message).to.include("InvalidReferrer");
+    }
+  });
+
+  it("copies the referrer onto the user's token ledgers", async () => {
+    const ledger = await program.account.individualTokenLedgerAccount.fetch(ledgerPda(program, quoteMint, taker));
+    expect(ledger.referrer.toBase58()).to.equal(referrer.publicKey.toBase58());
+    const makerLedger = await program.account.individualTokenLedgerAccount.fetch(ledgerPda(program, quoteMint, maker));
+    expect(makerLedger.referrer.toBase58()).to.equal(PublicKey.default.toBase58());
+  });
+
+  it("only lets the admin set the referral share, up to the cap", async () => {
+    try {
+      await setReferralShare(maker, REFERRAL_SHARE_BPS);
+      expect.fail("set_referral_share should have failed");
+    } catch (error) {
+      expect(error.message).to.include("Unauthorized");
+    }
+    try {
+      await setReferralShare(admin, 5001);
+      expect.fail("set_referral_share should have failed");
+    } catch (error) {
+      expect(error.message).to.include("InvalidReferralShare");
+    }
+  });
+
+  it("pays the referrer a share of the taker fee as the fill settles", async () => {
+    await setReferralShare(admin, REFERRAL_SHARE_BPS);
+    const takerQuote = (await program.account.individualTokenLedgerAccount.fetch(ledgerPda(program, quoteMint, taker))).availableBalance.toNumber();
+    const feesBefore = await program.account.feeLedgerAccount.fetch(feeLedgerPda(program, quoteMint));
+
+    await placeLimitOrder(program, maker, baseMint, quoteMint, "buy", 1, 10000, ...orderAccounts(program, maker, baseMint, quoteMint));
+    const signature = await placeLimitOrder(program, taker, baseMint, quoteMint, "sell", 1, 10000, ...orderAccounts(program, taker, baseMint, quoteMint));
+
+    await provider.connection.confirmTransaction(signature, "confirmed");
+    const tx = await provider.connection.getTransaction(signature, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    const parser = new anchor.EventParser(program.programId, program.coder);
+    const [takerFill, makerFill]: any[] = [...parser.parseLogs(tx.meta.logMessages)].filter((event) => event.name === "orderFilledEvent");
+    expect(takerFill.data.fee.toNumber()).to.equal(25);
+    expect(takerFill.data.referralFee.toNumber()).to.equal(5);
+    expect(makerFill.data.referralFee.toNumber()).to.equal(0);
+
+    try {
+      await consumeTakerEvent([]);
+      expect.fail("consume_events should have failed");
+    } catch (error) {
+      expect(error.message).to.include("MissingReferrerLedger");
+    }
+    await consumeTakerEvent([ledgerPda(program, quoteMint, referrer)]);
+
+    const referrerLedger = await program.account.individualTokenLedgerAccount.fetch(ledgerPda(program, quoteMint, referrer));
+    expect(referrerLedger.availableBalance.toNumber()).to.equal(5);
+    expect(referrerLedger.referralEarnings.toNumber()).to.equal(5);
+    const takerLedger = await program.account.individualTokenLedgerAccount.fetch(ledgerPda(program, quoteMint, taker));
+    expect(takerLedger.availableBalance.toNumber()).to.equal(takerQuote + 10000 - 25);
+    const feesAfter = await program.account.feeLedgerAccount.fetch(feeLedgerPda(program, quoteMint));
+    expect(feesAfter.accruedFees.toNumber() - feesBefore.accruedFees.toNumber()).to.equal(20);
+  });
+});
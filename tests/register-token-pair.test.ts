@@ -5,9 +5,10 @@ import { expect } from "chai";
 import {
   createFundedUser,
   createTokenMint,
-  registerVaultTokenLedger,
-  registerUser,
-  registerUserTokenLedger
+  dexAdmin,
+  marketEventsPda,
+  marketPda,
+  resetDexManager,
 } from "./test-utils";
 import { PublicKey, Keypair, SystemProgram } from "@solana/web3.js";
 
@@ -17,12 +18,14 @@ describe("rust-dex: register_token_pair", () => {
   const provider = anchor.getProvider() as anchor.AnchorProvider;
 
   let user: Keypair;
+  let admin: Keypair;
   let mint1: PublicKey;
   let mint2: PublicKey;
   let mintAuthority: Keypair;
 
   before(async () => {
     user = await createFundedUser(provider);
+    admin = dexAdmin(provider);
     mintAuthority = await createFundedUser(provider);
 
     mint1 = await createTokenMint(provider.connection, mintAuthority);
@@ -30,32 +33,25 @@ describe("rust-dex: register_token_pair", () => {
   });
 
   it("Is initialized!", async () => {
-    const [dexManagerPda] = PublicKey.findProgramAddressSync(
-      [Buffer.from("dex_manager")],
-      program.programId
-    );
+    await resetDexManager(program, admin);
+  });
 
+  it("Should reject a token pair registered by someone other than the admin", async () => {
     try {
-      await program.methods.closeDexManager()
+      await program.methods
+        .registerTokenPair(mint1, mint2)
         .accountsPartial({
-          dexManager: dexManagerPda,
-          user: user.publicKey,
+          market: marketPda(program, mint1, mint2),
+          marketEvents: marketEventsPda(program, mint1, mint2),
+          admin: user.publicKey,
           systemProgram: SystemProgram.programId,
         })
         .signers([user])
         .rpc();
+      expect.fail("register_token_pair should have failed");
     } catch (error) {
-      console.log("No existing dex_manager to close:", error.message);
+      expect(error.message).to.include("Unauthorized");
     }
-
-    await program.methods.initialize()
-      .accountsPartial({
-        dexManager: dexManagerPda,
-        user: user.publicKey,
-        systemProgram: SystemProgram.programId,
-      })
-      .signers([user])
-      .rpc();
   });
 
   it("Should register token pair", async () => {
@@ -72,12 +68,14 @@ describe("rust-dex: register_token_pair", () => {
     await program.methods
       .registerTokenPair(mint1, mint2)
       .accountsPartial({
-        user: user.publicKey,
+        market: marketPda(program, mint1, mint2),
+        marketEvents: marketEventsPda(program, mint1, mint2),
+        admin: admin.publicKey,
         systemProgram: SystemProgram.programId,
         tokenPair: tokenPairPda,
         oppositePair: oppositePairPda,
       })
-      .signers([user])
+      .signers([admin])
       .rpc();
 
     const tokenPairAccount = await program.account.tokenPairAccount.fetch(tokenPairPda);
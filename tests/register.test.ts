@@ -8,7 +8,9 @@ import {
   createUserTokenAccount,
   registerVaultTokenLedger,
   registerUser,
-  registerUserTokenLedger
+  registerUserTokenLedger,
+  dexAdmin,
+  ensureDexManager
 } from "./test-utils";
 import { PublicKey, Keypair, SendTransactionError } from "@solana/web3.js";
 
@@ -19,19 +21,20 @@ describe("rust-dex", () => {
 
   let mint: PublicKey;
   let user: Keypair;
-  let vault: Keypair;
+  let admin: Keypair;
   let userTokenAccount: PublicKey;
   let vaultTokenAccount: PublicKey;
 
   before(async () => {
     const mintAuthority = await createFundedUser(provider);
     user = await createFundedUser(provider);
-    vault = await createFundedUser(provider);
+    admin = dexAdmin(provider);
+    await ensureDexManager(program, admin);
 
     mint = await createTokenMint(provider.connection, mintAuthority);
     userTokenAccount = await createUserTokenAccount(provider.connection, user, mint);
     
-    const { vaultTokenAccount: vaultTokenAcc } = await registerVaultTokenLedger(program, vault, mint);
+    const { vaultTokenAccount: vaultTokenAcc } = await registerVaultTokenLedger(program, admin, mint);
     vaultTokenAccount = vaultTokenAcc;
     
     await registerUser(program, user);
@@ -0,0 +1,150 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { RustDex } from "../target/types/rust_dex";
+import { PublicKey, Keypair } from "@solana/web3.js";
+import { expect } from "chai";
+import {
+  createFundedUser,
+  createTokenMint,
+  registerVaultTokenLedger,
+  registerUser,
+  placeLimitOrder,
+  dexAdmin,
+  marketEventsPda,
+  marketPda,
+  fundUser,
+  ledgerPda,
+  pairPda,
+  registerTokenPair,
+  resetDexManager,
+  orderAccounts,
+} from "./test-utils";
+
+describe("rust-dex: swap-route", () => {
+  anchor.setProvider(anchor.AnchorProvider.env());
+  const program = anchor.workspace.rustDex as Program<RustDex>;
+  const provider = anchor.getProvider() as anchor.AnchorProvider;
+
+  let mintAuthority: Keypair;
+  let maker: Keypair;
+  let taker: Keypair;
+  let admin: Keypair;
+
+  // Route: tokenA -> tokenB -> tokenC, there is no A/C market
+  let mints: PublicKey[];
+  let dexManagerPda: PublicKey;
+
+  const INITIAL_AMOUNT = 1000000;
+  const DEPOSIT_AMOUNT = 100000;
+
+  async function balances(mint: PublicKey, user: Keypair) {
+    const ledger = await program.account.individualTokenLedgerAccount.fetch(ledgerPda(program, mint, user));
+    return { available: ledger.availableBalance.toNumber(), locked: ledger.lockedBalance.toNumber() };
+  }
+
+  function swapRemainingAccounts(path: PublicKey[], makers: Keypair[]) {
+    const accounts = [];
+    for (let i = 0; i < path.length - 1; i++) {
+      accounts.push({ pubkey: pairPda(program, path[i], path[i + 1]), isSigner: false, isWritable: true });
+      accounts.push({ pubkey: pairPda(program, path[i + 1], path[i]), isSigner: false, isWritable: true });
+      accounts.push({ pubkey: marketPda(program, path[i], path[i + 1]), isSigner: false, isWritable: true });
+      accounts.push({ pubkey: marketEventsPda(program, path[i], path[i + 1]), isSigner: false, isWritable: true });
+    }
+    for (const user of makers) {
+      for (const mint of path) {
+        accounts.push({ pubkey: ledgerPda(program, mint, user), isSigner: false, isWritable: true });
+      }
+    }
+    return accounts;
+  }
+
+  before(async () => {
+    mintAuthority = await createFundedUser(provider);
+    maker = await createFundedUser(provider);
+    taker = await createFundedUser(provider);
+    admin = dexAdmin(provider);
+
+    mints = [];
+    for (let i = 0; i < 3; i++) {
+      mints.push(await createTokenMint(provider.connection, mintAuthority, 6));
+    }
+
+    dexManagerPda = await resetDexManager(program, admin);
+
+    // A/B and B/C markets only
+    for (const [base, quote] of [[mints[0], mints[1]], [mints[1], mints[2]]]) {
+      await registerTokenPair(program, admin, base, quote);
+    }
+
+    await registerUser(program, maker);
+    await registerUser(program, taker);
+    for (const mint of mints) {
+      const { vaultTokenAccount, vaultTokenLedgerPda } = await registerVaultTokenLedger(program, admin, mint);
+      for (const user of [maker, taker]) {
+        await fundUser(program, mintAuthority, user, mint, { vaultTokenAccount, vaultTokenLedgerPda }, INITIAL_AMOUNT, DEPOSIT_AMOUNT);
+      }
+    }
+
+    // Maker bids 10 A at 2 B each, and 20 B at 3 C each
+    const hops = [[mints[0], mints[1], 2, 10], [mints[1], mints[2], 3, 20]];
+    for (const [base, quote, price, amount] of hops as [PublicKey, PublicKey, number, number][]) {
+      await placeLimitOrder(program, maker, base, quote, "buy", price, amount, ...orderAccounts(program, maker, base, quote));
+    }
+  });
+
+  async function swapRoute(path: PublicKey[], amountIn: number, minAmountOut: number) {
+    await program.methods
+      .swapRoute(path, new anchor.BN(amountIn), new anchor.BN(minAmountOut))
+      .accountsPartial({
+        dexManager: dexManagerPda,
+        userTokenInLedger: ledgerPda(program, path[0], taker),
+        userTokenOutLedger: ledgerPda(program, path[path.length - 1], taker),
+        user: taker.publicKey,
+      })
+      .remainingAccounts(swapRemainingAccounts(path, [maker]))
+      .signers([taker])
+      .rpc();
+  }
+
+  it("rejects a route whose output is below min_amount_out", async () => {
+    try {
+      await swapRoute(mints, 10, 61);
+      expect.fail("swap should have failed");
+    } catch (error) {
+      expect(error.message).to.include("SlippageExceeded");
+    }
+  });
+
+  it("swaps A to C through the B market in one instruction", async () => {
+    const takerA = await balances(mints[0], taker);
+    const takerB = await balances(mints[1], taker);
+    const takerC = await balances(mints[2], taker);
+    const makerA = await balances(mints[0], maker);
+    const makerB = await balances(mints[1], maker);
+    const makerC = await balances(mints[2], maker);
+
+    await swapRoute(mints, 10, 60);
+
+    // Taker sold 10 A and received 60 C, the intermediate B never touched its ledger
+    expect((await balances(mints[0], taker)).available).to.equal(takerA.available - 10);
+    expect((await balances(mints[1], taker))).to.deep.equal(takerB);
+    expect((await balances(mints[2], taker)).available).to.equal(takerC.available + 60);
+
+    // Maker paid 20 B for 10 A, then 60 C for 20 B, all settled in place
+    expect((await balances(mints[0], maker)).available).to.equal(makerA.available + 10);
+    expect((await balances(mints[1], maker)).locked).to.equal(makerB.locked - 20);
+    expect((await balances(mints[1], maker)).available).to.equal(makerB.available + 20);
+    expect((await balances(mints[2], maker)).locked).to.equal(makerC.locked - 60);
+  });
+
+  it("fails atomically when a hop cannot be filled", async () => {
+    const takerA = await balances(mints[0], taker);
+    try {
+      await swapRoute(mints, 10, 0);
+      expect.fail("swap should have failed");
+    } catch (error) {
+      expect(error.message).to.include("InsufficientLiquidity");
+    }
+    expect(await balances(mints[0], taker)).to.deep.equal(takerA);
+  });
+});
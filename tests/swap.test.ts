@@ -0,0 +1,185 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { RustDex } from "../target/types/rust_dex";
+import { getAccount, mintTo, TOKEN_PROGRAM_ID } from "@solana/spl-token";
+import { PublicKey, Keypair } from "@solana/web3.js";
+import { expect } from "chai";
+import {
+  createFundedUser,
+  createTokenMint,
+  createUserTokenAccount,
+  registerVaultTokenLedger,
+  registerUser,
+  registerUserTokenLedger,
+  depositTokens,
+  placeLimitOrder,
+  dexAdmin,
+  marketEventsPda,
+  marketPda,
+  ledgerPda,
+  pairPda,
+  registerTokenPair,
+  resetDexManager,
+  userPda,
+} from "./test-utils";
+
+describe("rust-dex: swap", () => {
+  anchor.setProvider(anchor.AnchorProvider.env());
+  const program = anchor.workspace.rustDex as Program<RustDex>;
+  const provider = anchor.getProvider() as anchor.AnchorProvider;
+
+  let mintAuthority: Keypair;
+  let maker: Keypair;
+  let trader: Keypair;
+  let admin: Keypair;
+
+  let baseMint: PublicKey;
+  let quoteMint: PublicKey;
+  let vaultTokenAccounts: Map<string, PublicKey>;
+  let traderBaseTokenAccount: PublicKey;
+  let traderQuoteTokenAccount: PublicKey;
+
+  let dexManagerPda: PublicKey;
+
+  const INITIAL_AMOUNT = 1000000;
+  const DEPOSIT_AMOUNT = 100000;
+
+  async function walletBalance(tokenAccount: PublicKey) {
+    return Number((await getAccount(provider.connection, tokenAccount)).amount);
+  }
+
+  before(async () => {
+    mintAuthority = await createFundedUser(provider);
+    maker = await createFundedUser(provider);
+    trader = await createFundedUser(provider);
+    admin = dexAdmin(provider);
+
+    baseMint = await createTokenMint(provider.connection, mintAuthority, 9);
+    quoteMint = await createTokenMint(provider.connection, mintAuthority, 6);
+
+    dexManagerPda = await resetDexManager(program, admin);
+
+    await registerTokenPair(program, admin, baseMint, quoteMint);
+
+    // Only the maker is a DEX user, the trader swaps straight from its wallet
+    await registerUser(program, maker);
+    vaultTokenAccounts = new Map();
+    for (const mint of [baseMint, quoteMint]) {
+      const { vaultTokenAccount, vaultTokenLedgerPda } = await registerVaultTokenLedger(program, admin, mint);
+      vaultTokenAccounts.set(mint.toBase58(), vaultTokenAccount);
+      const makerTokenAccount = await createUserTokenAccount(provider.connection, maker, mint);
+      await mintTo(provider.connection, mintAuthority, mint, makerTokenAccount, mintAuthority, INITIAL_AMOUNT);
+      await registerUserTokenLedger(program, maker, mint, makerTokenAccount);
+      await depositTokens(program, maker, mint, DEPOSIT_AMOUNT, makerTokenAccount, vaultTokenAccount, vaultTokenLedgerPda, ledgerPda(program, mint, maker));
+    }
+
+    traderBaseTokenAccount = await createUserTokenAccount(provider.connection, trader, baseMint);
+    traderQuoteTokenAccount = await createUserTokenAccount(provider.connection, trader, quoteMint);
+    await mintTo(provider.connection, mintAuthority, baseMint, traderBaseTokenAccount, mintAuthority, INITIAL_AMOUNT);
+    await mintTo(provider.connection, mintAuthority, quoteMint, traderQuoteTokenAccount, mintAuthority, INITIAL_AMOUNT);
+
+    // Maker bids 10 base at 100 quote and asks 10 base at 110 quote
+    for (const [side, price] of [["buy", 100], ["sell", 110]] as const) {
+      await placeLimitOrder(
+        program,
+        maker,
+        baseMint,
+        quoteMint,
+        side,
+        price,
+        10,
+        dexManagerPda,
+        pairPda(program, baseMint, quoteMint),
+        pairPda(program, quoteMint, baseMint),
+        userPda(program, "order_events", maker),
+        ledgerPda(program, baseMint, maker),
+        ledgerPda(program, quoteMint, maker),
+        userPda(program, "user_orderbook", maker)
+      );
+    }
+  });
+
+  function swapAccounts(tokenIn: PublicKey, tokenOut: PublicKey, userIn: PublicKey, userOut: PublicKey) {
+    const [vaultInLedger] = PublicKey.findProgramAddressSync(
+      [Buffer.from("vault_token_ledger"), tokenIn.toBuffer()],
+      program.programId
+    );
+    const [vaultOutLedger] = PublicKey.findProgramAddressSync(
+      [Buffer.from("vault_token_ledger"), tokenOut.toBuffer()],
+      program.programId
+    );
+    const [vaultOutAuthority] = PublicKey.findProgramAddressSync(
+      [Buffer.from("vault_token_account"), tokenOut.toBuffer()],
+      program.programId
+    );
+    return {
+      inOutQueue: pairPda(program, tokenIn, tokenOut),
+      outInQueue: pairPda(program, tokenOut, tokenIn),
+      market: marketPda(program, baseMint, quoteMint),
+      marketEvents: marketEventsPda(program, baseMint, quoteMint),
+      dexManager: dexManagerPda,
+      vaultInLedger,
+      vaultInTokenAccount: vaultTokenAccounts.get(tokenIn.toBase58()),
+      vaultOutLedger,
+      vaultOutAuthority,
+      vaultOutTokenAccount: vaultTokenAccounts.get(tokenOut.toBase58()),
+      userInTokenAccount: userIn,
+      userOutTokenAccount: userOut,
+      user: trader.publicKey,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    };
+  }
+
+  const makerLedgers = () => [baseMint, quoteMint].map((mint) => ({
+    pubkey: ledgerPda(program, mint, maker),
+    isSigner: false,
+    isWritable: true,
+  }));
+
+  it("swaps an exact amount of base into quote from the wallet", async () => {
+    const baseBefore = await walletBalance(traderBaseTokenAccount);
+    const quoteBefore = await walletBalance(traderQuoteTokenAccount);
+    const makerBase = await program.account.individualTokenLedgerAccount.fetch(ledgerPda(program, baseMint, maker));
+
+    await program.methods
+      .swapExactIn(baseMint, quoteMint, new anchor.BN(5), new anchor.BN(500))
+      .accountsPartial(swapAccounts(baseMint, quoteMint, traderBaseTokenAccount, traderQuoteTokenAccount))
+      .remainingAccounts(makerLedgers())
+      .signers([trader])
+      .rpc();
+
+    expect(await walletBalance(traderBaseTokenAccount)).to.equal(baseBefore - 5);
+    expect(await walletBalance(traderQuoteTokenAccount)).to.equal(quoteBefore + 500);
+    const makerBaseAfter = await program.account.individualTokenLedgerAccount.fetch(ledgerPda(program, baseMint, maker));
+    expect(makerBaseAfter.availableBalance.toNumber()).to.equal(makerBase.availableBalance.toNumber() + 5);
+  });
+
+  it("swaps quote into an exact amount of base", async () => {
+    const baseBefore = await walletBalance(traderBaseTokenAccount);
+    const quoteBefore = await walletBalance(traderQuoteTokenAccount);
+
+    await program.methods
+      .swapExactOut(quoteMint, baseMint, new anchor.BN(5), new anchor.BN(550))
+      .accountsPartial(swapAccounts(quoteMint, baseMint, traderQuoteTokenAccount, traderBaseTokenAccount))
+      .remainingAccounts(makerLedgers())
+      .signers([trader])
+      .rpc();
+
+    expect(await walletBalance(traderBaseTokenAccount)).to.equal(baseBefore + 5);
+    expect(await walletBalance(traderQuoteTokenAccount)).to.equal(quoteBefore - 550);
+  });
+
+  it("rejects a swap whose cost exceeds max_amount_in", async () => {
+    try {
+      await program.methods
+        .swapExactOut(quoteMint, baseMint, new anchor.BN(5), new anchor.BN(549))
+        .accountsPartial(swapAccounts(quoteMint, baseMint, traderQuoteTokenAccount, traderBaseTokenAccount))
+        .remainingAccounts(makerLedgers())
+        .signers([trader])
+        .rpc();
+      expect.fail("swap should have failed");
+    } catch (error) {
+      expect(error.message).to.include("MaxAmountInExceeded");
+    }
+  });
+});
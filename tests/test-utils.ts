@@ -4,10 +4,11 @@ import { RustDex } from "../target/types/rust_dex";
 import { 
   createMint,
   createAssociatedTokenAccount,
+  mintTo,
   TOKEN_PROGRAM_ID
 } from "@solana/spl-token";
-import { PublicKey, Keypair, SystemProgram } from "@solana/web3.js";
-import { use } from "chai";
+import { AccountMeta, PublicKey, Keypair, SystemProgram } from "@solana/web3.js";
+import { expect } from "chai";
 
 const LAMPORTS_PER_SOL = anchor.web3.LAMPORTS_PER_SOL;
 
@@ -48,9 +49,83 @@ export async function createUserTokenAccount(
   );
 }
 
+/** The DEX admin, the provider wallet, which initializes the DEX in every suite. */
+export function dexAdmin(provider: anchor.AnchorProvider): Keypair {
+  return (provider.wallet as anchor.Wallet).payer;
+}
+
+/** Initializes the DEX with `admin` unless an earlier suite already did. */
+export async function ensureDexManager(program: Program<RustDex>, admin: Keypair) {
+  const [dexManagerPda] = PublicKey.findProgramAddressSync(
+    [Buffer.from("dex_manager")],
+    program.programId
+  );
+  if (await program.account.dexManager.fetchNullable(dexManagerPda)) {
+    return;
+  }
+  await program.methods.initialize()
+    .accountsPartial({
+      dexManager: dexManagerPda,
+      user: admin.publicKey,
+      systemProgram: SystemProgram.programId,
+    })
+    .signers([admin])
+    .rpc();
+}
+
+/** Closes the DEX manager an earlier suite left behind and initializes a fresh one with `admin`. */
+export async function resetDexManager(program: Program<RustDex>, admin: Keypair) {
+  const [dexManagerPda] = PublicKey.findProgramAddressSync(
+    [Buffer.from("dex_manager")],
+    program.programId
+  );
+  try {
+    await program.methods.closeDexManager()
+      .accountsPartial({
+        dexManager: dexManagerPda,
+        admin: admin.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([admin])
+      .rpc();
+  } catch (error) {
+    console.log("No existing dex_manager to close:", error.message);
+  }
+  // Whoever initializes the DEX becomes its admin
+  await program.methods.initialize()
+    .accountsPartial({
+      dexManager: dexManagerPda,
+      user: admin.publicKey,
+      systemProgram: SystemProgram.programId,
+    })
+    .signers([admin])
+    .rpc();
+  return dexManagerPda;
+}
+
+export async function registerTokenPair(
+  program: Program<RustDex>,
+  admin: Keypair,
+  baseMint: PublicKey,
+  quoteMint: PublicKey
+) {
+  await program.methods
+    .registerTokenPair(baseMint, quoteMint)
+    .accountsPartial({
+      market: marketPda(program, baseMint, quoteMint),
+      marketEvents: marketEventsPda(program, baseMint, quoteMint),
+      admin: admin.publicKey,
+      systemProgram: SystemProgram.programId,
+      tokenPair: pairPda(program, baseMint, quoteMint),
+      oppositePair: pairPda(program, quoteMint, baseMint),
+    })
+    .signers([admin])
+    .rpc();
+}
+
 export async function registerVaultTokenLedger(
   program: Program<RustDex>,
-  vault: Keypair,
+  admin: Keypair,
   mint: PublicKey
 ) {
   const [vaultTokenLedgerPda] = PublicKey.findProgramAddressSync(
@@ -72,11 +147,11 @@ export async function registerVaultTokenLedger(
       vaultTokenAuthority: vaultTokenAuthorityPda,
       mintAccount: mint,
       vaultTokenAccount: vaultTokenAccountKeypair.publicKey,
-      user: vault.publicKey,
+      admin: admin.publicKey,
       systemProgram: SystemProgram.programId,
       tokenProgram: TOKEN_PROGRAM_ID,
     })
-    .signers([vault, vaultTokenAccountKeypair])
+    .signers([admin, vaultTokenAccountKeypair])
     .rpc();
 
   return {
@@ -85,9 +160,74 @@ export async function registerVaultTokenLedger(
   };
 }
 
+/** The ledger a mint's trading fees accrue to, created along with its vault. */
+export function feeLedgerPda(program: Program<RustDex>, mint: PublicKey) {
+  return PublicKey.findProgramAddressSync(
+    [Buffer.from("fee_ledger"), mint.toBuffer()],
+    program.programId
+  )[0];
+}
+
+/** A market's mints in ascending order, the order its accounts are derived with. */
+export function marketMints(baseMint: PublicKey, quoteMint: PublicKey) {
+  return Buffer.compare(baseMint.toBuffer(), quoteMint.toBuffer()) < 0 ? [baseMint, quoteMint] : [quoteMint, baseMint];
+}
+
+export function marketPda(program: Program<RustDex>, baseMint: PublicKey, quoteMint: PublicKey) {
+  return PublicKey.findProgramAddressSync(
+    [Buffer.from("market"), ...marketMints(baseMint, quoteMint).map((mint) => mint.toBuffer())],
+    program.programId
+  )[0];
+}
+
+export function marketEventsPda(program: Program<RustDex>, baseMint: PublicKey, quoteMint: PublicKey) {
+  return PublicKey.findProgramAddressSync(
+    [Buffer.from("market_events"), ...marketMints(baseMint, quoteMint).map((mint) => mint.toBuffer())],
+    program.programId
+  )[0];
+}
+
+export function crankPoolPda(program: Program<RustDex>, baseMint: PublicKey, quoteMint: PublicKey) {
+  return PublicKey.findProgramAddressSync(
+    [Buffer.from("crank_pool"), ...marketMints(baseMint, quoteMint).map((mint) => mint.toBuffer())],
+    program.programId
+  )[0];
+}
+
+/** The book of orders buying `buyToken` with `sellToken`. */
+export function pairPda(program: Program<RustDex>, buyToken: PublicKey, sellToken: PublicKey) {
+  return PublicKey.findProgramAddressSync(
+    [Buffer.from("token_pair"), buyToken.toBuffer(), sellToken.toBuffer()],
+    program.programId
+  )[0];
+}
+
+export function ledgerPda(program: Program<RustDex>, mint: PublicKey, user: Keypair) {
+  return PublicKey.findProgramAddressSync(
+    [Buffer.from("individual_token_ledger"), mint.toBuffer(), user.publicKey.toBuffer()],
+    program.programId
+  )[0];
+}
+
+/** A per-user account `register_user` creates, e.g. `order_events` or `user_orderbook`. */
+export function userPda(program: Program<RustDex>, seed: string, user: Keypair) {
+  return PublicKey.findProgramAddressSync(
+    [Buffer.from(seed), user.publicKey.toBuffer()],
+    program.programId
+  )[0];
+}
+
+export function feeTiersPda(program: Program<RustDex>, quoteMint: PublicKey) {
+  return PublicKey.findProgramAddressSync(
+    [Buffer.from("fee_tiers"), quoteMint.toBuffer()],
+    program.programId
+  )[0];
+}
+
 export async function registerUser(
   program: Program<RustDex>,
-  user: Keypair
+  user: Keypair,
+  referrer: PublicKey | null = null
 ) {
   const [individualLedgerPda] = PublicKey.findProgramAddressSync(
     [Buffer.from("user_ledger"), user.publicKey.toBuffer()],
@@ -105,7 +245,7 @@ export async function registerUser(
   );
 
   await program.methods
-    .registerUser()
+    .registerUser(referrer)
     .accountsPartial({
       individualLedger: individualLedgerPda,
       userOrderBook: userOrderbookPda,
@@ -152,6 +292,30 @@ export async function registerUserTokenLedger(
   return userTokenLedgerPda;
 }
 
+/**
+ * Mints `initialAmount` of `mint` to a new token account of `user`, registers
+ * the user's token ledger and deposits `depositAmount` into the vault.
+ */
+export async function fundUser(
+  program: Program<RustDex>,
+  mintAuthority: Keypair,
+  user: Keypair,
+  mint: PublicKey,
+  vault: { vaultTokenAccount: PublicKey; vaultTokenLedgerPda: PublicKey },
+  initialAmount: number,
+  depositAmount: number
+) {
+  const connection = program.provider.connection;
+  const userTokenAccount = await createUserTokenAccount(connection, user, mint);
+  await mintTo(connection, mintAuthority, mint, userTokenAccount, mintAuthority, initialAmount);
+  await registerUserTokenLedger(program, user, mint, userTokenAccount);
+  await depositTokens(
+    program, user, mint, depositAmount, userTokenAccount, vault.vaultTokenAccount, vault.vaultTokenLedgerPda,
+    ledgerPda(program, mint, user)
+  );
+  return userTokenAccount;
+}
+
 export async function depositTokens(
   program: Program<RustDex>,
   user: Keypair,
@@ -176,12 +340,36 @@ export async function depositTokens(
     .rpc();
 }
 
+export type Side = "buy" | "sell";
+
+/** Encodes a side as the program's `OrderSide` enum argument. */
+export function orderSide(side: Side) {
+  return side === "buy" ? { buy: {} } : { sell: {} };
+}
+
+/** The accounts `placeLimitOrder` and `placeMarketOrder` take after `amount`, for `user` on the base/quote market. */
+export function orderAccounts(program: Program<RustDex>, user: Keypair, baseMint: PublicKey, quoteMint: PublicKey) {
+  const [dexManagerPda] = PublicKey.findProgramAddressSync(
+    [Buffer.from("dex_manager")],
+    program.programId
+  );
+  return [
+    dexManagerPda,
+    pairPda(program, baseMint, quoteMint),
+    pairPda(program, quoteMint, baseMint),
+    userPda(program, "order_events", user),
+    ledgerPda(program, baseMint, user),
+    ledgerPda(program, quoteMint, user),
+    userPda(program, "user_orderbook", user),
+  ] as const;
+}
+
 export async function placeLimitOrder(
   program: Program<RustDex>,
   fromUser: Keypair,
   baseMint: PublicKey,
   quoteMint: PublicKey,
-  side: "buy" | "sell",
+  side: Side,
   price: number,
   amount: number,
   dexManagerPda: PublicKey,
@@ -191,10 +379,13 @@ export async function placeLimitOrder(
   userBaseTokenLedgerPda: PublicKey,
   userQuoteTokenLedgerPda: PublicKey,
   userOrderbookPda: PublicKey,
+  makerLedgers: AccountMeta[] = [],
 ) {
-  await program.methods
-    .placeLimitOrder(baseMint, quoteMint, side, price, new anchor.BN(amount))
+  return await program.methods
+    .placeLimitOrder(baseMint, quoteMint, orderSide(side), price, new anchor.BN(amount))
     .accountsPartial({
+      market: marketPda(program, baseMint, quoteMint),
+      marketEvents: marketEventsPda(program, baseMint, quoteMint),
       baseQuoteQueue: buyBaseQueuePda,
       quoteBaseQueue: sellBaseQueuePda,
       dexManager: dexManagerPda,
@@ -205,6 +396,7 @@ export async function placeLimitOrder(
       user: fromUser.publicKey,
       systemProgram: SystemProgram.programId,
     })
+    .remainingAccounts(makerLedgers)
     .signers([fromUser])
     .rpc();
 }
@@ -214,7 +406,7 @@ export async function placeMarketOrder(
   fromUser: Keypair,
   baseMint: PublicKey,
   quoteMint: PublicKey,
-  side: "buy" | "sell",
+  side: Side,
   amount: number,
   dexManagerPda: PublicKey,
   buyBaseQueuePda: PublicKey,
@@ -223,10 +415,13 @@ export async function placeMarketOrder(
   userBaseTokenLedgerPda: PublicKey,
   userQuoteTokenLedgerPda: PublicKey,
   userOrderbookPda: PublicKey,
+  makerLedgers: AccountMeta[] = [],
 ) {
-  await program.methods
-    .placeMarketOrder(baseMint, quoteMint, side, new anchor.BN(amount))
+  return await program.methods
+    .placeMarketOrder(baseMint, quoteMint, orderSide(side), new anchor.BN(amount))
     .accountsPartial({
+      market: marketPda(program, baseMint, quoteMint),
+      marketEvents: marketEventsPda(program, baseMint, quoteMint),
       baseQuoteQueue: buyBaseQueuePda,
       quoteBaseQueue: sellBaseQueuePda,
       dexManager: dexManagerPda,
@@ -237,6 +432,7 @@ export async function placeMarketOrder(
       user: fromUser.publicKey,
       systemProgram: SystemProgram.programId,
     })
+    .remainingAccounts(makerLedgers)
     .signers([fromUser])
     .rpc();
 }
@@ -248,15 +444,31 @@ export async function cancelOrder(
   baseQuoteQueuePda: PublicKey,
   userOrderbookPda: PublicKey,
   userSellTokenLedgerPda: PublicKey,
+  baseMint: PublicKey,
+  quoteMint: PublicKey,
 ) {
-  await program.methods
+  return await program.methods
     .cancelOrder(new anchor.BN(orderId))
     .accountsPartial({
       baseQuoteQueue: baseQuoteQueuePda,
+      market: marketPda(program, baseMint, quoteMint),
+      marketEvents: marketEventsPda(program, baseMint, quoteMint),
       userOrderBook: userOrderbookPda,
       userSellTokenLedger: userSellTokenLedgerPda,
       user: user.publicKey,
     })
     .signers([user])
     .rpc();
-}
\ No newline at end of file
+}
+
+/** Expects `promise` to fail with the error `code`. */
+export async function expectError(promise: Promise<unknown>, code: string) {
+  let failure: Error | undefined;
+  try {
+    await promise;
+  } catch (error) {
+    failure = error;
+  }
+  expect(failure, `expected ${code}`).to.not.equal(undefined);
+  expect(failure.message).to.include(code);
+}
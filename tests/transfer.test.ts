@@ -11,7 +11,9 @@ import {
   registerVaultTokenLedger,
   registerUser,
   registerUserTokenLedger,
-  depositTokens
+  depositTokens,
+  dexAdmin,
+  ensureDexManager
 } from "./test-utils";
 
 describe("rust-dex: transfer", () => {
@@ -22,7 +24,7 @@ describe("rust-dex: transfer", () => {
   let mintAuthority: Keypair;
   let user1: Keypair;
   let user2: Keypair;
-  let vault: Keypair;
+  let admin: Keypair;
   let mint: PublicKey;
   let user1TokenAccount: PublicKey;
   let user2TokenAccount: PublicKey;
@@ -39,7 +41,8 @@ describe("rust-dex: transfer", () => {
     mintAuthority = await createFundedUser(provider);
     user1 = await createFundedUser(provider);
     user2 = await createFundedUser(provider);
-    vault = await createFundedUser(provider);
+    admin = dexAdmin(provider);
+    await ensureDexManager(program, admin);
 
     mint = await createTokenMint(provider.connection, mintAuthority);
     user1TokenAccount = await createUserTokenAccount(provider.connection, user1, mint);
@@ -50,7 +53,7 @@ describe("rust-dex: transfer", () => {
     await mintTo(provider.connection, mintAuthority, mint, user2TokenAccount, mintAuthority, INITIAL_AMOUNT);
 
     // Register vault and users
-    const vaultResult = await registerVaultTokenLedger(program, vault, mint);
+    const vaultResult = await registerVaultTokenLedger(program, admin, mint);
     vaultTokenAccount = vaultResult.vaultTokenAccount;
     vaultTokenLedgerPda = vaultResult.vaultTokenLedgerPda;
 
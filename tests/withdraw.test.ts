@@ -11,7 +11,9 @@ import {
   registerVaultTokenLedger,
   registerUser,
   registerUserTokenLedger,
-  depositTokens
+  depositTokens,
+  dexAdmin,
+  ensureDexManager
 } from "./test-utils";
 
 describe("rust-dex: withdraw", () => {
@@ -22,7 +24,7 @@ describe("rust-dex: withdraw", () => {
   let mint: PublicKey;
   let user: Keypair;
   let anotherUser: Keypair;
-  let vault: Keypair;
+  let admin: Keypair;
   let mintAuthority: Keypair;
   let userTokenAccount: PublicKey;
   let anotherUserTokenAccount: PublicKey;
@@ -37,14 +39,15 @@ describe("rust-dex: withdraw", () => {
     mintAuthority = await createFundedUser(provider);
     user = await createFundedUser(provider);
     anotherUser = await createFundedUser(provider);
-    vault = await createFundedUser(provider);
+    admin = dexAdmin(provider);
+    await ensureDexManager(program, admin);
 
     mint = await createTokenMint(provider.connection, mintAuthority);
     
     userTokenAccount = await createUserTokenAccount(provider.connection, user, mint);
     anotherUserTokenAccount = await createUserTokenAccount(provider.connection, anotherUser, mint);
     
-    const vaultResult = await registerVaultTokenLedger(program, vault, mint);
+    const vaultResult = await registerVaultTokenLedger(program, admin, mint);
     vaultTokenAccount = vaultResult.vaultTokenAccount;
     vaultTokenLedgerPda = vaultResult.vaultTokenLedgerPda;
     
@@ -117,6 +120,21 @@ describe("rust-dex: withdraw", () => {
 
     expect(BigInt(afterUser.value.amount)).to.equal(BigInt(beforeUser.value.amount) + BigInt(amount));
     expect(BigInt(afterVault.value.amount)).to.equal(BigInt(beforeVault.value.amount) - BigInt(amount));
+
+    // Both ledgers follow the tokens out of the vault
+    const userLedger = await program.account.individualTokenLedgerAccount.fetch(userTokenLedgerPda);
+    const vaultLedger = await program.account.vaultTokenLedgerAccount.fetch(vaultTokenLedgerPda);
+    expect(BigInt(userLedger.availableBalance.toString())).to.equal(BigInt((depositAmount - withdrawAmount) * 10 ** 9));
+    expect(BigInt(vaultLedger.totalBalance.toString())).to.equal(BigInt(afterVault.value.amount));
+  });
+
+  it("Should pass the vault audit after deposits and withdrawals", async () => {
+    await program.methods.auditVault(mint)
+      .accountsPartial({
+        vaultTokenLedger: vaultTokenLedgerPda,
+        vaultTokenAccount,
+      })
+      .rpc();
   });
 
   it("Should fail when withdrawing more than vault balance", async () => {
@@ -226,4 +244,24 @@ describe("rust-dex: withdraw", () => {
     }
     expect(errorCaught).to.be.true;
   });
+
+  it("Should report untracked tokens in the vault as the audit's surplus", async () => {
+    await mintTo(provider.connection, mintAuthority, mint, vaultTokenAccount, mintAuthority, 1);
+
+    const signature = await program.methods.auditVault(mint)
+      .accountsPartial({
+        vaultTokenLedger: vaultTokenLedgerPda,
+        vaultTokenAccount,
+      })
+      .rpc();
+
+    await provider.connection.confirmTransaction(signature, "confirmed");
+    const tx = await provider.connection.getTransaction(signature, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    const parser = new anchor.EventParser(program.programId, program.coder);
+    const [audit]: any[] = [...parser.parseLogs(tx.meta.logMessages)].filter((event) => event.name === "vaultAuditedEvent");
+    expect(audit.data.surplus.toNumber()).to.equal(1);
+  });
 });